@@ -0,0 +1,6 @@
+// Load-bank diagnostics without the app: `lbctl help`.
+// No webview libraries needed: `cargo build --bin lbctl --no-default-features`.
+
+fn main() -> std::process::ExitCode {
+    ewt_lib::run_cli()
+}
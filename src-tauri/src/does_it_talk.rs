@@ -2,20 +2,25 @@
 
 //use encoding_rs::WINDOWS_1252;
 use serde::Serialize;
+#[cfg(feature = "gui")]
+use std::sync::Mutex;
 use std::{
     borrow::Cow,
     io::{Read, Write},
-    sync::Mutex,
     time::{Duration, Instant},
 };
-use tauri::State;
+#[cfg(feature = "gui")]
+use tauri::{AppHandle, State};
 
+use crate::lb_protocol::{frame_len, to_hex, Frame, FrameDecoder, FrameFields, FRAME_LEN};
+#[cfg(feature = "gui")]
+use crate::lb_serial::{LoadBankSerialState, SerialConfig};
+
+#[cfg(feature = "gui")]
 pub struct SerialState {
     pub port: Mutex<Option<Box<dyn serialport::SerialPort>>>,
 }
 
-const FRAME_LEN: usize = 15;
-
 #[derive(Serialize)]
 pub struct Roundtrip {
     sent_bytes: Vec<u8>,
@@ -38,8 +43,15 @@ pub struct Roundtrip {
 
     sent_debug_utf8_valid: bool,
     recv_debug_utf8_valid: bool,
+
+    // Decoded load-bank frames
+    sent_frame_error: Option<String>,
+    recv_frames: Vec<FrameFields>,
+    recv_frame_errors: Vec<String>,
+    recv_garbage_bytes: u64,
 }
 
+#[cfg(feature = "gui")]
 #[tauri::command]
 pub fn list_ports() -> Vec<String> {
     serialport::available_ports()
@@ -47,30 +59,41 @@ pub fn list_ports() -> Vec<String> {
         .unwrap_or_default()
 }
 
+// `config` omitted => the port's saved config (or defaults); given => saved for the port
+#[cfg(feature = "gui")]
 #[tauri::command]
-pub fn connect(state: State<SerialState>, port_name: String, baud: u32) -> Result<(), String> {
+pub fn connect(
+    app: AppHandle,
+    state: State<SerialState>,
+    serial_state: State<LoadBankSerialState>,
+    port_name: String,
+    baud: u32,
+    config: Option<SerialConfig>,
+) -> Result<(), String> {
+    let config = serial_state.resolve(&app, &port_name, config)?;
     eprintln!(
-        "[TAURI/COMM] connect requested: port={}, baud={}",
-        port_name, baud
+        "[TAURI/COMM] connect requested: port={}, baud={}, {}",
+        port_name,
+        baud,
+        config.label()
     );
-    let port = serialport::new(&port_name, baud)
-        .timeout(Duration::from_millis(100))
-        .open()
-        .map_err(|e| {
-            eprintln!("[TAURI/COMM] failed to open {}: {}", &port_name, e);
-            e.to_string()
-        })?;
+    let port = config.open(&port_name, baud).map_err(|e| {
+        eprintln!("[TAURI/COMM] failed to open {}: {}", &port_name, e);
+        e
+    })?;
     *state.port.lock().unwrap() = Some(port);
     eprintln!("[TAURI/COMM] port {} opened", &port_name);
     Ok(())
 }
 
+#[cfg(feature = "gui")]
 #[tauri::command]
 pub fn close(state: State<SerialState>) {
     *state.port.lock().unwrap() = None;
 }
 
 // Send TEXT (UTF-8) and listen
+#[cfg(feature = "gui")]
 #[tauri::command]
 pub fn test_roundtrip_text(
     state: State<SerialState>,
@@ -82,6 +105,7 @@ pub fn test_roundtrip_text(
 }
 
 // Send raw BYTES and listen
+#[cfg(feature = "gui")]
 #[tauri::command]
 pub fn test_roundtrip_bytes(
     state: State<SerialState>,
@@ -96,7 +120,15 @@ pub fn test_roundtrip_bytes(
     );
     let mut guard = state.port.lock().unwrap();
     let port = guard.as_mut().ok_or("Port not open")?;
+    roundtrip_bytes(port, data, listen_for)
+}
 
+/// `test_roundtrip_bytes` on any byte stream (also used by the `lbctl raw` CLI).
+pub fn roundtrip_bytes<P: Read + Write + ?Sized>(
+    port: &mut P,
+    data: Vec<u8>,
+    listen_for: Duration,
+) -> Result<Roundtrip, String> {
     // Drain leftovers
     let mut junk = [0u8; 256];
     while let Ok(n) = port.read(&mut junk) {
@@ -128,6 +160,23 @@ pub fn test_roundtrip_bytes(
     let (sent_debug_utf8, sent_debug_utf8_valid) = decode_utf8_with_validity(sent_tail);
     let (recv_debug_utf8, recv_debug_utf8_valid) = decode_utf8_with_validity(recv_tail);
 
+    let sent_frame_error = Frame::from_bytes(sent_frame)
+        .and_then(|f| f.decode())
+        .err()
+        .map(|e| e.to_string());
+
+    let mut decoder = FrameDecoder::new();
+    let mut recv_frames = vec![];
+    let mut recv_frame_errors = vec![];
+    for f in decoder.feed(&buf) {
+        match f.decode() {
+            Ok(fields) => recv_frames.push(fields),
+            Err(e) => recv_frame_errors.push(format!("{}: {}", f.to_hex(), e)),
+        }
+    }
+    // trailing bytes that never completed a frame count as garbage too
+    let recv_garbage_bytes = decoder.stats().garbage_bytes + decoder.buffered() as u64;
+
     eprintln!(
         "[TAURI/COMM] roundtrip_bytes done: sent={:?} recv={:?}",
         data, buf
@@ -155,18 +204,19 @@ pub fn test_roundtrip_bytes(
 
         sent_debug_utf8_valid,
         recv_debug_utf8_valid,
-    })
-}
 
-fn to_hex(data: &[u8]) -> String {
-    data.iter()
-        .map(|b| format!("{:02X}", b))
-        .collect::<Vec<_>>()
-        .join(" ")
+        sent_frame_error,
+        recv_frames,
+        recv_frame_errors,
+        recv_garbage_bytes,
+    })
 }
 
 fn split_frame_and_tail(data: &[u8]) -> (&[u8], &[u8]) {
-    let n = FRAME_LEN.min(data.len());
+    let n = data
+        .first()
+        .map_or(FRAME_LEN, |v| frame_len(*v))
+        .min(data.len());
     (&data[..n], &data[n..])
 }
 
@@ -0,0 +1,318 @@
+// Duty-cycle budget per resistor branch (port of `DutyCycleBudget` in
+// src/services/utils/setpoints.ts).
+//
+// Fed with every mask the bank reports, so it tracks real ON time rather than
+// requested ON time. The overload windows of the resistor spec (RDP4000: 7.5x
+// for 5 s, 5x for 10 s, ... per 120 s cycle) give each branch an ON-time
+// allowance at the working U2; the worker refuses masks that would exceed it
+// and opens the contactors when a closed branch runs out.
+
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use crate::lb_safety::Interlock;
+use crate::load_model::{branches_in, max_allowed_on_ms, ResistorSpec, LB_BRANCHES};
+
+const DEFAULT_CYCLE_MS: u64 = 120_000;
+
+#[derive(Clone, Copy, Debug)]
+struct Interval {
+    start: Instant,
+    end: Instant,
+}
+
+pub struct DutyCycleBudget {
+    spec: ResistorSpec,
+    cycle: Duration,
+    current_mask: u16,
+    last_change: Instant,
+    intervals_by_bit: HashMap<u16, Vec<Interval>>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BranchBudget {
+    pub id: &'static str,
+    pub mask_bit: u16,
+    pub on: bool,
+    /// Load factor at the working U2 (P_branch / P_R).
+    pub factor: f64,
+    pub used_on_ms: f64,
+    /// None => continuous rating (no limit).
+    pub allowed_on_ms: Option<f64>,
+    pub remaining_on_ms: Option<f64>,
+    /// No overload window covers this factor: the branch may not be closed.
+    pub forbidden: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BudgetSnapshot {
+    pub bank_id: String,
+    pub cycle_ms: f64,
+    pub u2_v: f64,
+    pub mask: u16,
+    pub branches: Vec<BranchBudget>,
+    /// A closed branch has no ON time left.
+    pub exhausted: bool,
+}
+
+impl DutyCycleBudget {
+    pub fn new(spec: ResistorSpec) -> Self {
+        let cycle_ms = spec
+            .overload_windows
+            .first()
+            .map_or(DEFAULT_CYCLE_MS, |w| w.cycle_ms as u64);
+        Self {
+            spec,
+            cycle: Duration::from_millis(cycle_ms),
+            current_mask: 0,
+            last_change: Instant::now(),
+            intervals_by_bit: HashMap::new(),
+        }
+    }
+
+    /// Call with every mask the bank reports (status frames).
+    pub fn set_mask(&mut self, next_mask: u16, at: Instant) {
+        if next_mask == self.current_mask {
+            return;
+        }
+        let from = self.last_change;
+        let to = at.max(from);
+        // record interval for every branch that was ON
+        for b in branches_in(self.current_mask) {
+            self.intervals_by_bit
+                .entry(b.mask_bit)
+                .or_default()
+                .push(Interval {
+                    start: from,
+                    end: to,
+                });
+        }
+        self.current_mask = next_mask;
+        self.last_change = to;
+        self.prune(to);
+    }
+
+    /// Rolling ON time of `mask_bit` within the last cycle (includes the current ON segment).
+    pub fn used_on_ms(&self, mask_bit: u16, now: Instant) -> f64 {
+        let window_start = now.checked_sub(self.cycle);
+        let clip = |start: Instant| window_start.map_or(start, |w| start.max(w));
+
+        let mut used: f64 = self
+            .intervals_by_bit
+            .get(&mask_bit)
+            .into_iter()
+            .flatten()
+            .map(|it| {
+                let end = it.end.min(now);
+                end.saturating_duration_since(clip(it.start)).as_secs_f64() * 1000.0
+            })
+            .sum();
+
+        if self.current_mask & mask_bit != 0 {
+            used += now
+                .saturating_duration_since(clip(self.last_change))
+                .as_secs_f64()
+                * 1000.0;
+        }
+        used
+    }
+
+    /// Removes intervals completely outside the rolling window.
+    pub fn prune(&mut self, now: Instant) {
+        let Some(window_start) = now.checked_sub(self.cycle) else {
+            return;
+        };
+        self.intervals_by_bit.retain(|_, arr| {
+            arr.retain(|it| it.end > window_start);
+            !arr.is_empty()
+        });
+    }
+
+    fn branch(
+        &self,
+        mask_bit: u16,
+        ohm: f64,
+        u2_v: f64,
+        now: Instant,
+    ) -> (f64, Option<Option<f64>>, f64) {
+        let p_w = u2_v * u2_v / ohm;
+        (
+            p_w / self.spec.p_r_w,
+            max_allowed_on_ms(p_w, &self.spec),
+            self.used_on_ms(mask_bit, now),
+        )
+    }
+
+    pub fn snapshot(&self, bank_id: &str, u2_v: f64, now: Instant) -> BudgetSnapshot {
+        let branches: Vec<BranchBudget> = LB_BRANCHES
+            .iter()
+            .map(|b| {
+                let (factor, allowed, used) = self.branch(b.mask_bit, b.ohm, u2_v, now);
+                let allowed_on_ms = allowed.flatten();
+                BranchBudget {
+                    id: b.id,
+                    mask_bit: b.mask_bit,
+                    on: self.current_mask & b.mask_bit != 0,
+                    factor,
+                    used_on_ms: used,
+                    allowed_on_ms,
+                    remaining_on_ms: allowed_on_ms.map(|a| (a - used).max(0.0)),
+                    forbidden: allowed.is_none(),
+                }
+            })
+            .collect();
+
+        BudgetSnapshot {
+            bank_id: bank_id.to_string(),
+            cycle_ms: self.cycle.as_secs_f64() * 1000.0,
+            u2_v,
+            mask: self.current_mask,
+            exhausted: branches
+                .iter()
+                .any(|b| b.on && (b.forbidden || b.remaining_on_ms == Some(0.0))),
+            branches,
+        }
+    }
+
+    /// Refuses masks that close a branch beyond its overload windows.
+    pub fn check(&self, mask: u16, u2_v: f64, now: Instant) -> Result<(), Interlock> {
+        for b in branches_in(mask) {
+            let (factor, allowed, used) = self.branch(b.mask_bit, b.ohm, u2_v, now);
+            match allowed {
+                None => {
+                    return Err(Interlock::Overload {
+                        branch: b.id.to_string(),
+                        factor,
+                    })
+                }
+                Some(Some(allowed_on_ms)) if used >= allowed_on_ms => {
+                    return Err(Interlock::BudgetExhausted {
+                        branch: b.id.to_string(),
+                        used_on_ms: used,
+                        allowed_on_ms,
+                    })
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// ON time `mask` may still stay closed this cycle (None => continuous).
+    pub fn remaining_on_ms(&self, mask: u16, u2_v: f64, now: Instant) -> Option<f64> {
+        branches_in(mask)
+            .filter_map(|b| {
+                let (_, allowed, used) = self.branch(b.mask_bit, b.ohm, u2_v, now);
+                allowed.flatten().map(|allowed| (allowed - used).max(0.0))
+            })
+            .reduce(f64::min)
+    }
+
+    /// Closed branch that ran out of ON time (the worker opens everything).
+    pub fn exhausted(&self, u2_v: f64, now: Instant) -> Option<Interlock> {
+        self.check(self.current_mask, u2_v, now).err()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::load_model::RDP4000;
+
+    // R4 (0.5 ohm): 100 V => 5.0x, 120 V => 7.2x, 130 V => 8.45x of P_R
+    const R4: u16 = 1 << 3;
+
+    fn at(t0: Instant, ms: u64) -> Instant {
+        t0 + Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn counts_on_time_within_the_window() {
+        let mut budget = DutyCycleBudget::new(RDP4000);
+        // not before the budget's own start
+        let t0 = Instant::now();
+        budget.set_mask(R4, t0);
+        budget.set_mask(0, at(t0, 3_000));
+        budget.set_mask(R4, at(t0, 10_000));
+        budget.set_mask(0, at(t0, 12_000));
+        assert_eq!(budget.used_on_ms(R4, at(t0, 12_000)), 5_000.0);
+        assert_eq!(budget.used_on_ms(1 << 0, at(t0, 12_000)), 0.0);
+
+        // the open segment counts up to `now`
+        budget.set_mask(R4, at(t0, 20_000));
+        assert_eq!(budget.used_on_ms(R4, at(t0, 21_500)), 6_500.0);
+        budget.set_mask(0, at(t0, 22_000));
+
+        // window [1 s, 121 s]: the first interval is clipped
+        assert_eq!(budget.used_on_ms(R4, at(t0, 121_000)), 6_000.0);
+        // window [11 s, 131 s]
+        assert_eq!(budget.used_on_ms(R4, at(t0, 131_000)), 3_000.0);
+        assert_eq!(budget.used_on_ms(R4, at(t0, 142_000)), 0.0);
+
+        budget.prune(at(t0, 142_000));
+        assert!(budget.intervals_by_bit.is_empty());
+    }
+
+    #[test]
+    fn refuses_per_overload_window() {
+        let t0 = Instant::now();
+        let budget = DutyCycleBudget::new(RDP4000);
+        // R1 (4.28 ohm) at 100 V stays under P_R: continuous
+        assert_eq!(budget.remaining_on_ms(1 << 0, 100.0, t0), None);
+        assert_eq!(budget.remaining_on_ms(R4, 50.0, t0), Some(40_000.0));
+        assert_eq!(budget.remaining_on_ms(R4, 60.0, t0), Some(20_000.0));
+        assert_eq!(budget.remaining_on_ms(R4, 100.0, t0), Some(10_000.0));
+        assert_eq!(budget.remaining_on_ms(R4, 120.0, t0), Some(5_000.0));
+        assert!(budget.check(R4, 120.0, t0).is_ok());
+        assert!(matches!(
+            budget.check(R4 | 1, 130.0, t0),
+            Err(Interlock::Overload { branch, factor }) if branch == "R4" && factor > 7.5
+        ));
+        assert!(budget.snapshot("lb", 130.0, t0).branches[3].forbidden);
+    }
+
+    #[test]
+    fn exhausts_at_the_allowance() {
+        let mut budget = DutyCycleBudget::new(RDP4000);
+        let t0 = Instant::now();
+        budget.set_mask(R4, t0);
+        assert!(budget.exhausted(100.0, at(t0, 9_999)).is_none());
+        assert!(budget.exhausted(120.0, at(t0, 5_000)).is_some());
+        assert!(matches!(
+            budget.exhausted(100.0, at(t0, 10_000)),
+            Some(Interlock::BudgetExhausted { branch, used_on_ms, allowed_on_ms })
+                if branch == "R4" && used_on_ms == 10_000.0 && allowed_on_ms == 10_000.0
+        ));
+        let snapshot = budget.snapshot("lb", 100.0, at(t0, 10_000));
+        assert!(snapshot.exhausted);
+        assert_eq!(snapshot.branches[3].remaining_on_ms, Some(0.0));
+        // other branches keep their own allowance
+        assert!(budget.check(1 << 2, 100.0, at(t0, 10_000)).is_ok());
+    }
+
+    #[test]
+    fn recovers_once_the_window_moves_on() {
+        let mut budget = DutyCycleBudget::new(RDP4000);
+        let t0 = Instant::now();
+        budget.set_mask(R4, t0);
+        budget.set_mask(0, at(t0, 10_000));
+        assert!(budget.check(R4, 100.0, at(t0, 10_000)).is_err());
+        assert!(budget.check(R4, 100.0, at(t0, 119_000)).is_err());
+
+        // window [5 s, 125 s]: half of the ON time has aged out
+        assert!(budget.check(R4, 100.0, at(t0, 125_000)).is_ok());
+        assert_eq!(
+            budget.remaining_on_ms(R4, 100.0, at(t0, 125_000)),
+            Some(5_000.0)
+        );
+        assert_eq!(
+            budget.remaining_on_ms(R4, 100.0, at(t0, 130_000)),
+            Some(10_000.0)
+        );
+    }
+}
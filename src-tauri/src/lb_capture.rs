@@ -0,0 +1,597 @@
+// Load-bank traffic capture (field diagnostics).
+//
+// Every TX/RX chunk, decoded frame, health change and link-stats sample (see
+// lb_link_stats) goes to JSONL files under `<app data>/lb-captures`, one
+// record per line:
+//   {"t":"2026-01-31T10:00:00.123+00:00","ms":812,"bankId":"default","portName":"COM3","kind":"rx","hex":"01 0F ..."}
+// Recording is on by default. Files rotate by size and the oldest ones are
+// deleted once `max_files` is exceeded. `lb_capture_export` zips captures so
+// they can be sent to the firmware team.
+//
+// Workers only queue records (`CaptureSink`); one writer thread does the file
+// I/O, in order. `lbctl capture` uses a sink of its own writing one file.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{fs::File, io::Write, path::Path, sync::mpsc, thread, time::Instant};
+#[cfg(feature = "gui")]
+use std::{
+    fs::{self, OpenOptions},
+    io,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+#[cfg(feature = "gui")]
+use tauri::{AppHandle, Manager, State};
+#[cfg(feature = "gui")]
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use crate::lb_link::LinkState;
+use crate::lb_link_stats::{LinkCounters, LinkRates};
+use crate::lb_protocol::FrameFields;
+
+#[cfg(feature = "gui")]
+pub const CAPTURE_DIR_NAME: &str = "lb-captures";
+#[cfg(feature = "gui")]
+pub const CAPTURE_EXT: &str = "jsonl";
+
+#[cfg(feature = "gui")]
+const DEFAULT_MAX_FILE_BYTES: u64 = 8 * 1024 * 1024;
+#[cfg(feature = "gui")]
+const DEFAULT_MAX_FILES: usize = 20;
+
+// -----------------------------------------------------------------------------
+// Records
+// -----------------------------------------------------------------------------
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum CaptureEvent {
+    Tx {
+        hex: String,
+    },
+    Rx {
+        hex: String,
+    },
+    /// Frame accepted by the decoder.
+    Frame {
+        hex: String,
+        fields: FrameFields,
+    },
+    /// CRC-valid frame that failed to decode (unknown version etc.).
+    FrameError {
+        hex: String,
+        error: String,
+    },
+    Health {
+        online: bool,
+        link: LinkState,
+        reason: Option<String>,
+    },
+    /// Link quality sample, written when a counter moved.
+    LinkStats {
+        counters: LinkCounters,
+        rates: LinkRates,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureRecord {
+    pub t: DateTime<Local>,
+    /// Milliseconds since the session started (monotonic).
+    pub ms: u64,
+    pub bank_id: String,
+    pub port_name: String,
+    #[serde(flatten)]
+    pub event: CaptureEvent,
+}
+
+// -----------------------------------------------------------------------------
+// Recorder
+// -----------------------------------------------------------------------------
+
+#[cfg(feature = "gui")]
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureInfo {
+    pub recording: bool,
+    pub dir: Option<String>,
+    pub session: Option<String>,
+    pub current_file: Option<String>,
+    pub records: u64,
+    pub max_file_bytes: u64,
+    pub max_files: usize,
+}
+
+#[cfg(feature = "gui")]
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureFile {
+    pub name: String,
+    pub path: String,
+    pub size_bytes: u64,
+    pub modified: Option<DateTime<Local>>,
+}
+
+#[cfg(feature = "gui")]
+struct Recorder {
+    dir: PathBuf,
+    session: String,
+    started: Instant,
+    file: File,
+    file_name: String,
+    file_index: u32,
+    file_bytes: u64,
+    records: u64,
+}
+
+#[cfg(feature = "gui")]
+struct CaptureInner {
+    enabled: bool,
+    recorder: Option<Recorder>,
+    max_file_bytes: u64,
+    max_files: usize,
+}
+
+// record as queued by a worker, stamped when it happened
+struct Queued {
+    t: DateTime<Local>,
+    at: Instant,
+    bank_id: String,
+    port_name: String,
+    event: CaptureEvent,
+}
+
+#[cfg(feature = "gui")]
+pub struct LoadBankCaptureState {
+    inner: Arc<Mutex<CaptureInner>>,
+    // started by the first sink
+    writer: Mutex<Option<mpsc::Sender<Queued>>>,
+}
+
+#[cfg(feature = "gui")]
+impl Default for LoadBankCaptureState {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(CaptureInner {
+                enabled: true,
+                recorder: None,
+                max_file_bytes: DEFAULT_MAX_FILE_BYTES,
+                max_files: DEFAULT_MAX_FILES,
+            })),
+            writer: Mutex::new(None),
+        }
+    }
+}
+
+/// Cheap handle for the runtime workers. The default one records nothing.
+#[derive(Clone, Default)]
+pub struct CaptureSink {
+    writer: Option<mpsc::Sender<Queued>>,
+}
+
+#[cfg(feature = "gui")]
+impl LoadBankCaptureState {
+    pub fn sink(&self, app: &AppHandle) -> CaptureSink {
+        let mut writer = self.writer.lock().unwrap();
+        let writer = writer.get_or_insert_with(|| {
+            let (tx, rx) = mpsc::channel::<Queued>();
+            let (app, inner) = (app.clone(), self.inner.clone());
+            thread::spawn(move || {
+                for q in rx {
+                    inner.lock().unwrap().write(&app, q);
+                }
+            });
+            tx
+        });
+        CaptureSink {
+            writer: Some(writer.clone()),
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+pub fn capture_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join(CAPTURE_DIR_NAME);
+    fs::create_dir_all(&dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+    Ok(dir)
+}
+
+#[cfg(feature = "gui")]
+fn open_capture_file(dir: &Path, session: &str, index: u32) -> io::Result<(File, String)> {
+    let name = format!("lb-{session}-{index:03}.{CAPTURE_EXT}");
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(dir.join(&name))?;
+    Ok((file, name))
+}
+
+#[cfg(feature = "gui")]
+impl Recorder {
+    fn start(dir: PathBuf) -> io::Result<Self> {
+        let session = Local::now().format("%Y%m%d-%H%M%S").to_string();
+        let (file, file_name) = open_capture_file(&dir, &session, 0)?;
+        eprintln!("[LB/CAP] recording to {}", dir.join(&file_name).display());
+        Ok(Self {
+            dir,
+            session,
+            started: Instant::now(),
+            file,
+            file_name,
+            file_index: 0,
+            file_bytes: 0,
+            records: 0,
+        })
+    }
+
+    fn write(&mut self, line: &[u8], max_file_bytes: u64, max_files: usize) -> io::Result<()> {
+        if self.file_bytes > 0 && self.file_bytes + line.len() as u64 > max_file_bytes {
+            self.file_index += 1;
+            let (file, file_name) = open_capture_file(&self.dir, &self.session, self.file_index)?;
+            self.file = file;
+            self.file_name = file_name;
+            self.file_bytes = 0;
+            prune_captures(&self.dir, max_files);
+        }
+        self.file.write_all(line)?;
+        self.file_bytes += line.len() as u64;
+        self.records += 1;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "gui")]
+impl CaptureInner {
+    fn info(&self) -> CaptureInfo {
+        let r = self.recorder.as_ref();
+        CaptureInfo {
+            recording: self.enabled,
+            dir: r.map(|r| r.dir.to_string_lossy().into_owned()),
+            session: r.map(|r| r.session.clone()),
+            current_file: r.map(|r| r.file_name.clone()),
+            records: r.map_or(0, |r| r.records),
+            max_file_bytes: self.max_file_bytes,
+            max_files: self.max_files,
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some(mut r) = self.recorder.take() {
+            let _ = r.file.flush();
+            eprintln!(
+                "[LB/CAP] closed session {} ({} records)",
+                r.session, r.records
+            );
+        }
+    }
+
+    // writer thread
+    fn write(&mut self, app: &AppHandle, q: Queued) {
+        if !self.enabled {
+            return;
+        }
+
+        if self.recorder.is_none() {
+            match capture_dir(app).and_then(|d| Recorder::start(d).map_err(|e| e.to_string())) {
+                Ok(r) => self.recorder = Some(r),
+                Err(e) => {
+                    // don't retry on every chunk
+                    eprintln!("[LB/CAP] capture disabled: {e}");
+                    self.enabled = false;
+                    return;
+                }
+            }
+        }
+
+        let (max_file_bytes, max_files) = (self.max_file_bytes, self.max_files);
+        let r = self.recorder.as_mut().unwrap();
+        let Ok(mut line) = serde_json::to_vec(&q.record(r.started)) else {
+            return;
+        };
+        line.push(b'\n');
+
+        if let Err(e) = r.write(&line, max_file_bytes, max_files) {
+            eprintln!("[LB/CAP] write failed, capture disabled: {e}");
+            self.close();
+            self.enabled = false;
+        }
+    }
+}
+
+impl Queued {
+    fn record(self, started: Instant) -> CaptureRecord {
+        CaptureRecord {
+            t: self.t,
+            ms: self.at.saturating_duration_since(started).as_millis() as u64,
+            bank_id: self.bank_id,
+            port_name: self.port_name,
+            event: self.event,
+        }
+    }
+}
+
+impl CaptureSink {
+    /// Records everything into `path` (no rotation). The writer thread ends
+    /// once every clone of the sink is dropped and returns the record count.
+    pub fn to_file(path: &Path) -> Result<(Self, thread::JoinHandle<u64>), String> {
+        let mut file = File::create(path).map_err(|e| format!("create {}: {e}", path.display()))?;
+        eprintln!("[LB/CAP] recording to {}", path.display());
+        let (tx, rx) = mpsc::channel::<Queued>();
+        let started = Instant::now();
+        let writer = thread::spawn(move || {
+            let mut records = 0;
+            for q in rx {
+                let Ok(mut line) = serde_json::to_vec(&q.record(started)) else {
+                    continue;
+                };
+                line.push(b'\n');
+                // unbuffered: the file stays complete when lbctl is stopped with Ctrl-C
+                if let Err(e) = file.write_all(&line) {
+                    eprintln!("[LB/CAP] write failed, capture stopped: {e}");
+                    break;
+                }
+                records += 1;
+            }
+            records
+        });
+        Ok((Self { writer: Some(tx) }, writer))
+    }
+
+    pub fn record(&self, bank_id: &str, port_name: &str, event: CaptureEvent) {
+        let Some(writer) = &self.writer else {
+            return;
+        };
+        // the writer only stops with the state
+        let _ = writer.send(Queued {
+            t: Local::now(),
+            at: Instant::now(),
+            bank_id: bank_id.to_string(),
+            port_name: port_name.to_string(),
+            event,
+        });
+    }
+}
+
+/// Capture files, oldest first.
+#[cfg(feature = "gui")]
+fn list_capture_files(dir: &Path) -> Vec<CaptureFile> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut files: Vec<CaptureFile> = entries
+        .flatten()
+        .filter(|e| e.path().extension().is_some_and(|x| x == CAPTURE_EXT))
+        .filter_map(|e| {
+            let meta = e.metadata().ok()?;
+            Some(CaptureFile {
+                name: e.file_name().to_string_lossy().into_owned(),
+                path: e.path().to_string_lossy().into_owned(),
+                size_bytes: meta.len(),
+                modified: meta.modified().ok().map(DateTime::<Local>::from),
+            })
+        })
+        .collect();
+    // names embed the session timestamp + index, so they sort chronologically
+    files.sort_by(|a, b| a.name.cmp(&b.name));
+    files
+}
+
+#[cfg(feature = "gui")]
+fn prune_captures(dir: &Path, max_files: usize) {
+    let files = list_capture_files(dir);
+    let excess = files.len().saturating_sub(max_files);
+    for f in files.into_iter().take(excess) {
+        match fs::remove_file(&f.path) {
+            Ok(()) => eprintln!("[LB/CAP] rotated out {}", f.name),
+            Err(e) => eprintln!("[LB/CAP] remove {} failed: {e}", f.name),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Commands
+// -----------------------------------------------------------------------------
+
+/// Starts a new capture session (closes the current one).
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_capture_start(
+    app: AppHandle,
+    state: State<LoadBankCaptureState>,
+    max_file_kb: Option<u64>,
+    max_files: Option<usize>,
+) -> Result<CaptureInfo, String> {
+    let dir = capture_dir(&app)?;
+    let mut inner = state.inner.lock().unwrap();
+    inner.close();
+
+    if let Some(kb) = max_file_kb {
+        inner.max_file_bytes = kb.max(1) * 1024;
+    }
+    if let Some(n) = max_files {
+        inner.max_files = n.max(1);
+    }
+    inner.recorder = Some(Recorder::start(dir.clone()).map_err(|e| e.to_string())?);
+    inner.enabled = true;
+    prune_captures(&dir, inner.max_files);
+
+    Ok(inner.info())
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_capture_stop(state: State<LoadBankCaptureState>) -> CaptureInfo {
+    let mut inner = state.inner.lock().unwrap();
+    let info = inner.info();
+    inner.close();
+    inner.enabled = false;
+    CaptureInfo {
+        recording: false,
+        ..info
+    }
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_capture_status(state: State<LoadBankCaptureState>) -> CaptureInfo {
+    state.inner.lock().unwrap().info()
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_capture_list(app: AppHandle) -> Result<Vec<CaptureFile>, String> {
+    Ok(list_capture_files(&capture_dir(&app)?))
+}
+
+/// Zips the named captures (all when `names` is empty) into `dest_path`.
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_capture_export(
+    app: AppHandle,
+    state: State<LoadBankCaptureState>,
+    names: Vec<String>,
+    dest_path: String,
+) -> Result<CaptureFile, String> {
+    let dir = capture_dir(&app)?;
+    let files: Vec<CaptureFile> = list_capture_files(&dir)
+        .into_iter()
+        .filter(|f| names.is_empty() || names.contains(&f.name))
+        .collect();
+    if files.is_empty() {
+        return Err("no captures to export".into());
+    }
+
+    // make sure the live file is complete on disk
+    if let Some(r) = state.inner.lock().unwrap().recorder.as_mut() {
+        let _ = r.file.flush();
+    }
+
+    let out = File::create(&dest_path).map_err(|e| format!("create {dest_path}: {e}"))?;
+    let mut zip = ZipWriter::new(out);
+    let opts = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    for f in &files {
+        let bytes = fs::read(&f.path).map_err(|e| format!("read {}: {e}", f.name))?;
+        zip.start_file(f.name.as_str(), opts)
+            .map_err(|e| e.to_string())?;
+        zip.write_all(&bytes).map_err(|e| e.to_string())?;
+    }
+    zip.finish().map_err(|e| e.to_string())?;
+
+    let size_bytes = fs::metadata(&dest_path).map(|m| m.len()).unwrap_or(0);
+    eprintln!("[LB/CAP] exported {} file(s) to {}", files.len(), dest_path);
+    Ok(CaptureFile {
+        name: Path::new(&dest_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        path: dest_path,
+        size_bytes,
+        modified: Some(Local::now()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("lb-capture-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn rx(hex: &str) -> CaptureEvent {
+        CaptureEvent::Rx { hex: hex.into() }
+    }
+
+    #[test]
+    fn records_one_json_line_each() {
+        let dir = temp_dir("file");
+        let path = dir.join("session.jsonl");
+        let (sink, writer) = CaptureSink::to_file(&path).unwrap();
+        sink.record(
+            "lb1",
+            "COM3",
+            CaptureEvent::Tx {
+                hex: "01 0F".into(),
+            },
+        );
+        sink.record("lb1", "COM3", rx("02"));
+        sink.clone().record(
+            "lb1",
+            "COM3",
+            CaptureEvent::Frame {
+                hex: "01".into(),
+                fields: FrameFields {
+                    contactors_mask: 0x0005,
+                    ..FrameFields::default()
+                },
+            },
+        );
+        drop(sink);
+        assert_eq!(writer.join().unwrap(), 3);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].contains(r#""bankId":"lb1","portName":"COM3","kind":"rx","hex":"02""#));
+        let records: Vec<CaptureRecord> = lines
+            .iter()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert!(records.windows(2).all(|w| w[0].ms <= w[1].ms));
+        assert!(matches!(
+            &records[2].event,
+            CaptureEvent::Frame { fields, .. } if fields.contactors_mask == 0x0005
+        ));
+
+        // the default sink records nothing
+        CaptureSink::default().record("lb1", "COM3", rx("03"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[cfg(feature = "gui")]
+    #[test]
+    fn rotates_by_size_and_keeps_max_files() {
+        let dir = temp_dir("rotate");
+        let mut r = Recorder::start(dir.clone()).unwrap();
+        let line = [b'x'; 40];
+        // 100 bytes per file: two lines each
+        for _ in 0..4 {
+            r.write(&line, 100, 10).unwrap();
+        }
+        assert_eq!((r.file_index, r.records), (1, 4));
+        let names: Vec<String> = list_capture_files(&dir)
+            .into_iter()
+            .map(|f| f.name)
+            .collect();
+        assert_eq!(
+            names,
+            [
+                format!("lb-{}-000.{CAPTURE_EXT}", r.session),
+                format!("lb-{}-001.{CAPTURE_EXT}", r.session)
+            ]
+        );
+
+        // a line bigger than a file still gets written, alone
+        r.write(&[b'y'; 150], 100, 2).unwrap();
+        r.write(&line, 100, 2).unwrap();
+        let files = list_capture_files(&dir);
+        let sizes: Vec<u64> = files.iter().map(|f| f.size_bytes).collect();
+        assert_eq!(sizes, [150, 40]);
+        assert_eq!(files[1].name, r.file_name);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
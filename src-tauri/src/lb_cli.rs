@@ -0,0 +1,496 @@
+// Headless load-bank diagnostics (`lbctl`, see src/bin/lbctl.rs).
+//
+// Runs the app's load-bank worker (lb_worker) without the webview, so a bank
+// can be checked over SSH or on a machine without the app with the same
+// pairing, interlocks, duty-cycle budget and emergency-stop latch. Status
+// lines go to stdout (JSON with `--json`), the worker's [LB] logs to stderr.
+// `capture` writes the JSONL format of lb_capture, so its files can be
+// replayed (`replay://<file>`) or checked with `lb_replay_check`.
+
+use chrono::Local;
+use std::{
+    path::Path,
+    process::ExitCode,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tokio::sync::oneshot;
+
+use crate::does_it_talk::roundtrip_bytes;
+use crate::lb_capture::CaptureSink;
+use crate::lb_failsafe::install_panic_hook;
+use crate::lb_link::{list_ports_detailed, open_transport, LoadBankStatus};
+use crate::lb_protocol::from_hex;
+use crate::lb_serial::{FlowControl, SerialConfig};
+use crate::lb_sim::{LoadBankSimState, SimDevice};
+use crate::lb_worker::{
+    worker_task, ContactorCmdError, RuntimeCmd, RuntimeMode, WorkerConfig, WorkerEvent,
+    WorkerHandle, WorkerHost, DEFAULT_CONFIRM_RETRIES, DEFAULT_CONFIRM_TIMEOUT_MS,
+};
+use crate::load_model::branches_in;
+
+const USAGE: &str = "\
+lbctl - load-bank diagnostics without the app
+
+usage: lbctl [options] <command> [args]
+
+commands:
+  list-ports                   serial ports (USB ids, serial numbers)
+  probe <port>                 handshake and print the status
+  set-mask <port> <mask>       close contactors (interlocks and duty-cycle budget
+                               apply), wait for the confirming status and watch the
+                               bank until Ctrl-C; --hold <ms> opens them after <ms>
+  monitor <port>               print every decoded status frame
+  raw <port> <hex>             send bytes without handshake, print what comes back
+  capture <port> <file>        record the traffic to a .jsonl capture (replayable)
+
+options:
+  --baud <n>     serial baud rate (default 115200)
+  --format <f>   data bits / parity / stop bits (default 8N1)
+  --flow <f>     none | software | hardware (default none)
+  --dtr <on|off> --rts <on|off>   line levels set after open (default: driver's)
+  --read-timeout <ms>  serial read timeout (default 30)
+  --json         print JSON instead of text
+  --poll <ms>    poll interval for monitor / capture / --hold (default 400, 0 = off)
+  --for <ms>     stop monitor / capture after <ms> (default: until Ctrl-C)
+  --hold <ms>    see set-mask
+  --window <ms>  raw: listen window (default 500)
+
+<port> is a serial port (COM3, /dev/ttyUSB0), tcp://host:port or sim.
+<mask> is decimal, 0x.. or 0b..
+Contactors are opened when lbctl ends (not when it is killed).
+";
+
+const DEFAULT_BAUD: u32 = 115_200;
+const DEFAULT_POLL_MS: u64 = 400;
+const DEFAULT_WINDOW_MS: u64 = 500;
+// device sends HELLO periodically; the worker keeps handshaking until then
+const CONNECT_TIMEOUT_MS: u64 = 3000;
+const CLI_BANK_ID: &str = "lbctl";
+const WORKER_GONE: &str = "load bank worker stopped";
+
+// -----------------------------------------------------------------------------
+// Arguments
+// -----------------------------------------------------------------------------
+
+struct Opts {
+    baud: u32,
+    serial: SerialConfig,
+    json: bool,
+    poll_ms: u64,
+    for_ms: Option<u64>,
+    hold_ms: Option<u64>,
+    window_ms: u64,
+    args: Vec<String>,
+}
+
+fn parse_u64(flag: &str, v: Option<String>) -> Result<u64, String> {
+    let v = v.ok_or(format!("{flag} needs a value"))?;
+    v.parse().map_err(|_| format!("{flag}: not a number: {v}"))
+}
+
+fn parse_level(flag: &str, v: Option<String>) -> Result<Option<bool>, String> {
+    match v.as_deref() {
+        Some("on" | "1") => Ok(Some(true)),
+        Some("off" | "0") => Ok(Some(false)),
+        _ => Err(format!("{flag} needs on / off")),
+    }
+}
+
+fn parse_opts(argv: Vec<String>) -> Result<Opts, String> {
+    let mut o = Opts {
+        baud: DEFAULT_BAUD,
+        serial: SerialConfig::default(),
+        json: false,
+        poll_ms: DEFAULT_POLL_MS,
+        for_ms: None,
+        hold_ms: None,
+        window_ms: DEFAULT_WINDOW_MS,
+        args: vec![],
+    };
+    let mut it = argv.into_iter();
+    while let Some(a) = it.next() {
+        match a.as_str() {
+            "--baud" => o.baud = parse_u64(&a, it.next())? as u32,
+            "--format" => {
+                let f = it.next().ok_or("--format needs a value")?;
+                o.serial = o.serial.with_label(&f)?;
+            }
+            "--flow" => {
+                o.serial.flow_control = match it.next().as_deref() {
+                    Some("none") => FlowControl::None,
+                    Some("software") => FlowControl::Software,
+                    Some("hardware") => FlowControl::Hardware,
+                    _ => return Err("--flow needs none / software / hardware".into()),
+                }
+            }
+            "--dtr" => o.serial.dtr = parse_level(&a, it.next())?,
+            "--rts" => o.serial.rts = parse_level(&a, it.next())?,
+            "--read-timeout" => o.serial.read_timeout_ms = parse_u64(&a, it.next())?,
+            "--json" => o.json = true,
+            "--poll" => o.poll_ms = parse_u64(&a, it.next())?,
+            "--for" => o.for_ms = Some(parse_u64(&a, it.next())?),
+            "--hold" => o.hold_ms = Some(parse_u64(&a, it.next())?),
+            "--window" => o.window_ms = parse_u64(&a, it.next())?,
+            _ if a.starts_with("--") => return Err(format!("unknown option {a}")),
+            _ => o.args.push(a),
+        }
+    }
+    o.serial.validate()?;
+    Ok(o)
+}
+
+fn parse_mask(s: &str) -> Result<u16, String> {
+    let parsed = if let Some(hex) = s.strip_prefix("0x").or(s.strip_prefix("0X")) {
+        u16::from_str_radix(hex, 16)
+    } else if let Some(bin) = s.strip_prefix("0b") {
+        u16::from_str_radix(bin, 2)
+    } else {
+        s.parse()
+    };
+    parsed.map_err(|_| format!("bad mask: {s}"))
+}
+
+/// Entry point of `lbctl` (arguments without the program name).
+pub fn run(argv: Vec<String>) -> ExitCode {
+    let o = match parse_opts(argv) {
+        Ok(o) => o,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            return ExitCode::from(2);
+        }
+    };
+    let args: Vec<&str> = o.args.iter().map(String::as_str).collect();
+    let res = match args.as_slice() {
+        ["list-ports"] => cmd_list_ports(&o),
+        ["probe", port] => cmd_probe(&o, port),
+        ["set-mask", port, mask] => parse_mask(mask).and_then(|m| cmd_set_mask(&o, port, m)),
+        ["monitor", port] => cmd_monitor(&o, port, CaptureSink::default()),
+        ["raw", port, hex] => cmd_raw(&o, port, hex),
+        ["capture", port, path] => cmd_capture(&o, port, path),
+        [] | ["help"] => {
+            print!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        _ => {
+            eprintln!("error: bad arguments: {}\n\n{USAGE}", args.join(" "));
+            return ExitCode::from(2);
+        }
+    };
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Bank (one worker, as in the app)
+// -----------------------------------------------------------------------------
+
+struct CliHost {
+    // `sim` gets a private simulated bank (nothing else shares it here)
+    sim: Arc<Mutex<SimDevice>>,
+    events: mpsc::Sender<WorkerEvent>,
+}
+
+impl WorkerHost for CliHost {
+    fn emit(&self, event: WorkerEvent) {
+        let _ = self.events.send(event);
+    }
+
+    fn sim_device(&self) -> Arc<Mutex<SimDevice>> {
+        self.sim.clone()
+    }
+}
+
+struct Bank {
+    port_name: String,
+    worker: WorkerHandle,
+    events: mpsc::Receiver<WorkerEvent>,
+    // current-thread runtime running the worker task
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Bank {
+    /// Starts a worker on `port_name` and waits for the first status.
+    fn connect(
+        o: &Opts,
+        port_name: &str,
+        capture: CaptureSink,
+    ) -> Result<(Self, LoadBankStatus), String> {
+        let cfg = WorkerConfig {
+            serial: Some(o.serial.clone()),
+            capture,
+            ..WorkerConfig::new(CLI_BANK_ID, o.baud, RuntimeMode::from_port_name(port_name))
+        };
+        let (tx, events) = mpsc::channel();
+        let host = CliHost {
+            sim: LoadBankSimState::default().device,
+            events: tx,
+        };
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .map_err(|e| format!("runtime: {e}"))?;
+        // a worker panic sends all-open to the bank, as in the app
+        install_panic_hook(cfg.failsafe.clone());
+        let (worker, task) = worker_task(Arc::new(host), cfg);
+        let thread = thread::spawn(move || rt.block_on(task));
+        let bank = Self {
+            port_name: port_name.to_string(),
+            worker,
+            events,
+            thread: Some(thread),
+        };
+
+        let deadline = Instant::now() + Duration::from_millis(CONNECT_TIMEOUT_MS);
+        let mut last_reason = None;
+        loop {
+            let event = match bank
+                .events
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            {
+                Ok(event) => event,
+                Err(_) => {
+                    let reason = last_reason.unwrap_or_else(|| "no answer".to_string());
+                    return Err(format!("{port_name}: {reason}"));
+                }
+            };
+            match event {
+                WorkerEvent::Status(status) => return Ok((bank, status)),
+                WorkerEvent::Health(h) => {
+                    if let Some(v) = h.unsupported_version {
+                        return Err(format!("{port_name}: unsupported protocol version {v}"));
+                    }
+                    last_reason = h.reason.or(last_reason);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn send(&self, cmd: RuntimeCmd) -> Result<(), String> {
+        self.worker.send(cmd).map_err(|_| WORKER_GONE.to_string())
+    }
+
+    fn set_polling(&self, poll_ms: u64) -> Result<(), String> {
+        self.send(RuntimeCmd::SetPolling {
+            enabled: poll_ms > 0,
+            interval_ms: poll_ms,
+        })
+    }
+
+    /// Closes `mask` through the interlocks and the duty-cycle budget and
+    /// waits for the status reporting it (the worker retries the write).
+    fn set_mask(&self, mask: u16) -> Result<LoadBankStatus, String> {
+        let (reply, wait) = oneshot::channel();
+        self.send(RuntimeCmd::SetContactorsConfirmed {
+            mask,
+            timeout: Duration::from_millis(DEFAULT_CONFIRM_TIMEOUT_MS),
+            retries: DEFAULT_CONFIRM_RETRIES,
+            reply,
+        })?;
+        let res = match wait.blocking_recv() {
+            Ok(res) => res.map_err(|e| confirm_error(mask, e)),
+            Err(_) => Err(WORKER_GONE.into()),
+        };
+        // the statuses up to the confirming one are not printed again
+        self.events.try_iter().for_each(drop);
+        res
+    }
+
+    /// Prints statuses until `until` (forever if None), interlock trips to stderr.
+    fn watch(&self, o: &Opts, until: Option<Instant>) -> Result<(), String> {
+        loop {
+            let event = match until {
+                Some(u) => match self
+                    .events
+                    .recv_timeout(u.saturating_duration_since(Instant::now()))
+                {
+                    Ok(event) => event,
+                    Err(RecvTimeoutError::Timeout) => return Ok(()),
+                    Err(RecvTimeoutError::Disconnected) => return Err(WORKER_GONE.into()),
+                },
+                None => self.events.recv().map_err(|_| WORKER_GONE.to_string())?,
+            };
+            match event {
+                WorkerEvent::Status(status) => print_status(o, &status),
+                WorkerEvent::Interlock(i) => {
+                    eprintln!("{}: interlock: {}", self.port_name, i.message)
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Drop for Bank {
+    // the worker opens the contactors before it releases the port
+    fn drop(&mut self) {
+        self.worker.stop();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn confirm_error(mask: u16, e: ContactorCmdError) -> String {
+    match e {
+        ContactorCmdError::Interlock { message, .. } => format!("refused: {message}"),
+        ContactorCmdError::Mismatch { reported, .. } => {
+            format!("mask 0x{mask:04X} not confirmed, bank reports 0x{reported:04X}")
+        }
+        ContactorCmdError::Timeout { attempts, .. } => {
+            format!("mask 0x{mask:04X} not confirmed after {attempts} attempt(s)")
+        }
+        ContactorCmdError::Offline { reason } => format!("bank offline: {reason}"),
+        ContactorCmdError::Superseded => format!("mask 0x{mask:04X} superseded"),
+        ContactorCmdError::NotRunning => WORKER_GONE.into(),
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Output
+// -----------------------------------------------------------------------------
+
+fn print_json<T: serde::Serialize>(v: &T) {
+    println!("{}", serde_json::to_string(v).unwrap_or_default());
+}
+
+fn mask_label(mask: u16) -> String {
+    let branches: Vec<&str> = branches_in(mask).map(|b| b.id).collect();
+    if mask == 0 {
+        "all open".into()
+    } else if branches.is_empty() {
+        "no wired branch".into()
+    } else {
+        branches.join("+")
+    }
+}
+
+fn print_status(o: &Opts, s: &LoadBankStatus) {
+    if o.json {
+        return print_json(s);
+    }
+    let mut line = format!(
+        "{} {} v{} bank {} power {} mask 0x{:04X} ({})",
+        Local::now().format("%H:%M:%S%.3f"),
+        s.port_name,
+        s.version,
+        s.bank_no,
+        s.bank_power,
+        s.contactors_mask,
+        mask_label(s.contactors_mask)
+    );
+    if let Some(h) = s.bank_health {
+        line.push_str(&format!(" health {h}"));
+    }
+    if !s.faults.is_empty() {
+        let faults: Vec<&str> = s.faults.iter().map(|f| f.message.as_str()).collect();
+        line.push_str(&format!(" | faults: {}", faults.join("; ")));
+    }
+    println!("{line}");
+}
+
+// -----------------------------------------------------------------------------
+// Commands
+// -----------------------------------------------------------------------------
+
+fn cmd_list_ports(o: &Opts) -> Result<(), String> {
+    let ports = list_ports_detailed();
+    if o.json {
+        print_json(&ports);
+        return Ok(());
+    }
+    if ports.is_empty() {
+        println!("no serial ports");
+    }
+    for p in ports {
+        let ids = match (p.vid, p.pid) {
+            (Some(vid), Some(pid)) => format!("{vid:04X}:{pid:04X}"),
+            _ => "-".into(),
+        };
+        println!(
+            "{}\t{}\t{}\t{}\t{} {}",
+            p.port_name,
+            p.port_type,
+            ids,
+            p.serial_number.unwrap_or_else(|| "-".into()),
+            p.manufacturer.unwrap_or_default(),
+            p.product.unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+fn cmd_probe(o: &Opts, port_name: &str) -> Result<(), String> {
+    let (_, status) = Bank::connect(o, port_name, CaptureSink::default())?;
+    print_status(o, &status);
+    Ok(())
+}
+
+fn cmd_set_mask(o: &Opts, port_name: &str, mask: u16) -> Result<(), String> {
+    // default interlocks (the app's safety settings live in the app)
+    let (bank, status) = Bank::connect(o, port_name, CaptureSink::default())?;
+    if !o.json {
+        print_status(o, &status);
+    }
+    let status = bank.set_mask(mask)?;
+    print_status(o, &status);
+    if mask == 0 {
+        return Ok(());
+    }
+
+    bank.set_polling(o.poll_ms)?;
+    let until = o
+        .hold_ms
+        .map(|ms| Instant::now() + Duration::from_millis(ms));
+    if until.is_none() {
+        eprintln!("note: watching {port_name} until Ctrl-C; the budget and interlocks open the contactors if needed");
+    }
+    bank.watch(o, until)?;
+    let status = bank.set_mask(0)?;
+    print_status(o, &status);
+    Ok(())
+}
+
+fn cmd_monitor(o: &Opts, port_name: &str, capture: CaptureSink) -> Result<(), String> {
+    let until = o
+        .for_ms
+        .map(|ms| Instant::now() + Duration::from_millis(ms));
+    let (bank, status) = Bank::connect(o, port_name, capture)?;
+    print_status(o, &status);
+    bank.set_polling(o.poll_ms)?;
+    bank.watch(o, until)
+}
+
+fn cmd_capture(o: &Opts, port_name: &str, path: &str) -> Result<(), String> {
+    let (capture, writer) = CaptureSink::to_file(Path::new(path))?;
+    let res = cmd_monitor(o, port_name, capture);
+    // the worker is gone: the writer ends once everything queued is written
+    let records = writer.join().unwrap_or(0);
+    eprintln!("[LB/CAP] {records} records");
+    res
+}
+
+fn cmd_raw(o: &Opts, port_name: &str, hex: &str) -> Result<(), String> {
+    let data = from_hex(hex).ok_or(format!("bad hex: {hex}"))?;
+    // `sim` gets a private simulated bank (nothing else shares it here)
+    let sim = LoadBankSimState::default().device;
+    let mut p = open_transport(port_name, o.baud, &o.serial, &sim)
+        .map_err(|e| format!("open {port_name}: {e}"))?;
+    let rt = roundtrip_bytes(p.as_mut(), data, Duration::from_millis(o.window_ms))?;
+    if o.json {
+        print_json(&rt);
+    } else {
+        println!("{}", serde_json::to_string_pretty(&rt).unwrap_or_default());
+    }
+    Ok(())
+}
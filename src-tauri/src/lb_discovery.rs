@@ -0,0 +1,345 @@
+// Which ports an AUTO runtime may try, and in what order.
+//
+// A handshake attempt writes an ACK to whatever answers with a HELLO-shaped
+// frame and costs a few hundred ms, so AUTO no longer walks every port:
+// 1) adapters that last hosted this bank (USB serial number remembered at
+//    adopt, so a COM / tty rename doesn't lose the bank)
+// 2) adapters that hosted some other bank
+// 3) ports matching a known VID/PID(/serial) of the policy
+// 4) everything else, only with `blindScan` on or after `lb_scan_all_ports`
+//
+// Policy and remembered adapters live in `<app data>/lb-discovery.json`.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, path::PathBuf, sync::Mutex};
+use tauri::{AppHandle, Manager, State};
+
+use crate::lb_link::SerialPortInfo;
+
+pub const DISCOVERY_FILE_NAME: &str = "lb-discovery.json";
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct UsbId {
+    pub vid: u16,
+    pub pid: u16,
+    /// None => any adapter with this VID/PID.
+    #[serde(default)]
+    pub serial_number: Option<String>,
+}
+
+impl UsbId {
+    fn matches(&self, p: &SerialPortInfo) -> bool {
+        p.vid == Some(self.vid)
+            && p.pid == Some(self.pid)
+            && (self.serial_number.is_none() || self.serial_number == p.serial_number)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DiscoveryPolicy {
+    /// Adapters load banks are known to sit behind.
+    pub known: Vec<UsbId>,
+    /// Also try ports matching nothing (after the others).
+    pub blind_scan: bool,
+}
+
+/// USB adapter a bank was last adopted on.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct KnownHost {
+    pub serial_number: String,
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+    pub bank_no: u8,
+    pub bank_id: String,
+    pub port_name: String,
+    pub last_seen: DateTime<Local>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct DiscoveryStore {
+    policy: DiscoveryPolicy,
+    /// by USB serial number
+    hosts: BTreeMap<String, KnownHost>,
+}
+
+/// Ports to try, best first. `bank_no` is the bank this runtime last talked
+/// to (None before the first handshake: match on `bank_id` instead).
+pub fn rank_ports(
+    policy: &DiscoveryPolicy,
+    hosts: &BTreeMap<String, KnownHost>,
+    ports: &[SerialPortInfo],
+    bank_id: &str,
+    bank_no: Option<u8>,
+    blind: bool,
+) -> Vec<String> {
+    let mut ranked: Vec<(u8, &str)> = ports
+        .iter()
+        .filter_map(|p| {
+            let host = p.serial_number.as_ref().and_then(|s| hosts.get(s));
+            let rank = match host {
+                Some(h) if bank_no.map_or(h.bank_id == bank_id, |n| n == h.bank_no) => 0,
+                Some(_) => 1,
+                None if policy.known.iter().any(|id| id.matches(p)) => 2,
+                None if blind || policy.blind_scan => 3,
+                None => return None,
+            };
+            Some((rank, p.port_name.as_str()))
+        })
+        .collect();
+    ranked.sort();
+    ranked
+        .into_iter()
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+// -----------------------------------------------------------------------------
+// State
+// -----------------------------------------------------------------------------
+
+#[derive(Default)]
+pub struct LoadBankDiscoveryState {
+    // loaded on first use
+    store: Mutex<Option<DiscoveryStore>>,
+}
+
+fn store_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(dir.join(DISCOVERY_FILE_NAME))
+}
+
+fn load_store(app: &AppHandle) -> DiscoveryStore {
+    let Ok(path) = store_path(app) else {
+        return DiscoveryStore::default();
+    };
+    match fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+            eprintln!("[LB/DISCOVERY] ignoring {}: {e}", path.display());
+            DiscoveryStore::default()
+        }),
+        Err(_) => DiscoveryStore::default(),
+    }
+}
+
+fn save_store(app: &AppHandle, store: &DiscoveryStore) -> Result<(), String> {
+    let path = store_path(app)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+    }
+    let json = serde_json::to_string_pretty(store).map_err(|e| e.to_string())?;
+    fs::write(&path, json).map_err(|e| format!("write {}: {e}", path.display()))
+}
+
+impl LoadBankDiscoveryState {
+    fn with_store<R>(&self, app: &AppHandle, f: impl FnOnce(&mut DiscoveryStore) -> R) -> R {
+        let mut guard = self.store.lock().unwrap();
+        f(guard.get_or_insert_with(|| load_store(app)))
+    }
+
+    pub fn rank(
+        &self,
+        app: &AppHandle,
+        ports: &[SerialPortInfo],
+        bank_id: &str,
+        bank_no: Option<u8>,
+        blind: bool,
+    ) -> Vec<String> {
+        self.with_store(app, |s| {
+            rank_ports(&s.policy, &s.hosts, ports, bank_id, bank_no, blind)
+        })
+    }
+
+    /// Called after a handshake; USB adapters without a serial number can't
+    /// be told apart, so they aren't remembered.
+    pub fn remember(&self, app: &AppHandle, port: &SerialPortInfo, bank_id: &str, bank_no: u8) {
+        let Some(serial_number) = port.serial_number.clone() else {
+            return;
+        };
+        self.with_store(app, |s| {
+            let moved = s.hosts.get(&serial_number).is_none_or(|h| {
+                h.bank_no != bank_no || h.bank_id != bank_id || h.port_name != port.port_name
+            });
+            s.hosts.insert(
+                serial_number.clone(),
+                KnownHost {
+                    serial_number: serial_number.clone(),
+                    vid: port.vid,
+                    pid: port.pid,
+                    bank_no,
+                    bank_id: bank_id.to_string(),
+                    port_name: port.port_name.clone(),
+                    last_seen: Local::now(),
+                },
+            );
+            // last_seen alone isn't worth a write per reconnect
+            if moved {
+                eprintln!(
+                    "[LB/DISCOVERY] bank {bank_no} ({bank_id}) on {serial_number} ({})",
+                    port.port_name
+                );
+                if let Err(e) = save_store(app, s) {
+                    eprintln!("[LB/DISCOVERY] save failed: {e}");
+                }
+            }
+        });
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Commands
+// -----------------------------------------------------------------------------
+
+#[tauri::command]
+pub fn lb_get_discovery_policy(
+    app: AppHandle,
+    state: State<LoadBankDiscoveryState>,
+) -> DiscoveryPolicy {
+    state.with_store(&app, |s| s.policy.clone())
+}
+
+#[tauri::command]
+pub fn lb_set_discovery_policy(
+    app: AppHandle,
+    state: State<LoadBankDiscoveryState>,
+    policy: DiscoveryPolicy,
+) -> Result<DiscoveryPolicy, String> {
+    state.with_store(&app, |s| {
+        eprintln!("[LB/DISCOVERY] policy: {:?}", policy);
+        s.policy = policy.clone();
+        save_store(&app, s)
+    })?;
+    Ok(policy)
+}
+
+/// Remembered adapters, most recently seen first.
+#[tauri::command]
+pub fn lb_list_known_hosts(app: AppHandle, state: State<LoadBankDiscoveryState>) -> Vec<KnownHost> {
+    let mut hosts: Vec<KnownHost> = state.with_store(&app, |s| s.hosts.values().cloned().collect());
+    hosts.sort_by_key(|h| std::cmp::Reverse(h.last_seen));
+    hosts
+}
+
+#[tauri::command]
+pub fn lb_forget_known_host(
+    app: AppHandle,
+    state: State<LoadBankDiscoveryState>,
+    serial_number: String,
+) -> Result<(), String> {
+    state.with_store(&app, |s| {
+        if s.hosts.remove(&serial_number).is_none() {
+            return Ok(());
+        }
+        save_store(&app, s)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FTDI: (u16, u16) = (0x0403, 0x6001);
+
+    fn port(name: &str, usb: Option<(u16, u16)>, serial_number: Option<&str>) -> SerialPortInfo {
+        SerialPortInfo {
+            port_name: name.into(),
+            port_type: if usb.is_some() { "usb" } else { "pci" }.into(),
+            vid: usb.map(|u| u.0),
+            pid: usb.map(|u| u.1),
+            serial_number: serial_number.map(str::to_string),
+            manufacturer: None,
+            product: None,
+        }
+    }
+
+    fn host(serial_number: &str, bank_id: &str, bank_no: u8) -> (String, KnownHost) {
+        (
+            serial_number.into(),
+            KnownHost {
+                serial_number: serial_number.into(),
+                vid: Some(FTDI.0),
+                pid: Some(FTDI.1),
+                bank_no,
+                bank_id: bank_id.into(),
+                port_name: "COM1".into(),
+                last_seen: Local::now(),
+            },
+        )
+    }
+
+    fn ports() -> Vec<SerialPortInfo> {
+        vec![
+            port("COM1", None, None),
+            port("COM2", Some(FTDI), Some("OTHER")),
+            port("COM3", Some(FTDI), Some("NEW")),
+            port("COM4", Some(FTDI), Some("MINE")),
+            port("COM5", Some((0x1A86, 0x7523)), None),
+        ]
+    }
+
+    #[test]
+    fn remembered_adapters_first_then_known_ids() {
+        let policy = DiscoveryPolicy {
+            known: vec![UsbId {
+                vid: FTDI.0,
+                pid: FTDI.1,
+                serial_number: None,
+            }],
+            blind_scan: false,
+        };
+        let hosts = BTreeMap::from([host("MINE", "a", 1), host("OTHER", "b", 2)]);
+
+        // by bank number once known, by bank id before the first handshake
+        let ranked = rank_ports(&policy, &hosts, &ports(), "a", Some(1), false);
+        assert_eq!(ranked, ["COM4", "COM2", "COM3"]);
+        let ranked = rank_ports(&policy, &hosts, &ports(), "b", None, false);
+        assert_eq!(ranked, ["COM2", "COM4", "COM3"]);
+        let ranked = rank_ports(&policy, &hosts, &ports(), "a", Some(2), false);
+        assert_eq!(ranked, ["COM2", "COM4", "COM3"]);
+    }
+
+    #[test]
+    fn unknown_ports_only_when_blind() {
+        let none = DiscoveryPolicy::default();
+        let hosts = BTreeMap::new();
+        assert!(rank_ports(&none, &hosts, &ports(), "a", None, false).is_empty());
+
+        let all = ["COM1", "COM2", "COM3", "COM4", "COM5"];
+        assert_eq!(rank_ports(&none, &hosts, &ports(), "a", None, true), all);
+        let blind = DiscoveryPolicy {
+            blind_scan: true,
+            ..DiscoveryPolicy::default()
+        };
+        assert_eq!(rank_ports(&blind, &hosts, &ports(), "a", None, false), all);
+    }
+
+    #[test]
+    fn known_ids_can_pin_a_serial_number() {
+        let policy = DiscoveryPolicy {
+            known: vec![
+                UsbId {
+                    vid: FTDI.0,
+                    pid: FTDI.1,
+                    serial_number: Some("NEW".into()),
+                },
+                UsbId {
+                    vid: 0x1A86,
+                    pid: 0x7523,
+                    serial_number: None,
+                },
+            ],
+            blind_scan: false,
+        };
+        let ranked = rank_ports(&policy, &BTreeMap::new(), &ports(), "a", None, false);
+        assert_eq!(ranked, ["COM3", "COM5"]);
+
+        // `serialNumber` may be left out
+        let id: UsbId = serde_json::from_str(r#"{"vid":1027,"pid":24577}"#).unwrap();
+        assert!(id.matches(&ports()[3]));
+        assert!(!id.matches(&ports()[0]));
+    }
+}
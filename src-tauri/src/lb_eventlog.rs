@@ -0,0 +1,667 @@
+// Load-bank event log (maintenance history).
+//
+// Health changes, handshake failures, fault edges, contactor changes and
+// refused masks of real banks (serial or TCP; the simulator and replays
+// aren't logged) go to a SQLite database at `<app data>/lb-events.sqlite3`,
+// tagged with the station (host name unless set), bank and port. The UI queries it by time range and
+// bank number; `lb_eventlog_export_xlsx` writes the same rows through
+// `export_xlsx`.
+//
+// Workers only queue their writes (`EventLogSink`); one writer thread runs
+// them in order, so SQLite never blocks the async runtime.
+
+use chrono::Local;
+#[cfg(feature = "gui")]
+use chrono::{DateTime, TimeZone};
+use rusqlite::{params, Connection, OptionalExtension};
+#[cfg(feature = "gui")]
+use rusqlite::{params_from_iter, types::Value};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+#[cfg(any(test, feature = "gui"))]
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+};
+use sysinfo::System;
+#[cfg(feature = "gui")]
+use tauri::{AppHandle, Manager, State};
+
+#[cfg(feature = "gui")]
+use crate::export_xlsx::{export_xlsx, CellValue, SheetDto, WorkbookDto};
+use crate::lb_faults::{Fault, FaultEdge, FaultSeverity};
+use crate::lb_link::LinkState;
+use crate::lb_safety::{EstopState, Interlock};
+use crate::lb_sequence::SequenceState;
+use crate::lb_wear;
+use crate::lb_worker::InterlockAction;
+use crate::load_model::branches_in;
+
+#[cfg(feature = "gui")]
+pub const EVENTLOG_FILE_NAME: &str = "lb-events.sqlite3";
+
+#[cfg(feature = "gui")]
+const DEFAULT_QUERY_LIMIT: u32 = 10_000;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS lb_meta (
+    key   TEXT PRIMARY KEY,
+    value TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS lb_events (
+    id        INTEGER PRIMARY KEY,
+    t_ms      INTEGER NOT NULL,
+    station   TEXT NOT NULL,
+    bank_id   TEXT NOT NULL,
+    bank_no   INTEGER,
+    port_name TEXT NOT NULL,
+    kind      TEXT NOT NULL,
+    code      TEXT,
+    state     TEXT,
+    severity  TEXT,
+    message   TEXT NOT NULL,
+    mask      INTEGER
+);
+CREATE INDEX IF NOT EXISTS lb_events_t ON lb_events (t_ms);
+CREATE INDEX IF NOT EXISTS lb_events_bank ON lb_events (bank_no, t_ms);
+";
+
+// -----------------------------------------------------------------------------
+// Events
+// -----------------------------------------------------------------------------
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum LogKind {
+    Health,
+    HandshakeFailed,
+    Fault,
+    Contactors,
+    Sequence,
+    EmergencyStop,
+    Interlock,
+}
+
+impl LogKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            LogKind::Health => "health",
+            LogKind::HandshakeFailed => "handshakeFailed",
+            LogKind::Fault => "fault",
+            LogKind::Contactors => "contactors",
+            LogKind::Sequence => "sequence",
+            LogKind::EmergencyStop => "emergencyStop",
+            LogKind::Interlock => "interlock",
+        }
+    }
+}
+
+/// Serialized name of a unit enum variant ("online", "raised", ...).
+fn variant_name<T: Serialize>(v: &T) -> Option<String> {
+    serde_json::to_value(v)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+}
+
+pub struct LogEvent {
+    pub kind: LogKind,
+    pub code: Option<String>,
+    /// Link state for health, edge for faults.
+    pub state: Option<String>,
+    pub severity: Option<FaultSeverity>,
+    pub message: String,
+    pub mask: Option<u16>,
+}
+
+impl LogEvent {
+    pub fn health(online: bool, link: LinkState, reason: Option<&str>) -> Self {
+        let state = if online { "online" } else { "offline" };
+        Self {
+            kind: LogKind::Health,
+            code: None,
+            state: variant_name(&link),
+            severity: None,
+            message: reason.unwrap_or(state).to_string(),
+            mask: None,
+        }
+    }
+
+    pub fn handshake_failed(reason: &str) -> Self {
+        Self {
+            kind: LogKind::HandshakeFailed,
+            code: None,
+            state: None,
+            severity: None,
+            message: reason.to_string(),
+            mask: None,
+        }
+    }
+
+    pub fn fault(edge: FaultEdge, fault: &Fault) -> Self {
+        Self {
+            kind: LogKind::Fault,
+            code: Some(fault.code.clone()),
+            state: variant_name(&edge),
+            severity: Some(fault.severity),
+            message: fault.message.clone(),
+            mask: None,
+        }
+    }
+
+    pub fn contactors(mask: u16) -> Self {
+        let branches: Vec<&str> = branches_in(mask).map(|b| b.id).collect();
+        let message = if mask == 0 {
+            "all open".to_string()
+        } else if branches.is_empty() {
+            format!("0x{mask:04X}")
+        } else {
+            format!("0x{mask:04X} ({})", branches.join("+"))
+        };
+        Self {
+            kind: LogKind::Contactors,
+            code: None,
+            state: None,
+            severity: None,
+            message,
+            mask: Some(mask),
+        }
+    }
+
+    /// Load profile started / ended (state: running, finished, aborted, failed).
+    pub fn sequence(state: SequenceState, message: String) -> Self {
+        Self {
+            kind: LogKind::Sequence,
+            code: None,
+            state: variant_name(&state),
+            severity: None,
+            message,
+            mask: None,
+        }
+    }
+
+    pub fn emergency_stop(state: EstopState, message: String) -> Self {
+        Self {
+            kind: LogKind::EmergencyStop,
+            code: None,
+            state: variant_name(&state),
+            severity: None,
+            message,
+            mask: None,
+        }
+    }
+
+    /// Mask refused or contactors opened by a safety interlock (code: its kind).
+    pub fn interlock(action: InterlockAction, mask: u16, interlock: &Interlock) -> Self {
+        let code = serde_json::to_value(interlock)
+            .ok()
+            .and_then(|v| v["kind"].as_str().map(str::to_string));
+        Self {
+            kind: LogKind::Interlock,
+            code,
+            state: variant_name(&action),
+            severity: None,
+            message: interlock.to_string(),
+            mask: Some(mask),
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EventLogRow {
+    pub id: i64,
+    pub t: DateTime<Local>,
+    pub station: String,
+    pub bank_id: String,
+    pub bank_no: Option<u8>,
+    pub port_name: String,
+    pub kind: String,
+    pub code: Option<String>,
+    pub state: Option<String>,
+    pub severity: Option<String>,
+    pub message: String,
+    pub mask: Option<u16>,
+}
+
+#[cfg(feature = "gui")]
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct EventLogFilter {
+    pub from: Option<DateTime<Local>>,
+    pub to: Option<DateTime<Local>>,
+    pub bank_no: Option<u8>,
+    pub bank_id: Option<String>,
+    /// Empty => every kind.
+    pub kinds: Vec<LogKind>,
+    pub limit: Option<u32>,
+}
+
+#[cfg(feature = "gui")]
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EventLogInfo {
+    pub enabled: bool,
+    pub path: Option<String>,
+    pub station: Option<String>,
+    pub events: i64,
+}
+
+// -----------------------------------------------------------------------------
+// Store
+// -----------------------------------------------------------------------------
+
+struct Db {
+    conn: Connection,
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    path: PathBuf,
+    station: String,
+}
+
+#[cfg(feature = "gui")]
+fn eventlog_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(dir.join(EVENTLOG_FILE_NAME))
+}
+
+impl Db {
+    fn open(path: &Path) -> Result<Self, String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+        }
+        let conn = Connection::open(path).map_err(|e| format!("open {}: {e}", path.display()))?;
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .and_then(|_| conn.pragma_update(None, "synchronous", "NORMAL"))
+            .and_then(|_| conn.execute_batch(SCHEMA))
+            .and_then(|_| conn.execute_batch(lb_wear::SCHEMA))
+            .map_err(|e| e.to_string())?;
+
+        let station = conn
+            .query_row("SELECT value FROM lb_meta WHERE key = 'station'", [], |r| {
+                r.get::<_, String>(0)
+            })
+            .optional()
+            .map_err(|e| e.to_string())?
+            .unwrap_or_else(default_station);
+
+        eprintln!(
+            "[LB/LOG] event log at {} (station {station})",
+            path.display()
+        );
+        Ok(Self {
+            conn,
+            path: path.to_path_buf(),
+            station,
+        })
+    }
+
+    fn insert(
+        &self,
+        t_ms: i64,
+        bank_id: &str,
+        bank_no: Option<u8>,
+        port_name: &str,
+        e: &LogEvent,
+    ) -> rusqlite::Result<()> {
+        self.conn.execute(
+            "INSERT INTO lb_events
+                (t_ms, station, bank_id, bank_no, port_name, kind, code, state, severity, message, mask)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                t_ms,
+                self.station,
+                bank_id,
+                bank_no,
+                port_name,
+                e.kind.as_str(),
+                e.code,
+                e.state,
+                e.severity.as_ref().and_then(variant_name),
+                e.message,
+                e.mask,
+            ],
+        )?;
+        Ok(())
+    }
+
+    #[cfg(feature = "gui")]
+    fn query(&self, f: &EventLogFilter) -> rusqlite::Result<Vec<EventLogRow>> {
+        let mut sql = String::from(
+            "SELECT id, t_ms, station, bank_id, bank_no, port_name, kind, code, state, severity, message, mask
+             FROM lb_events WHERE 1 = 1",
+        );
+        let mut args: Vec<Value> = vec![];
+        if let Some(from) = f.from {
+            sql.push_str(" AND t_ms >= ?");
+            args.push(Value::Integer(from.timestamp_millis()));
+        }
+        if let Some(to) = f.to {
+            sql.push_str(" AND t_ms <= ?");
+            args.push(Value::Integer(to.timestamp_millis()));
+        }
+        if let Some(bank_no) = f.bank_no {
+            sql.push_str(" AND bank_no = ?");
+            args.push(Value::Integer(bank_no as i64));
+        }
+        if let Some(bank_id) = &f.bank_id {
+            sql.push_str(" AND bank_id = ?");
+            args.push(Value::Text(bank_id.clone()));
+        }
+        if !f.kinds.is_empty() {
+            let marks = vec!["?"; f.kinds.len()].join(", ");
+            sql.push_str(&format!(" AND kind IN ({marks})"));
+            args.extend(f.kinds.iter().map(|k| Value::Text(k.as_str().into())));
+        }
+        // newest first
+        sql.push_str(" ORDER BY t_ms DESC, id DESC LIMIT ?");
+        args.push(Value::Integer(f.limit.unwrap_or(DEFAULT_QUERY_LIMIT) as i64));
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(args), |r| {
+            Ok(EventLogRow {
+                id: r.get(0)?,
+                t: Local
+                    .timestamp_millis_opt(r.get(1)?)
+                    .single()
+                    .unwrap_or_default(),
+                station: r.get(2)?,
+                bank_id: r.get(3)?,
+                bank_no: r.get(4)?,
+                port_name: r.get(5)?,
+                kind: r.get(6)?,
+                code: r.get(7)?,
+                state: r.get(8)?,
+                severity: r.get(9)?,
+                message: r.get(10)?,
+                mask: r.get(11)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    #[cfg(feature = "gui")]
+    fn count(&self) -> i64 {
+        self.conn
+            .query_row("SELECT COUNT(*) FROM lb_events", [], |r| r.get(0))
+            .unwrap_or(0)
+    }
+}
+
+fn default_station() -> String {
+    System::host_name().unwrap_or_else(|| "unknown".into())
+}
+
+struct EventLogInner {
+    enabled: bool,
+    // `<app data>/lb-events.sqlite3` once the app is known
+    path: Option<PathBuf>,
+    db: Option<Db>,
+}
+
+impl EventLogInner {
+    fn db(&mut self) -> Result<&Db, String> {
+        if self.db.is_none() {
+            let path = self.path.as_deref().ok_or("event log location unknown")?;
+            self.db = Some(Db::open(path)?);
+        }
+        Ok(self.db.as_ref().unwrap())
+    }
+
+    #[cfg(feature = "gui")]
+    fn locate(&mut self, app: &AppHandle) -> Result<(), String> {
+        if self.path.is_none() {
+            self.path = Some(eventlog_path(app)?);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "gui")]
+    fn app_db(&mut self, app: &AppHandle) -> Result<&Db, String> {
+        self.locate(app)?;
+        self.db()
+    }
+
+    fn log(
+        &mut self,
+        t_ms: i64,
+        bank_id: &str,
+        bank_no: Option<u8>,
+        port_name: &str,
+        event: &LogEvent,
+    ) {
+        if !self.enabled {
+            return;
+        }
+        let res = self.db().and_then(|db| {
+            db.insert(t_ms, bank_id, bank_no, port_name, event)
+                .map_err(|e| e.to_string())
+        });
+        if let Err(e) = res {
+            // don't retry on every event
+            eprintln!("[LB/LOG] event log disabled: {e}");
+            self.enabled = false;
+            self.db = None;
+        }
+    }
+}
+
+type Job = Box<dyn FnOnce(&mut EventLogInner) + Send>;
+
+#[cfg(feature = "gui")]
+pub struct LoadBankEventLogState {
+    inner: Arc<Mutex<EventLogInner>>,
+    // started by the first sink
+    writer: Mutex<Option<EventLogSink>>,
+}
+
+#[cfg(feature = "gui")]
+impl Default for LoadBankEventLogState {
+    fn default() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(EventLogInner {
+                enabled: true,
+                path: None,
+                db: None,
+            })),
+            writer: Mutex::new(None),
+        }
+    }
+}
+
+/// Cheap handle for the runtime workers. The default one logs nothing (lbctl).
+#[derive(Clone, Default)]
+pub struct EventLogSink {
+    writer: Option<mpsc::Sender<Job>>,
+}
+
+#[cfg(feature = "gui")]
+impl LoadBankEventLogState {
+    pub fn sink(&self, app: &AppHandle) -> EventLogSink {
+        let mut writer = self.writer.lock().unwrap();
+        writer
+            .get_or_insert_with(|| {
+                // opened by the writer thread
+                if let Err(e) = self.inner.lock().unwrap().locate(app) {
+                    eprintln!("[LB/LOG] event log unavailable: {e}");
+                }
+                EventLogSink::spawn(self.inner.clone())
+            })
+            .clone()
+    }
+
+    /// Runs `f` on the shared database (other lb modules keep their tables there too).
+    pub fn with_conn<R>(
+        &self,
+        app: &AppHandle,
+        f: impl FnOnce(&Connection) -> rusqlite::Result<R>,
+    ) -> Result<R, String> {
+        let mut inner = self.inner.lock().unwrap();
+        f(&inner.app_db(app)?.conn).map_err(|e| e.to_string())
+    }
+}
+
+impl EventLogSink {
+    // one writer thread per database, stops with the last sink
+    #[cfg(any(test, feature = "gui"))]
+    fn spawn(inner: Arc<Mutex<EventLogInner>>) -> Self {
+        let (tx, rx) = mpsc::channel::<Job>();
+        thread::spawn(move || {
+            for job in rx {
+                job(&mut inner.lock().unwrap());
+            }
+        });
+        Self { writer: Some(tx) }
+    }
+
+    /// Own writer on the database at `path`, opened on first use.
+    #[cfg(test)]
+    pub fn open(path: PathBuf) -> Self {
+        Self::spawn(Arc::new(Mutex::new(EventLogInner {
+            enabled: true,
+            path: Some(path),
+            db: None,
+        })))
+    }
+
+    fn queue(&self, job: Job) {
+        if let Some(writer) = &self.writer {
+            let _ = writer.send(job);
+        }
+    }
+
+    /// Queues `f` on the shared database, after everything queued before.
+    pub fn with_conn(&self, f: impl FnOnce(Result<&Connection, String>) + Send + 'static) {
+        self.queue(Box::new(move |inner| f(inner.db().map(|db| &db.conn))));
+    }
+
+    pub fn log(&self, bank_id: &str, bank_no: Option<u8>, port_name: &str, event: LogEvent) {
+        let t_ms = Local::now().timestamp_millis();
+        let (bank_id, port_name) = (bank_id.to_string(), port_name.to_string());
+        self.queue(Box::new(move |inner| {
+            inner.log(t_ms, &bank_id, bank_no, &port_name, &event)
+        }));
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Commands
+// -----------------------------------------------------------------------------
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_eventlog_info(app: AppHandle, state: State<LoadBankEventLogState>) -> EventLogInfo {
+    let mut inner = state.inner.lock().unwrap();
+    let enabled = inner.enabled;
+    match inner.app_db(&app) {
+        Ok(db) => EventLogInfo {
+            enabled,
+            path: Some(db.path.to_string_lossy().into_owned()),
+            station: Some(db.station.clone()),
+            events: db.count(),
+        },
+        Err(_) => EventLogInfo {
+            enabled: false,
+            path: None,
+            station: None,
+            events: 0,
+        },
+    }
+}
+
+/// Station name stored with every event (persisted in the database).
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_eventlog_set_station(
+    app: AppHandle,
+    state: State<LoadBankEventLogState>,
+    station: String,
+) -> Result<String, String> {
+    let station = station.trim().to_string();
+    if station.is_empty() {
+        return Err("station name is empty".into());
+    }
+    let mut inner = state.inner.lock().unwrap();
+    inner.app_db(&app)?;
+    let db = inner.db.as_mut().unwrap();
+    db.conn
+        .execute(
+            "INSERT INTO lb_meta (key, value) VALUES ('station', ?1)
+             ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            params![station],
+        )
+        .map_err(|e| e.to_string())?;
+    db.station = station.clone();
+    Ok(station)
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_eventlog_query(
+    app: AppHandle,
+    state: State<LoadBankEventLogState>,
+    filter: Option<EventLogFilter>,
+) -> Result<Vec<EventLogRow>, String> {
+    let mut inner = state.inner.lock().unwrap();
+    inner
+        .app_db(&app)?
+        .query(&filter.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "gui")]
+fn opt_text(v: Option<String>) -> CellValue {
+    v.map_or(CellValue::Empty, CellValue::String)
+}
+
+/// Writes the filtered events to `dest_path` (.xlsx); returns the row count.
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_eventlog_export_xlsx(
+    app: AppHandle,
+    state: State<LoadBankEventLogState>,
+    filter: Option<EventLogFilter>,
+    dest_path: String,
+) -> Result<usize, String> {
+    let rows = lb_eventlog_query(app, state, filter)?;
+    let headers = [
+        "Time", "Station", "Bank", "Bank no", "Port", "Kind", "Code", "State", "Severity",
+        "Message", "Mask",
+    ];
+    let sheet = SheetDto {
+        name: "LB events".into(),
+        headers: headers.iter().map(|h| h.to_string()).collect(),
+        rows: rows
+            .iter()
+            .rev()
+            .map(|r| {
+                vec![
+                    CellValue::DateTime(r.t.format("%Y-%m-%d %H:%M:%S%.3f").to_string()),
+                    CellValue::String(r.station.clone()),
+                    CellValue::String(r.bank_id.clone()),
+                    r.bank_no
+                        .map_or(CellValue::Empty, |n| CellValue::Int(n as i64)),
+                    CellValue::String(r.port_name.clone()),
+                    CellValue::String(r.kind.clone()),
+                    opt_text(r.code.clone()),
+                    opt_text(r.state.clone()),
+                    opt_text(r.severity.clone()),
+                    CellValue::String(r.message.clone()),
+                    opt_text(r.mask.map(|m| format!("0x{m:04X}"))),
+                ]
+            })
+            .collect(),
+    };
+    export_xlsx(
+        &dest_path,
+        WorkbookDto {
+            path: None,
+            sheets: vec![sheet],
+        },
+    )?;
+    eprintln!("[LB/LOG] exported {} event(s) to {}", rows.len(), dest_path);
+    Ok(rows.len())
+}
@@ -0,0 +1,149 @@
+// Last-resort all-open, for when a worker can't open the contactors itself.
+//
+// Every adopted port registers its write handle here, together with the
+// all-open frame for the firmware behind it (rebuilt from each status frame).
+// `open_all` writes that frame to every registered port, on app exit after
+// the runtimes were asked to stop.
+//
+// Workers run inside `worker_scope`. A panic in one (it never reaches its own
+// cleanup) sends all-open to that bank only and latches its emergency stop, so
+// a restarted runtime won't close anything until `lb_reset_estop`. Panics
+// anywhere else leave the banks alone.
+//
+// A worker that stops normally opens the contactors before releasing its
+// port and unregisters it. Nothing opens them if the host disappears
+// altogether, unless the firmware gets the proposed watchdog (`WATCHDOG_MS`).
+
+use std::{
+    collections::HashMap,
+    future::Future,
+    io::Write,
+    panic,
+    sync::{Arc, Mutex, TryLockError, Weak},
+};
+
+use crate::lb_protocol::Frame;
+use crate::lb_safety::EstopLatch;
+
+tokio::task_local! {
+    // bank of the worker task being polled
+    static WORKER: (String, Arc<EstopLatch>);
+}
+
+/// Write half of an adopted port, shared by the worker and the fail-safe.
+pub type SharedWriter = Arc<Mutex<Box<dyn Write + Send>>>;
+
+struct Entry {
+    port_name: String,
+    // weak: a dead worker must not keep its port open
+    writer: Weak<Mutex<Box<dyn Write + Send>>>,
+    open_frame: Frame,
+}
+
+/// Ports by bank id.
+#[derive(Clone, Default)]
+pub struct Failsafe {
+    ports: Arc<Mutex<HashMap<String, Entry>>>,
+}
+
+impl Failsafe {
+    pub fn register(
+        &self,
+        bank_id: &str,
+        port_name: &str,
+        writer: &SharedWriter,
+        open_frame: Frame,
+    ) {
+        self.ports.lock().unwrap().insert(
+            bank_id.to_string(),
+            Entry {
+                port_name: port_name.to_string(),
+                writer: Arc::downgrade(writer),
+                open_frame,
+            },
+        );
+    }
+
+    pub fn set_open_frame(&self, bank_id: &str, open_frame: Frame) {
+        if let Some(e) = self.ports.lock().unwrap().get_mut(bank_id) {
+            e.open_frame = open_frame;
+        }
+    }
+
+    pub fn unregister(&self, bank_id: &str) {
+        self.ports.lock().unwrap().remove(bank_id);
+    }
+
+    /// Best effort: skips whatever is locked elsewhere (the panicking thread
+    /// may hold it). Returns the number of ports written.
+    #[cfg(feature = "gui")]
+    pub fn open_all(&self, why: &str) -> usize {
+        let Ok(ports) = self.ports.try_lock() else {
+            eprintln!("[LB/FAILSAFE] {why}: registry busy, nothing sent");
+            return 0;
+        };
+        let sent = ports
+            .iter()
+            .filter(|(bank_id, e)| send_open(bank_id, e, why))
+            .count();
+        if sent > 0 {
+            eprintln!("[LB/FAILSAFE] {why}: all-open sent to {sent} bank(s)");
+        }
+        sent
+    }
+
+    /// Same as `open_all`, one bank.
+    pub fn open_bank(&self, bank_id: &str, why: &str) -> bool {
+        let Ok(ports) = self.ports.try_lock() else {
+            eprintln!("[LB/FAILSAFE] {why}: registry busy, nothing sent");
+            return false;
+        };
+        let sent = ports
+            .get(bank_id)
+            .is_some_and(|e| send_open(bank_id, e, why));
+        if sent {
+            eprintln!("[LB/FAILSAFE] {why}: all-open sent to {bank_id}");
+        }
+        sent
+    }
+}
+
+fn send_open(bank_id: &str, e: &Entry, why: &str) -> bool {
+    let Some(writer) = e.writer.upgrade() else {
+        return false;
+    };
+    let mut w = match writer.try_lock() {
+        Ok(w) => w,
+        // a panic mid-write poisons it, the port itself is fine
+        Err(TryLockError::Poisoned(p)) => p.into_inner(),
+        Err(TryLockError::WouldBlock) => {
+            eprintln!("[LB/FAILSAFE] {why}: {bank_id} ({}) busy", e.port_name);
+            return false;
+        }
+    };
+    match w.write_all(e.open_frame.as_bytes()).and_then(|_| w.flush()) {
+        Ok(()) => true,
+        Err(err) => {
+            eprintln!("[LB/FAILSAFE] {why}: {bank_id} ({}): {err}", e.port_name);
+            false
+        }
+    }
+}
+
+/// Runs a worker task under `bank_id`, for the panic hook.
+pub async fn worker_scope<F: Future>(bank_id: String, estop: Arc<EstopLatch>, fut: F) -> F::Output {
+    WORKER.scope((bank_id, estop), fut).await
+}
+
+/// On a panic inside a worker task: latches that bank's emergency stop and
+/// sends it all-open. Then runs the previous hook.
+pub fn install_panic_hook(failsafe: Failsafe) {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = WORKER.try_with(|(bank_id, estop)| {
+            estop.trip("worker panic");
+            failsafe.open_bank(bank_id, "worker panic");
+        });
+        previous(info);
+    }));
+}
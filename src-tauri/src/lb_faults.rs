@@ -0,0 +1,216 @@
+// Named faults decoded from the error bitfields of a status frame.
+//
+// - err_contactors: bit n = contactor C(n+1) stuck / not following the mask
+// - err_fans:       bit n = fan of tunnel n+1
+// - err_thermals:   bit n = thermal switch of tunnel n+1
+// - other_errors:   bit n = undocumented firmware error; one generic warning
+//                   per set bit until the firmware names them
+//
+// The worker diffs the decoded list on every status frame and emits
+// `lb/fault` once per rising / falling edge.
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::cmp::Reverse;
+
+use crate::lb_protocol::FrameFields;
+use crate::load_model::LB_BRANCHES;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub enum FaultSeverity {
+    Warning,
+    Critical,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FaultKind {
+    Contactor,
+    Fan,
+    Thermal,
+    Other,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Fault {
+    /// Stable id, e.g. "contactor:C3", "fan:T2", "other:7".
+    pub code: String,
+    pub kind: FaultKind,
+    pub bit: u8,
+    pub severity: FaultSeverity,
+    pub message: String,
+    /// Resistor branch switched by the faulty contactor (if wired).
+    pub branch: Option<&'static str>,
+    /// 1-based tunnel number.
+    pub tunnel: Option<u8>,
+}
+
+fn set_bits(value: u16) -> impl Iterator<Item = u8> {
+    (0..16u8).filter(move |b| value & (1 << b) != 0)
+}
+
+fn contactor_fault(bit: u8) -> Fault {
+    let branch = LB_BRANCHES.iter().find(|b| b.mask_bit == 1 << bit);
+    let id = format!("C{}", bit + 1);
+    let message = match branch {
+        Some(b) => format!("contactor {id} ({}) not following the command", b.id),
+        None => format!("contactor {id} not following the command"),
+    };
+    Fault {
+        code: format!("contactor:{id}"),
+        kind: FaultKind::Contactor,
+        bit,
+        severity: FaultSeverity::Critical,
+        message,
+        branch: branch.map(|b| b.id),
+        tunnel: branch.map(|b| b.tunnel as u8 + 1),
+    }
+}
+
+fn tunnel_fault(kind: FaultKind, bit: u8) -> Fault {
+    let (prefix, what, severity) = match kind {
+        FaultKind::Fan => ("fan", "fan failed", FaultSeverity::Warning),
+        _ => ("thermal", "thermal switch tripped", FaultSeverity::Critical),
+    };
+    Fault {
+        code: format!("{prefix}:T{}", bit + 1),
+        kind,
+        bit,
+        severity,
+        message: format!("tunnel {} {what}", bit + 1),
+        branch: None,
+        tunnel: Some(bit + 1),
+    }
+}
+
+fn other_fault(bit: u8) -> Fault {
+    Fault {
+        code: format!("other:{bit}"),
+        kind: FaultKind::Other,
+        bit,
+        severity: FaultSeverity::Warning,
+        message: format!("firmware error bit {bit} set"),
+        branch: None,
+        tunnel: None,
+    }
+}
+
+/// Every fault reported by a status frame, most severe first.
+pub fn decode_faults(f: &FrameFields) -> Vec<Fault> {
+    let mut out: Vec<Fault> = set_bits(f.err_contactors)
+        .map(contactor_fault)
+        .chain(set_bits(f.err_fans).map(|b| tunnel_fault(FaultKind::Fan, b)))
+        .chain(set_bits(f.err_thermals).map(|b| tunnel_fault(FaultKind::Thermal, b)))
+        .chain(set_bits(f.other_errors as u16).map(other_fault))
+        .collect();
+    out.sort_by_key(|f| Reverse(f.severity));
+    out
+}
+
+// -----------------------------------------------------------------------------
+// Edges
+// -----------------------------------------------------------------------------
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FaultEdge {
+    Raised,
+    Cleared,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FaultEvent {
+    pub t: DateTime<Local>,
+    pub bank_id: String,
+    pub port_name: String,
+    pub edge: FaultEdge,
+    pub fault: Fault,
+}
+
+/// Faults raised / cleared going from `prev` to `next`.
+pub fn fault_edges(prev: &[Fault], next: &[Fault]) -> Vec<(FaultEdge, Fault)> {
+    let raised = next
+        .iter()
+        .filter(|f| !prev.iter().any(|p| p.code == f.code))
+        .map(|f| (FaultEdge::Raised, f.clone()));
+    let cleared = prev
+        .iter()
+        .filter(|p| !next.iter().any(|f| f.code == p.code))
+        .map(|p| (FaultEdge::Cleared, p.clone()));
+    raised.chain(cleared).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(faults: &[Fault]) -> Vec<&str> {
+        faults.iter().map(|f| f.code.as_str()).collect()
+    }
+
+    #[test]
+    fn decodes_every_bitfield() {
+        let faults = decode_faults(&FrameFields {
+            err_contactors: 0x0104,
+            err_fans: 0x0002,
+            err_thermals: 0x0001,
+            other_errors: 0x81,
+            ..FrameFields::default()
+        });
+        assert_eq!(
+            codes(&faults),
+            [
+                "contactor:C3",
+                "contactor:C9",
+                "thermal:T1",
+                "fan:T2",
+                "other:0",
+                "other:7"
+            ]
+        );
+
+        let c3 = &faults[0];
+        assert_eq!((c3.branch, c3.tunnel), (Some("R3"), Some(2)));
+        // C9 switches no branch
+        assert_eq!((faults[1].branch, faults[1].tunnel), (None, None));
+        assert_eq!(faults[3].severity, FaultSeverity::Warning);
+
+        let other = &faults[5];
+        assert_eq!(
+            (other.kind, other.bit, other.severity),
+            (FaultKind::Other, 7, FaultSeverity::Warning)
+        );
+        assert!(other.message.contains("bit 7"));
+
+        assert!(decode_faults(&FrameFields::default()).is_empty());
+    }
+
+    #[test]
+    fn edges_on_raise_and_clear() {
+        let fans = |err_fans| {
+            decode_faults(&FrameFields {
+                err_fans,
+                ..FrameFields::default()
+            })
+        };
+        let edges = |prev: u16, next: u16| -> Vec<(FaultEdge, String)> {
+            fault_edges(&fans(prev), &fans(next))
+                .into_iter()
+                .map(|(edge, f)| (edge, f.code))
+                .collect()
+        };
+        let raised = |code: &str| (FaultEdge::Raised, code.to_string());
+        let cleared = |code: &str| (FaultEdge::Cleared, code.to_string());
+
+        assert_eq!(edges(0, 0x0001), [raised("fan:T1")]);
+        // only the new one rises; the standing one doesn't repeat
+        assert_eq!(edges(0x0001, 0x0003), [raised("fan:T2")]);
+        assert!(edges(0x0003, 0x0003).is_empty());
+        assert_eq!(edges(0x0003, 0x0002), [cleared("fan:T1")]);
+        assert_eq!(edges(0x0001, 0x0002), [raised("fan:T2"), cleared("fan:T1")]);
+        assert_eq!(edges(0x0002, 0), [cleared("fan:T2")]);
+    }
+}
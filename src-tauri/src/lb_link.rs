@@ -0,0 +1,300 @@
+// Link pieces used by the worker and `lbctl raw`, free of Tauri: port listing,
+// transport selection, the device-first handshake and the status payload.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::lb_faults::{decode_faults, Fault};
+use crate::lb_protocol::{
+    is_supported, supported_versions_label, Frame, FrameDecoder, FrameFields, DEFAULT_VERSION,
+    HANDSHAKE_ACK_VALUE,
+};
+use crate::lb_replay::{ReplayTransport, REPLAY_SCHEME};
+use crate::lb_serial::SerialConfig;
+use crate::lb_sim::{connect_sim, SimDevice};
+use crate::lb_transport::{SerialTransport, TcpTransport, Transport, TCP_SCHEME};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum LinkState {
+    Online,
+    Degraded,
+    #[default]
+    Offline,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum HandshakeError {
+    NoHello,
+    NoConfirm,
+    /// HELLO from firmware whose protocol version has no layout here.
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for HandshakeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HandshakeError::NoHello => write!(f, "no HELLO"),
+            HandshakeError::NoConfirm => write!(f, "no CONFIRM"),
+            HandshakeError::UnsupportedVersion(v) => write!(
+                f,
+                "unsupported firmware: protocol v{v} (supported: {})",
+                supported_versions_label()
+            ),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Payloads
+// -----------------------------------------------------------------------------
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SerialPortInfo {
+    pub port_name: String,
+    pub port_type: String,
+    pub vid: Option<u16>,
+    pub pid: Option<u16>,
+    pub serial_number: Option<String>,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadBankStatus {
+    pub bank_id: String,
+    pub port_name: String,
+    pub version: u8,
+    pub bank_power: u16,
+    pub bank_no: u8,
+    pub handshake: u8,
+    pub contactors_mask: u16,
+    pub err_contactors: u16,
+    pub err_fans: u16,
+    pub err_thermals: u16,
+    pub other_errors: u8,
+    /// v2+ firmware only.
+    pub bank_health: Option<u8>,
+    /// Error bitfields decoded (most severe first).
+    pub faults: Vec<Fault>,
+    pub raw_frame_hex: String,
+}
+
+impl LoadBankStatus {
+    pub fn from_fields(f: &FrameFields, frame: &Frame, bank_id: &str, port_name: &str) -> Self {
+        Self {
+            bank_id: bank_id.to_string(),
+            port_name: port_name.to_string(),
+            version: f.version,
+            bank_power: f.bank_power,
+            bank_no: f.bank_no,
+            handshake: f.handshake,
+            contactors_mask: f.contactors_mask,
+            err_contactors: f.err_contactors,
+            err_fans: f.err_fans,
+            err_thermals: f.err_thermals,
+            other_errors: f.other_errors,
+            bank_health: f.bank_health,
+            faults: decode_faults(f),
+            raw_frame_hex: frame.to_hex(),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Ports, transports, handshake
+// -----------------------------------------------------------------------------
+
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn list_ports_detailed() -> Vec<SerialPortInfo> {
+    let ports = match serialport::available_ports() {
+        Ok(p) => p,
+        Err(_) => return vec![],
+    };
+
+    ports
+        .into_iter()
+        .map(|p| match p.port_type {
+            serialport::SerialPortType::UsbPort(info) => SerialPortInfo {
+                port_name: p.port_name,
+                port_type: "usb".into(),
+                vid: Some(info.vid),
+                pid: Some(info.pid),
+                serial_number: info.serial_number,
+                manufacturer: info.manufacturer,
+                product: info.product,
+            },
+            serialport::SerialPortType::BluetoothPort => SerialPortInfo {
+                port_name: p.port_name,
+                port_type: "bluetooth".into(),
+                vid: None,
+                pid: None,
+                serial_number: None,
+                manufacturer: None,
+                product: None,
+            },
+            serialport::SerialPortType::PciPort => SerialPortInfo {
+                port_name: p.port_name,
+                port_type: "pci".into(),
+                vid: None,
+                pid: None,
+                serial_number: None,
+                manufacturer: None,
+                product: None,
+            },
+            serialport::SerialPortType::Unknown => SerialPortInfo {
+                port_name: p.port_name,
+                port_type: "unknown".into(),
+                vid: None,
+                pid: None,
+                serial_number: None,
+                manufacturer: None,
+                product: None,
+            },
+        })
+        .collect()
+}
+
+/// Contactor command frame: built from the last known status if available,
+/// else from the template of the negotiated version.
+pub fn contactors_command(last: Option<&FrameFields>, version: Option<u8>, mask: u16) -> Frame {
+    let mut f = last
+        .cloned()
+        .unwrap_or_else(|| FrameFields::command_template_for(version.unwrap_or(DEFAULT_VERSION)));
+
+    // Ensure command frames are in "paired" mode
+    f.handshake = HANDSHAKE_ACK_VALUE; // 0x00
+
+    f.contactors_mask = mask;
+    f.err_contactors = 0;
+    f.err_fans = 0;
+    f.err_thermals = 0;
+    f.other_errors = 0;
+
+    f.encode()
+}
+
+/// Opens whatever transport `port_name` points at (serial, `tcp://`, replay, sim).
+/// Only the read timeout of `serial` applies to the non-serial ones.
+pub fn open_transport(
+    port_name: &str,
+    baud: u32,
+    serial: &SerialConfig,
+    sim: &Arc<Mutex<SimDevice>>,
+) -> Result<Box<dyn Transport>, String> {
+    let read_timeout = serial.read_timeout();
+    if port_name == "sim" || port_name.starts_with("sim://") {
+        return Ok(Box::new(connect_sim(sim.clone(), read_timeout)?));
+    }
+    if let Some(addr) = port_name.strip_prefix(TCP_SCHEME) {
+        return Ok(Box::new(TcpTransport::connect(addr, read_timeout)?));
+    }
+    if let Some(spec) = port_name.strip_prefix(REPLAY_SCHEME) {
+        return Ok(Box::new(ReplayTransport::open(spec, read_timeout)?));
+    }
+    Ok(Box::new(SerialTransport::open(port_name, baud, serial)?))
+}
+
+// device-first handshake:
+// 1) listen for HELLO (byte4=0xFF), read-only
+// 2) send ACK (byte4=0x00) derived from HELLO, i.e. in the device's version;
+//    a version we have no layout for is not paired at all
+// 3) wait for CONFIRM (byte4=0x00)
+//
+// `on_rx` sees every chunk read, `on_ack` the ACK before it is written.
+pub fn handshake(
+    p: &mut dyn Transport,
+    mut on_rx: impl FnMut(&[u8]),
+    mut on_ack: impl FnMut(&Frame),
+) -> Result<(Frame, FrameFields), HandshakeError> {
+    let hello_window = Duration::from_millis(300);
+    let confirm_window = Duration::from_millis(300);
+
+    let mut probe = FrameDecoder::new();
+    let mut tmp = [0u8; 256];
+
+    // 1) WAIT FOR HELLO (0xFF at byte 4). READ-ONLY.
+    let t0 = Instant::now();
+    let mut hello_frame: Option<Frame> = None;
+
+    while t0.elapsed() < hello_window {
+        match p.read(&mut tmp) {
+            Ok(n) if n > 0 => {
+                let chunk = &tmp[..n];
+                probe.push(chunk);
+                on_rx(chunk);
+
+                while let Some(frame) = probe.next_frame() {
+                    if frame.is_hello() {
+                        hello_frame = Some(frame);
+                        break;
+                    }
+                }
+
+                if hello_frame.is_some() {
+                    break;
+                }
+            }
+            Ok(_) => {}
+            Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+            Err(_) => break,
+        }
+    }
+
+    // STRICT: if we didn't see HELLO, do NOT send anything.
+    let hello = hello_frame.ok_or(HandshakeError::NoHello)?;
+    if !is_supported(hello.version()) {
+        eprintln!(
+            "[LB] {} announces protocol v{}, not pairing",
+            p.name(),
+            hello.version()
+        );
+        return Err(HandshakeError::UnsupportedVersion(hello.version()));
+    }
+
+    // 2) SEND ACK derived from HELLO (0x00 at byte 4).
+    let ack = hello.to_ack();
+    on_ack(&ack);
+    let _ = p.write_all(ack.as_bytes());
+    let _ = p.flush();
+    eprintln!("[LB] handshake ACK -> {} {}", p.name(), ack.to_hex());
+
+    // Clear buffer so leftover HELLO spam doesn't drown confirm scanning.
+    probe.clear();
+
+    // 3) WAIT FOR CONFIRM (0x00 at byte 4).
+    let t1 = Instant::now();
+    while t1.elapsed() < confirm_window {
+        match p.read(&mut tmp) {
+            Ok(n) if n > 0 => {
+                let chunk = &tmp[..n];
+                probe.push(chunk);
+                on_rx(chunk);
+
+                while let Some(frame) = probe.next_frame() {
+                    if !frame.is_confirm() {
+                        continue;
+                    }
+                    // Seed UI with this frame as status
+                    match frame.decode() {
+                        Ok(fields) => return Ok((frame, fields)),
+                        Err(e) => {
+                            eprintln!("[LB] confirm on {} not decodable: {}", p.name(), e);
+                        }
+                    }
+                }
+            }
+            Ok(_) => {}
+            Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
+            Err(_) => break,
+        }
+    }
+
+    Err(HandshakeError::NoConfirm)
+}
@@ -0,0 +1,267 @@
+// Serial link quality (is it the cable, EMI or the firmware?).
+//
+// Each worker keeps counters since it started (decoder counters plus bytes
+// read, failed handshakes and reconnects) and samples them every
+// LINK_STATS_EVERY_MS. Rates are taken over the samples of the last
+// RATE_WINDOW_MS. Every sample is emitted as `lb/link-stats`, and it goes to
+// the capture when a counter moved since the last one written there.
+//
+// Rough reading:
+// - CRC errors / discarded bytes while frames keep coming: noise on the line
+//   (EMI, cable, wrong framing or baud)
+// - truncations and reconnects: the link drops (cable, adapter, USB power)
+// - failed handshakes on a clean line: firmware not answering or resetting
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::lb_protocol::DecoderStats;
+
+pub const LINK_STATS_EVERY_MS: u64 = 1000;
+const RATE_WINDOW_MS: u64 = 10_000;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkCounters {
+    pub rx_bytes: u64,
+    /// Frames with a valid CRC.
+    pub frames_ok: u64,
+    pub crc_errors: u64,
+    pub resyncs: u64,
+    /// Bytes skipped while looking for a frame.
+    pub discarded_bytes: u64,
+    /// Buffered partial frames thrown away (port dropped mid-frame).
+    pub truncations: u64,
+    pub truncated_bytes: u64,
+    /// Handshake attempts that failed (every port tried by an AUTO scan counts).
+    pub handshake_retries: u64,
+    /// Ports adopted after the first one.
+    pub reconnects: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkRates {
+    /// Span actually covered (shorter than the window right after start).
+    pub window_ms: u64,
+    pub rx_bytes_per_s: f64,
+    pub frames_per_s: f64,
+    pub crc_errors_per_min: f64,
+    pub resyncs_per_min: f64,
+    pub discarded_bytes_per_s: f64,
+    /// CRC errors / (frames + CRC errors) in the window; None without frames.
+    pub crc_error_ratio: Option<f64>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LinkStatsEvent {
+    pub t: DateTime<Local>,
+    pub bank_id: String,
+    pub port_name: Option<String>,
+    pub counters: LinkCounters,
+    pub rates: LinkRates,
+}
+
+// -----------------------------------------------------------------------------
+// Tracker (one per worker)
+// -----------------------------------------------------------------------------
+
+pub struct LinkStatsTracker {
+    rx_bytes: u64,
+    handshake_retries: u64,
+    adopted: u64,
+    // oldest first, covering at least RATE_WINDOW_MS once running that long
+    samples: VecDeque<(Instant, LinkCounters)>,
+    last_sample: Instant,
+    last_captured: Option<LinkCounters>,
+}
+
+impl LinkStatsTracker {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            rx_bytes: 0,
+            handshake_retries: 0,
+            adopted: 0,
+            samples: VecDeque::from([(now, LinkCounters::default())]),
+            last_sample: now,
+            last_captured: None,
+        }
+    }
+
+    pub fn rx(&mut self, bytes: usize) {
+        self.rx_bytes += bytes as u64;
+    }
+
+    pub fn handshake_failed(&mut self, attempts: u64) {
+        self.handshake_retries += attempts;
+    }
+
+    pub fn adopted(&mut self) {
+        self.adopted += 1;
+    }
+
+    pub fn next_due(&self) -> Instant {
+        self.last_sample + Duration::from_millis(LINK_STATS_EVERY_MS)
+    }
+
+    pub fn counters(&self, decoder: &DecoderStats) -> LinkCounters {
+        LinkCounters {
+            rx_bytes: self.rx_bytes,
+            frames_ok: decoder.frames,
+            crc_errors: decoder.crc_errors,
+            resyncs: decoder.resyncs,
+            discarded_bytes: decoder.garbage_bytes,
+            truncations: decoder.truncations,
+            truncated_bytes: decoder.truncated_bytes,
+            handshake_retries: self.handshake_retries,
+            reconnects: self.adopted.saturating_sub(1),
+        }
+    }
+
+    /// Takes a sample if one is due.
+    pub fn sample(
+        &mut self,
+        decoder: &DecoderStats,
+        now: Instant,
+    ) -> Option<(LinkCounters, LinkRates)> {
+        if now < self.next_due() {
+            return None;
+        }
+        self.last_sample = now;
+        let counters = self.counters(decoder);
+        self.samples.push_back((now, counters));
+        // keep one sample at or before the window start
+        let window = Duration::from_millis(RATE_WINDOW_MS);
+        while self.samples.len() > 2 && now.duration_since(self.samples[1].0) >= window {
+            self.samples.pop_front();
+        }
+        let (t0, c0) = self.samples[0];
+        Some((counters, rates(&c0, &counters, now.duration_since(t0))))
+    }
+
+    /// True (once) when `counters` differ from what was last written to the capture.
+    pub fn take_capture(&mut self, counters: &LinkCounters) -> bool {
+        if self.last_captured.as_ref() == Some(counters) {
+            return false;
+        }
+        self.last_captured = Some(*counters);
+        true
+    }
+}
+
+fn rates(from: &LinkCounters, to: &LinkCounters, span: Duration) -> LinkRates {
+    let secs = span.as_secs_f64().max(1e-3);
+    let per_s = |a: u64, b: u64| b.saturating_sub(a) as f64 / secs;
+    let frames = to.frames_ok.saturating_sub(from.frames_ok);
+    let crc_errors = to.crc_errors.saturating_sub(from.crc_errors);
+    LinkRates {
+        window_ms: span.as_millis() as u64,
+        rx_bytes_per_s: per_s(from.rx_bytes, to.rx_bytes),
+        frames_per_s: per_s(from.frames_ok, to.frames_ok),
+        crc_errors_per_min: per_s(from.crc_errors, to.crc_errors) * 60.0,
+        resyncs_per_min: per_s(from.resyncs, to.resyncs) * 60.0,
+        discarded_bytes_per_s: per_s(from.discarded_bytes, to.discarded_bytes),
+        crc_error_ratio: (frames + crc_errors > 0)
+            .then(|| crc_errors as f64 / (frames + crc_errors) as f64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(t0: Instant, ms: u64) -> Instant {
+        t0 + Duration::from_millis(ms)
+    }
+
+    fn decoder(frames: u64, crc_errors: u64) -> DecoderStats {
+        DecoderStats {
+            frames,
+            crc_errors,
+            ..DecoderStats::default()
+        }
+    }
+
+    #[test]
+    fn counts_since_start() {
+        let mut stats = LinkStatsTracker::new(Instant::now());
+        stats.rx(15);
+        stats.rx(30);
+        stats.handshake_failed(3);
+        stats.adopted();
+        let dec = DecoderStats {
+            resyncs: 1,
+            garbage_bytes: 7,
+            truncations: 1,
+            truncated_bytes: 4,
+            ..decoder(3, 2)
+        };
+        let expected = LinkCounters {
+            rx_bytes: 45,
+            frames_ok: 3,
+            crc_errors: 2,
+            resyncs: 1,
+            discarded_bytes: 7,
+            truncations: 1,
+            truncated_bytes: 4,
+            handshake_retries: 3,
+            // the first port isn't a reconnect
+            reconnects: 0,
+        };
+        assert_eq!(stats.counters(&dec), expected);
+        stats.adopted();
+        stats.adopted();
+        assert_eq!(stats.counters(&dec).reconnects, 2);
+    }
+
+    #[test]
+    fn samples_when_due_with_rates_over_the_window() {
+        let t0 = Instant::now();
+        let mut stats = LinkStatsTracker::new(t0);
+        assert!(stats.sample(&decoder(0, 0), at(t0, 999)).is_none());
+
+        // 10 frames/s, one CRC error every second
+        let mut last = None;
+        for s in 1..=15 {
+            stats.rx(150);
+            last = stats.sample(&decoder(10 * s, s), at(t0, 1000 * s));
+            assert!(last.is_some());
+            assert!(stats
+                .sample(&decoder(10 * s, s), at(t0, 1000 * s + 500))
+                .is_none());
+        }
+        let (counters, rates) = last.unwrap();
+        assert_eq!((counters.frames_ok, counters.crc_errors), (150, 15));
+        // only the last RATE_WINDOW_MS count
+        assert_eq!(rates.window_ms, RATE_WINDOW_MS);
+        assert_eq!(rates.frames_per_s, 10.0);
+        assert_eq!(rates.rx_bytes_per_s, 150.0);
+        assert_eq!(rates.crc_errors_per_min, 60.0);
+        assert_eq!(rates.crc_error_ratio, Some(1.0 / 11.0));
+    }
+
+    #[test]
+    fn ratio_needs_frames() {
+        let t0 = Instant::now();
+        let mut stats = LinkStatsTracker::new(t0);
+        let (_, rates) = stats.sample(&decoder(0, 0), at(t0, 1000)).unwrap();
+        assert_eq!(rates.crc_error_ratio, None);
+        assert_eq!(rates.window_ms, 1000);
+    }
+
+    #[test]
+    fn captures_only_changes() {
+        let mut stats = LinkStatsTracker::new(Instant::now());
+        let quiet = stats.counters(&decoder(0, 0));
+        assert!(stats.take_capture(&quiet));
+        assert!(!stats.take_capture(&quiet));
+        let moved = stats.counters(&decoder(1, 0));
+        assert!(stats.take_capture(&moved));
+        assert!(!stats.take_capture(&moved));
+    }
+}
@@ -0,0 +1,698 @@
+// Load-bank wire protocol (pure codec, no I/O).
+//
+// The layout depends on the version byte (see `LAYOUTS`). Every version keeps
+// byte 0 = version, byte 4 = handshake and a trailing Dallas/Maxim CRC8 over
+// everything before it, so frames can be found and paired before the version
+// is known.
+//
+// v1, 15 bytes:
+//  0  version
+//  1  bank_power hi | 2 bank_power lo
+//  3  bank_no
+//  4  handshake
+//  5  contactors_mask hi | 6 lo
+//  7  err_contactors hi  | 8 lo
+//  9  err_fans hi        | 10 lo
+//  11 err_thermals hi    | 12 lo
+//  13 other_errors
+//  14 crc8
+//
+// v2, 16 bytes: v1 bytes 0..13, then
+//  14 bank_health
+//  15 crc8
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+// -----------------------------------------------------------------------------
+// Constants
+// -----------------------------------------------------------------------------
+
+/// v1 frame length (the original boards).
+pub const FRAME_LEN: usize = 15;
+/// Room for the longest layout.
+pub const MAX_FRAME_LEN: usize = 32;
+
+/// Version used for host frames before the device announced its own.
+pub const DEFAULT_VERSION: u8 = 1;
+
+// Handshake rules:
+// - Device sends HELLO repeatedly until it receives ACK.
+// - After ACK, device replies with CONFIRM and then becomes mostly silent.
+pub const HANDSHAKE_BYTE_INDEX: usize = 4;
+pub const HANDSHAKE_HELLO_VALUE: u8 = 0xFF; // device -> app (hello / not paired)
+pub const HANDSHAKE_ACK_VALUE: u8 = 0x00; // app -> device (ack / pair)
+pub const HANDSHAKE_CONFIRM_VALUE: u8 = 0x00; // device -> app (confirm / paired)
+
+// Proposed host watchdog, not in the current firmware: a paired device would
+// open every contactor after WATCHDOG_MS without a valid frame from the app.
+// With the keepalive `feedWatchdog` option the app writes at least every
+// HOST_KEEPALIVE_MS (re-asserting the current mask). Only the simulator
+// implements it so far.
+pub const WATCHDOG_MS: u64 = 2000;
+pub const HOST_KEEPALIVE_MS: u64 = 500;
+
+// Dallas/Maxim CRC8
+const CRC8_TABLE: [u8; 256] = [
+    0, 94, 188, 226, 97, 63, 221, 131, 194, 156, 126, 32, 163, 253, 31, 65, 157, 195, 33, 127, 252,
+    162, 64, 30, 95, 1, 227, 189, 62, 96, 130, 220, 35, 125, 159, 193, 66, 28, 254, 160, 225, 191,
+    93, 3, 128, 222, 60, 98, 190, 224, 2, 92, 223, 129, 99, 61, 124, 34, 192, 158, 29, 67, 161,
+    255, 70, 24, 250, 164, 39, 121, 155, 197, 132, 218, 56, 102, 229, 187, 89, 7, 219, 133, 103,
+    57, 186, 228, 6, 88, 25, 71, 165, 251, 120, 38, 196, 154, 101, 59, 217, 135, 4, 90, 184, 230,
+    167, 249, 27, 69, 198, 152, 122, 36, 248, 166, 68, 26, 153, 199, 37, 123, 58, 100, 134, 216,
+    91, 5, 231, 185, 140, 210, 48, 110, 237, 179, 81, 15, 78, 16, 242, 172, 47, 113, 147, 205, 17,
+    79, 173, 243, 112, 46, 204, 146, 211, 141, 111, 49, 178, 236, 14, 80, 175, 241, 19, 77, 206,
+    144, 114, 44, 109, 51, 209, 143, 12, 82, 176, 238, 50, 108, 142, 208, 83, 13, 239, 177, 240,
+    174, 76, 18, 145, 207, 45, 115, 202, 148, 118, 40, 171, 245, 23, 73, 8, 86, 180, 234, 105, 55,
+    213, 139, 87, 9, 235, 181, 54, 104, 138, 212, 149, 203, 41, 119, 244, 170, 72, 22, 233, 183,
+    85, 11, 136, 214, 52, 106, 43, 117, 151, 201, 74, 20, 246, 168, 116, 42, 200, 150, 21, 75, 169,
+    247, 182, 232, 10, 84, 215, 137, 107, 53,
+];
+
+// -----------------------------------------------------------------------------
+// Helpers
+// -----------------------------------------------------------------------------
+
+pub fn crc8(payload: &[u8]) -> u8 {
+    let mut crc: u8 = 0;
+    for b in payload {
+        crc = CRC8_TABLE[(crc ^ b) as usize];
+    }
+    crc
+}
+
+/// CRC over everything but the trailing CRC byte.
+fn frame_crc(frame: &[u8]) -> u8 {
+    crc8(&frame[..frame.len() - 1])
+}
+
+fn has_valid_crc(window: &[u8]) -> bool {
+    window.last() == Some(&frame_crc(window))
+}
+
+pub fn to_hex(data: &[u8]) -> String {
+    data.iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Inverse of `to_hex`; separators between bytes are optional ("AA 01" / "AA01").
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    let digits: String = hex.split_whitespace().collect();
+    if !digits.len().is_multiple_of(2) || !digits.is_ascii() {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+        .collect()
+}
+
+fn u16_from_be(hi: u8, lo: u8) -> u16 {
+    ((hi as u16) << 8) | (lo as u16)
+}
+
+fn u16_to_be(v: u16) -> (u8, u8) {
+    ((v >> 8) as u8, (v & 0xFF) as u8)
+}
+
+// -----------------------------------------------------------------------------
+// Errors
+// -----------------------------------------------------------------------------
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FrameError {
+    ShortFrame { len: usize, expected: usize },
+    LongFrame { len: usize, expected: usize },
+    BadCrc { expected: u8, found: u8 },
+    UnknownVersion(u8),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameError::ShortFrame { len, expected } => {
+                write!(f, "short frame: {len} bytes (expected {expected})")
+            }
+            FrameError::LongFrame { len, expected } => {
+                write!(f, "long frame: {len} bytes (expected {expected})")
+            }
+            FrameError::BadCrc { expected, found } => {
+                write!(f, "bad CRC: expected {expected:02X}, found {found:02X}")
+            }
+            FrameError::UnknownVersion(v) => write!(
+                f,
+                "unknown protocol version {v} (supported: {})",
+                supported_versions_label()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+// -----------------------------------------------------------------------------
+// Frame fields (decoded view)
+// -----------------------------------------------------------------------------
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameFields {
+    pub version: u8,
+    pub bank_power: u16,
+    pub bank_no: u8,
+    pub handshake: u8,
+    pub contactors_mask: u16,
+    pub err_contactors: u16,
+    pub err_fans: u16,
+    pub err_thermals: u16,
+    pub other_errors: u8,
+    /// v2+ only.
+    pub bank_health: Option<u8>,
+}
+
+impl FrameFields {
+    /// Blank "paired" command template in the layout of a negotiated version.
+    pub fn command_template_for(version: u8) -> Self {
+        Self {
+            version,
+            handshake: HANDSHAKE_ACK_VALUE,
+            ..Self::default()
+        }
+    }
+
+    pub fn encode(&self) -> Frame {
+        Frame::encode(self)
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Layouts (one per protocol version)
+// -----------------------------------------------------------------------------
+
+/// Wire layout of one protocol version. New firmware = new `LAYOUTS` entry.
+pub struct FrameLayout {
+    pub version: u8,
+    /// Total length, CRC included.
+    pub len: usize,
+    /// Reads the fields from a CRC-checked frame of `len` bytes.
+    decode: fn(&[u8]) -> FrameFields,
+    /// Writes the fields into a `len`-byte buffer (the CRC byte is left to the caller).
+    encode: fn(&FrameFields, &mut [u8]),
+}
+
+pub static LAYOUTS: &[FrameLayout] = &[
+    FrameLayout {
+        version: 1,
+        len: FRAME_LEN,
+        decode: decode_v1,
+        encode: encode_v1,
+    },
+    FrameLayout {
+        version: 2,
+        len: 16,
+        decode: decode_v2,
+        encode: encode_v2,
+    },
+];
+
+pub fn layout_for(version: u8) -> Option<&'static FrameLayout> {
+    LAYOUTS.iter().find(|l| l.version == version)
+}
+
+pub fn is_supported(version: u8) -> bool {
+    layout_for(version).is_some()
+}
+
+pub fn supported_versions_label() -> String {
+    LAYOUTS
+        .iter()
+        .map(|l| format!("v{}", l.version))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Expected frame length for a version byte (v1 length when unknown).
+pub fn frame_len(version: u8) -> usize {
+    layout_for(version).map_or(FRAME_LEN, |l| l.len)
+}
+
+/// Lengths worth trying at a frame boundary whose version byte is `version`.
+fn candidate_lens(version: u8) -> Vec<usize> {
+    match layout_for(version) {
+        Some(l) => vec![l.len],
+        // unknown firmware: any known length, so it still shows up as a frame
+        None => {
+            let mut lens: Vec<usize> = LAYOUTS.iter().map(|l| l.len).collect();
+            lens.sort_unstable();
+            lens.dedup();
+            lens
+        }
+    }
+}
+
+fn decode_v1(b: &[u8]) -> FrameFields {
+    FrameFields {
+        version: b[0],
+        bank_power: u16_from_be(b[1], b[2]),
+        bank_no: b[3],
+        handshake: b[4],
+        contactors_mask: u16_from_be(b[5], b[6]),
+        err_contactors: u16_from_be(b[7], b[8]),
+        err_fans: u16_from_be(b[9], b[10]),
+        err_thermals: u16_from_be(b[11], b[12]),
+        other_errors: b[13],
+        bank_health: None,
+    }
+}
+
+fn encode_v1(f: &FrameFields, out: &mut [u8]) {
+    out[0] = f.version;
+    let (p_hi, p_lo) = u16_to_be(f.bank_power);
+    out[1] = p_hi;
+    out[2] = p_lo;
+    out[3] = f.bank_no;
+    out[4] = f.handshake;
+    let (c_hi, c_lo) = u16_to_be(f.contactors_mask);
+    out[5] = c_hi;
+    out[6] = c_lo;
+    let (ec_hi, ec_lo) = u16_to_be(f.err_contactors);
+    out[7] = ec_hi;
+    out[8] = ec_lo;
+    let (ef_hi, ef_lo) = u16_to_be(f.err_fans);
+    out[9] = ef_hi;
+    out[10] = ef_lo;
+    let (et_hi, et_lo) = u16_to_be(f.err_thermals);
+    out[11] = et_hi;
+    out[12] = et_lo;
+    out[13] = f.other_errors;
+}
+
+fn decode_v2(b: &[u8]) -> FrameFields {
+    FrameFields {
+        bank_health: Some(b[14]),
+        ..decode_v1(b)
+    }
+}
+
+fn encode_v2(f: &FrameFields, out: &mut [u8]) {
+    encode_v1(f, out);
+    out[14] = f.bank_health.unwrap_or(0);
+}
+
+// -----------------------------------------------------------------------------
+// Frame (raw, CRC-checked bytes)
+// -----------------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Frame {
+    bytes: [u8; MAX_FRAME_LEN],
+    len: usize,
+}
+
+impl Frame {
+    /// Encodes in the layout of `f.version` (v1 layout for unknown versions).
+    pub fn encode(f: &FrameFields) -> Self {
+        let layout = layout_for(f.version).unwrap_or(&LAYOUTS[0]);
+        let mut bytes = [0u8; MAX_FRAME_LEN];
+        let out = &mut bytes[..layout.len];
+        (layout.encode)(f, out);
+        out[layout.len - 1] = frame_crc(out);
+        Self {
+            bytes,
+            len: layout.len,
+        }
+    }
+
+    /// Validate length + CRC. The length is taken from the version byte; frames
+    /// of unknown versions pass with any known length (`decode` rejects them).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, FrameError> {
+        let Some(&version) = bytes.first() else {
+            return Err(FrameError::ShortFrame {
+                len: 0,
+                expected: FRAME_LEN,
+            });
+        };
+        let lens = candidate_lens(version);
+        if !lens.contains(&bytes.len()) {
+            let expected = frame_len(version);
+            return Err(if bytes.len() < expected {
+                FrameError::ShortFrame {
+                    len: bytes.len(),
+                    expected,
+                }
+            } else {
+                FrameError::LongFrame {
+                    len: bytes.len(),
+                    expected,
+                }
+            });
+        }
+        let expected = frame_crc(bytes);
+        let found = bytes[bytes.len() - 1];
+        if found != expected {
+            return Err(FrameError::BadCrc { expected, found });
+        }
+        Ok(Self::from_valid(bytes))
+    }
+
+    fn from_valid(window: &[u8]) -> Self {
+        let mut bytes = [0u8; MAX_FRAME_LEN];
+        bytes[..window.len()].copy_from_slice(window);
+        Self {
+            bytes,
+            len: window.len(),
+        }
+    }
+
+    /// Decode into typed fields; rejects versions this codec doesn't know.
+    pub fn decode(&self) -> Result<FrameFields, FrameError> {
+        let version = self.version();
+        let layout = layout_for(version).ok_or(FrameError::UnknownVersion(version))?;
+        if self.len != layout.len {
+            return Err(FrameError::LongFrame {
+                len: self.len,
+                expected: layout.len,
+            });
+        }
+        Ok((layout.decode)(self.as_bytes()))
+    }
+
+    pub fn version(&self) -> u8 {
+        self.bytes[0]
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    pub fn handshake(&self) -> u8 {
+        self.bytes[HANDSHAKE_BYTE_INDEX]
+    }
+
+    pub fn is_hello(&self) -> bool {
+        self.handshake() == HANDSHAKE_HELLO_VALUE
+    }
+
+    pub fn is_confirm(&self) -> bool {
+        self.handshake() == HANDSHAKE_CONFIRM_VALUE
+    }
+
+    /// Same frame with a different handshake byte (CRC recomputed).
+    pub fn with_handshake(mut self, value: u8) -> Self {
+        let len = self.len;
+        self.bytes[HANDSHAKE_BYTE_INDEX] = value;
+        self.bytes[len - 1] = frame_crc(&self.bytes[..len]);
+        self
+    }
+
+    /// Convert any valid frame into an ACK (byte4=0x00).
+    pub fn to_ack(self) -> Self {
+        self.with_handshake(HANDSHAKE_ACK_VALUE)
+    }
+
+    pub fn to_hex(self) -> String {
+        to_hex(self.as_bytes())
+    }
+}
+
+impl AsRef<[u8]> for Frame {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Streaming decoder
+// -----------------------------------------------------------------------------
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DecoderStats {
+    /// Frames with a valid CRC handed out.
+    pub frames: u64,
+    /// Complete frames of a known version, where one was expected, whose CRC
+    /// didn't match (the bytes after it count as a resync once a frame is found).
+    pub crc_errors: u64,
+    /// Times the decoder had to skip bytes to find the next frame boundary.
+    pub resyncs: u64,
+    /// Bytes discarded because they were not part of any valid frame.
+    pub garbage_bytes: u64,
+    /// Times `clear` threw away buffered bytes (port dropped mid-frame etc.).
+    pub truncations: u64,
+    pub truncated_bytes: u64,
+}
+
+enum Probe {
+    Frame(usize),
+    Incomplete,
+    NoFrame,
+}
+
+/// Accepts arbitrary byte chunks and yields CRC-valid frames.
+///
+/// Bytes that can no longer start a valid frame are discarded (and counted),
+/// so the internal buffer never holds more than one partial frame of garbage.
+#[derive(Default)]
+pub struct FrameDecoder {
+    buf: Vec<u8>,
+    stats: DecoderStats,
+    skipped_since_frame: bool,
+}
+
+impl FrameDecoder {
+    pub fn new() -> Self {
+        Self {
+            buf: Vec::with_capacity(4096),
+            ..Self::default()
+        }
+    }
+
+    pub fn push(&mut self, chunk: &[u8]) {
+        self.buf.extend_from_slice(chunk);
+    }
+
+    /// Push a chunk and collect every complete frame now available.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<Frame> {
+        self.push(chunk);
+        let mut out = vec![];
+        while let Some(f) = self.next_frame() {
+            out.push(f);
+        }
+        out
+    }
+
+    pub fn next_frame(&mut self) -> Option<Frame> {
+        // first offset that can still start a frame (complete or not)
+        let mut off = 0;
+        while off < self.buf.len() {
+            match self.probe(off) {
+                Probe::Frame(len) => {
+                    if off > 0 || self.skipped_since_frame {
+                        self.stats.resyncs += 1;
+                        self.skipped_since_frame = false;
+                    }
+                    self.stats.garbage_bytes += off as u64;
+
+                    let frame = Frame::from_valid(&self.buf[off..off + len]);
+                    self.buf.drain(0..off + len);
+                    self.stats.frames += 1;
+                    return Some(frame);
+                }
+                Probe::Incomplete => break,
+                Probe::NoFrame => {
+                    if off == 0 && !self.skipped_since_frame && is_supported(self.buf[0]) {
+                        self.stats.crc_errors += 1;
+                    }
+                    off += 1
+                }
+            }
+        }
+
+        // Every complete window before `off` failed CRC: those bytes can no
+        // longer become the start of a frame.
+        if off > 0 {
+            self.buf.drain(0..off);
+            self.stats.garbage_bytes += off as u64;
+            self.skipped_since_frame = true;
+        }
+        None
+    }
+
+    fn probe(&self, off: usize) -> Probe {
+        let rest = &self.buf[off..];
+        let mut incomplete = false;
+        for len in candidate_lens(rest[0]) {
+            if rest.len() < len {
+                incomplete = true;
+            } else if has_valid_crc(&rest[..len]) {
+                return Probe::Frame(len);
+            }
+        }
+        if incomplete {
+            Probe::Incomplete
+        } else {
+            Probe::NoFrame
+        }
+    }
+
+    /// Drop buffered bytes (counted as a truncation, stats are kept).
+    pub fn clear(&mut self) {
+        if !self.buf.is_empty() {
+            self.stats.truncations += 1;
+            self.stats.truncated_bytes += self.buf.len() as u64;
+        }
+        self.buf.clear();
+        self.skipped_since_frame = false;
+    }
+
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn stats(&self) -> &DecoderStats {
+        &self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(version: u8) -> FrameFields {
+        FrameFields {
+            version,
+            bank_power: 4000,
+            bank_no: 2,
+            handshake: HANDSHAKE_CONFIRM_VALUE,
+            contactors_mask: 0x0103,
+            err_contactors: 0x8001,
+            err_fans: 0x0002,
+            err_thermals: 0x0400,
+            other_errors: 0x5A,
+            bank_health: (version >= 2).then_some(87),
+        }
+    }
+
+    #[test]
+    fn crc8_known_vectors() {
+        assert_eq!(crc8(b""), 0x00);
+        assert_eq!(crc8(&[0x01]), 0x5E);
+        // Dallas/Maxim check value
+        assert_eq!(crc8(b"123456789"), 0xA1);
+        // a frame including its CRC sums to 0
+        assert_eq!(crc8(status(1).encode().as_bytes()), 0);
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        for version in [1, 2] {
+            let f = status(version);
+            let frame = f.encode();
+            assert_eq!(frame.as_bytes().len(), frame_len(version));
+            assert_eq!(frame.decode().unwrap(), f);
+            assert_eq!(Frame::from_bytes(frame.as_bytes()).unwrap(), frame);
+            assert_eq!(from_hex(&frame.to_hex()).unwrap(), frame.as_bytes());
+        }
+        // other_errors in both, health after it in v2
+        assert_eq!(status(1).encode().as_bytes()[13], 0x5A);
+        assert_eq!(status(2).encode().as_bytes()[14], 87);
+    }
+
+    #[test]
+    fn frame_errors() {
+        let good = status(1).encode();
+        let bytes = good.as_bytes();
+
+        assert_eq!(
+            Frame::from_bytes(&bytes[..10]),
+            Err(FrameError::ShortFrame {
+                len: 10,
+                expected: FRAME_LEN
+            })
+        );
+        assert_eq!(
+            Frame::from_bytes(&[]),
+            Err(FrameError::ShortFrame {
+                len: 0,
+                expected: FRAME_LEN
+            })
+        );
+
+        let mut bad = bytes.to_vec();
+        bad[6] ^= 0x40;
+        assert_eq!(
+            Frame::from_bytes(&bad),
+            Err(FrameError::BadCrc {
+                expected: frame_crc(&bad),
+                found: bytes[FRAME_LEN - 1]
+            })
+        );
+
+        // unknown version: framed (valid CRC) but not decodable
+        let mut future = bytes.to_vec();
+        future[0] = 9;
+        let n = future.len();
+        future[n - 1] = frame_crc(&future);
+        let frame = Frame::from_bytes(&future).unwrap();
+        assert_eq!(frame.decode(), Err(FrameError::UnknownVersion(9)));
+    }
+
+    #[test]
+    fn decoder_byte_by_byte() {
+        let frames = [status(1).encode(), status(2).encode(), status(1).encode()];
+        let stream: Vec<u8> = frames.iter().flat_map(|f| f.as_bytes().to_vec()).collect();
+
+        let mut d = FrameDecoder::new();
+        let mut out = vec![];
+        for b in &stream {
+            out.extend(d.feed(&[*b]));
+        }
+        assert_eq!(out, frames);
+        assert_eq!(d.buffered(), 0);
+        assert_eq!(
+            d.stats(),
+            &DecoderStats {
+                frames: 3,
+                ..DecoderStats::default()
+            }
+        );
+    }
+
+    #[test]
+    fn decoder_resyncs_after_garbage_and_split_chunks() {
+        let good = status(1).encode();
+        let mut bad = good.as_bytes().to_vec();
+        bad[6] ^= 0x40;
+
+        let mut stream = vec![0x55, 0x66, 0x77];
+        stream.extend(good.as_bytes());
+        stream.extend(&bad);
+        stream.extend(good.as_bytes());
+        stream.extend(good.as_bytes());
+
+        // split mid-frame at odd offsets
+        let mut d = FrameDecoder::new();
+        let mut out = vec![];
+        for chunk in stream.chunks(7) {
+            out.extend(d.feed(chunk));
+        }
+        assert_eq!(out, vec![good; 3]);
+        let s = d.stats();
+        assert_eq!(s.frames, 3);
+        assert_eq!(s.crc_errors, 1);
+        // before the first frame and after the corrupted one
+        assert_eq!(s.resyncs, 2);
+        assert_eq!(s.garbage_bytes, 3 + FRAME_LEN as u64);
+        assert_eq!(d.buffered(), 0);
+
+        // a partial frame dropped with the port
+        d.push(&good.as_bytes()[..5]);
+        assert_eq!(d.next_frame(), None);
+        d.clear();
+        assert_eq!((d.stats().truncations, d.stats().truncated_bytes), (1, 5));
+        assert_eq!(d.feed(good.as_bytes()), vec![good]);
+        assert_eq!(d.stats().resyncs, 2);
+    }
+}
@@ -0,0 +1,429 @@
+// Replays recorded captures (see lb_capture) through the runtime.
+//
+// `replay://<capture file>[?speed=<x>][&port=<name>]` opens a `ReplayTransport`
+// that hands the recorded RX chunks back to the worker with their original
+// timing (divided by `speed`). Host writes are accepted and discarded, so the
+// worker runs its normal handshake / parse path and emits the same events.
+//
+// `lb_replay_check` feeds a capture straight through `FrameDecoder` and checks
+// that every frame decoded at record time comes out again, in order (parser
+// regression check). Re-decoding also yields the handshake frames, which the
+// worker consumes without recording, so extra decoded frames are expected.
+
+#[cfg(any(test, feature = "gui"))]
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    fs,
+    io::{self, Read, Write},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::lb_capture::{CaptureEvent, CaptureRecord};
+use crate::lb_protocol::from_hex;
+#[cfg(any(test, feature = "gui"))]
+use crate::lb_protocol::{FrameDecoder, FrameFields};
+use crate::lb_transport::Transport;
+
+pub const REPLAY_SCHEME: &str = "replay://";
+
+const MIN_SPEED: f64 = 0.01;
+
+// -----------------------------------------------------------------------------
+// Capture loading
+// -----------------------------------------------------------------------------
+
+/// Records of a JSONL capture; malformed lines are skipped (counted in the second value).
+pub fn load_capture(path: &str) -> Result<(Vec<CaptureRecord>, usize), String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("read {path}: {e}"))?;
+    let mut bad_lines = 0;
+    let records = text
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .filter_map(|l| match serde_json::from_str::<CaptureRecord>(l) {
+            Ok(r) => Some(r),
+            Err(_) => {
+                bad_lines += 1;
+                None
+            }
+        })
+        .collect();
+    Ok((records, bad_lines))
+}
+
+/// RX chunks of one port as (ms since session start, bytes).
+/// `port` None => the port of the first RX record.
+fn rx_chunks(records: &[CaptureRecord], port: Option<&str>) -> Vec<(u64, Vec<u8>)> {
+    let port = port.map(str::to_string).or_else(|| {
+        records
+            .iter()
+            .find(|r| matches!(r.event, CaptureEvent::Rx { .. }))
+            .map(|r| r.port_name.clone())
+    });
+    records
+        .iter()
+        .filter(|r| Some(&r.port_name) == port.as_ref())
+        .filter_map(|r| match &r.event {
+            CaptureEvent::Rx { hex } => from_hex(hex).map(|b| (r.ms, b)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Makes the chunk times monotonic. A capture appended across sessions restarts
+/// at ms 0: the step back is replayed as no gap, the gaps after it are kept.
+fn monotonic(chunks: Vec<(u64, Vec<u8>)>) -> VecDeque<(u64, Vec<u8>)> {
+    let mut shift = 0;
+    let mut prev = 0;
+    chunks
+        .into_iter()
+        .map(|(ms, bytes)| {
+            if ms + shift < prev {
+                shift = prev - ms;
+            }
+            prev = ms + shift;
+            (prev, bytes)
+        })
+        .collect()
+}
+
+// -----------------------------------------------------------------------------
+// Transport
+// -----------------------------------------------------------------------------
+
+pub struct ReplayTransport {
+    name: String,
+    chunks: VecDeque<(u64, Vec<u8>)>,
+    base_ms: u64,
+    started: Instant,
+    speed: f64,
+    read_timeout: Duration,
+    pending: VecDeque<u8>,
+}
+
+impl ReplayTransport {
+    /// `spec` is the part after `replay://`: `<path>[?speed=<x>][&port=<name>]`.
+    pub fn open(spec: &str, read_timeout: Duration) -> Result<Self, String> {
+        let (path, query) = spec.split_once('?').unwrap_or((spec, ""));
+        let mut speed = 1.0;
+        let mut port = None;
+        for kv in query.split('&').filter(|kv| !kv.is_empty()) {
+            match kv.split_once('=') {
+                Some(("speed", v)) => {
+                    speed = v
+                        .parse::<f64>()
+                        .map_err(|_| format!("invalid replay speed: {v}"))?
+                        .max(MIN_SPEED)
+                }
+                Some(("port", v)) => port = Some(v.to_string()),
+                _ => return Err(format!("unknown replay option: {kv}")),
+            }
+        }
+
+        let (records, bad_lines) = load_capture(path)?;
+        let chunks = monotonic(rx_chunks(&records, port.as_deref()));
+        if chunks.is_empty() {
+            return Err(format!("{path}: no RX data to replay"));
+        }
+        eprintln!(
+            "[LB/REPLAY] {} rx chunks from {} at {}x ({} bad lines)",
+            chunks.len(),
+            path,
+            speed,
+            bad_lines
+        );
+
+        Ok(Self {
+            name: format!("{REPLAY_SCHEME}{spec}"),
+            base_ms: chunks.front().map_or(0, |c| c.0),
+            chunks,
+            started: Instant::now(),
+            speed,
+            read_timeout,
+            pending: VecDeque::new(),
+        })
+    }
+
+    /// Time until the next chunk is due (zero when due).
+    fn next_due_in(&self) -> Option<Duration> {
+        let (ms, _) = self.chunks.front()?;
+        let due = Duration::from_secs_f64((ms - self.base_ms) as f64 / 1000.0 / self.speed);
+        Some(due.saturating_sub(self.started.elapsed()))
+    }
+}
+
+impl Transport for ReplayTransport {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn try_clone_writer(&self) -> io::Result<Box<dyn Write + Send>> {
+        Ok(Box::new(io::sink()))
+    }
+}
+
+impl Read for ReplayTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pending.is_empty() {
+            let Some(wait) = self.next_due_in() else {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "replay finished",
+                ));
+            };
+            if wait > self.read_timeout {
+                thread::sleep(self.read_timeout);
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "timed out"));
+            }
+            thread::sleep(wait);
+            let (_, bytes) = self.chunks.pop_front().unwrap();
+            self.pending.extend(bytes);
+        }
+        let n = buf.len().min(self.pending.len());
+        for (dst, src) in buf.iter_mut().zip(self.pending.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for ReplayTransport {
+    // the recording already contains the device's answers
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Parser regression check
+// -----------------------------------------------------------------------------
+
+#[cfg(any(test, feature = "gui"))]
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameMismatch {
+    /// Index among the recorded frames.
+    pub index: usize,
+    pub recorded: FrameFields,
+}
+
+#[cfg(any(test, feature = "gui"))]
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayReport {
+    pub port_name: Option<String>,
+    pub rx_chunks: usize,
+    pub rx_bytes: usize,
+    pub bad_lines: usize,
+    pub recorded_frames: usize,
+    pub decoded_frames: usize,
+    /// Decoded frames not in the recording (handshake frames, or parser drift).
+    pub extra_decoded: usize,
+    pub decode_errors: Vec<String>,
+    pub resyncs: u64,
+    pub garbage_bytes: u64,
+    /// Recorded frames the decoder no longer produces (first 20).
+    pub mismatches: Vec<FrameMismatch>,
+    pub matches: bool,
+}
+
+#[cfg(any(test, feature = "gui"))]
+const MAX_MISMATCHES: usize = 20;
+
+/// Re-decodes the RX stream of a capture and diffs it against the recorded frames.
+#[cfg(any(test, feature = "gui"))]
+#[cfg_attr(feature = "gui", tauri::command)]
+pub fn lb_replay_check(path: String, port: Option<String>) -> Result<ReplayReport, String> {
+    let (records, bad_lines) = load_capture(&path)?;
+    let chunks = rx_chunks(&records, port.as_deref());
+    let port_name = port.or_else(|| {
+        records
+            .iter()
+            .find(|r| matches!(r.event, CaptureEvent::Rx { .. }))
+            .map(|r| r.port_name.clone())
+    });
+
+    let recorded: Vec<FrameFields> = records
+        .iter()
+        .filter(|r| Some(&r.port_name) == port_name.as_ref())
+        .filter_map(|r| match &r.event {
+            CaptureEvent::Frame { fields, .. } => Some(fields.clone()),
+            _ => None,
+        })
+        .collect();
+
+    let mut decoder = FrameDecoder::new();
+    let mut decoded = Vec::new();
+    let mut decode_errors = Vec::new();
+    for (_, bytes) in &chunks {
+        for frame in decoder.feed(bytes) {
+            match frame.decode() {
+                Ok(f) => decoded.push(f),
+                Err(e) => decode_errors.push(format!("{}: {e}", frame.to_hex())),
+            }
+        }
+    }
+
+    // recorded must be an ordered subsequence of decoded
+    let mut mismatches = Vec::new();
+    let mut missing = 0;
+    let mut cursor = 0;
+    for (index, r) in recorded.iter().enumerate() {
+        match decoded[cursor..].iter().position(|d| d == r) {
+            Some(pos) => cursor += pos + 1,
+            None => {
+                missing += 1;
+                if mismatches.len() < MAX_MISMATCHES {
+                    mismatches.push(FrameMismatch {
+                        index,
+                        recorded: r.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    let stats = decoder.stats();
+    Ok(ReplayReport {
+        port_name,
+        rx_chunks: chunks.len(),
+        rx_bytes: chunks.iter().map(|(_, b)| b.len()).sum(),
+        bad_lines,
+        recorded_frames: recorded.len(),
+        decoded_frames: decoded.len(),
+        extra_decoded: decoded.len() - (recorded.len() - missing),
+        decode_errors,
+        resyncs: stats.resyncs,
+        garbage_bytes: stats.garbage_bytes,
+        matches: mismatches.is_empty(),
+        mismatches,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lb_protocol::{DecoderStats, HANDSHAKE_HELLO_VALUE};
+
+    const HELLO: u8 = HANDSHAKE_HELLO_VALUE;
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    // RX stream of a fixture through a fresh decoder: (handshake, mask) per frame
+    fn decode_fixture(name: &str) -> (Vec<(u8, u16)>, DecoderStats) {
+        let (records, bad_lines) = load_capture(&fixture(name)).unwrap();
+        assert_eq!(bad_lines, 0);
+        let mut decoder = FrameDecoder::new();
+        let mut frames = vec![];
+        for (_, bytes) in rx_chunks(&records, None) {
+            for frame in decoder.feed(&bytes) {
+                let f = frame.decode().unwrap();
+                frames.push((f.handshake, f.contactors_mask));
+            }
+        }
+        (frames, decoder.stats().clone())
+    }
+
+    #[test]
+    fn clean_session() {
+        let (frames, stats) = decode_fixture("clean_session.jsonl");
+        assert_eq!(frames, [(HELLO, 0), (0, 0), (0, 3), (0, 3), (0, 0)]);
+        assert_eq!(
+            stats,
+            DecoderStats {
+                frames: 5,
+                ..DecoderStats::default()
+            }
+        );
+
+        let report = lb_replay_check(fixture("clean_session.jsonl"), None).unwrap();
+        assert!(report.matches);
+        assert_eq!(report.port_name.as_deref(), Some("COM3"));
+        assert_eq!((report.recorded_frames, report.extra_decoded), (4, 1));
+        assert!(report.decode_errors.is_empty());
+    }
+
+    #[test]
+    fn missed_confirm() {
+        let (frames, stats) = decode_fixture("missed_confirm.jsonl");
+        assert_eq!(
+            frames,
+            [
+                (HELLO, 0),
+                (HELLO, 0),
+                (HELLO, 0),
+                (HELLO, 0),
+                (0, 0),
+                (0, 1)
+            ]
+        );
+        assert_eq!((stats.frames, stats.crc_errors, stats.resyncs), (6, 0, 0));
+
+        let report = lb_replay_check(fixture("missed_confirm.jsonl"), None).unwrap();
+        assert!(report.matches);
+        assert_eq!((report.recorded_frames, report.extra_decoded), (2, 4));
+    }
+
+    #[test]
+    fn garbled_frame() {
+        let (frames, stats) = decode_fixture("garbled_frame.jsonl");
+        assert_eq!(frames, [(HELLO, 0), (0, 0), (0, 5), (0, 5)]);
+        // the corrupted status plus two noise bytes
+        assert_eq!(
+            stats,
+            DecoderStats {
+                frames: 4,
+                crc_errors: 1,
+                resyncs: 1,
+                garbage_bytes: 17,
+                ..DecoderStats::default()
+            }
+        );
+
+        let report = lb_replay_check(fixture("garbled_frame.jsonl"), None).unwrap();
+        assert!(report.matches);
+        assert_eq!((report.resyncs, report.garbage_bytes), (1, 17));
+    }
+
+    #[test]
+    fn transport_replays_every_rx_byte() {
+        let spec = format!("{}?speed=1000", fixture("clean_session.jsonl"));
+        let mut p = ReplayTransport::open(&spec, Duration::from_millis(20)).unwrap();
+        let mut decoder = FrameDecoder::new();
+        let mut buf = [0u8; 64];
+        loop {
+            match p.read(&mut buf) {
+                Ok(n) => decoder.push(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => {}
+                Err(e) => {
+                    assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+                    break;
+                }
+            }
+        }
+        assert_eq!(std::iter::from_fn(|| decoder.next_frame()).count(), 5);
+        // writes are swallowed
+        assert_eq!(p.write(&[1, 2, 3]).unwrap(), 3);
+    }
+
+    #[test]
+    fn restarted_session_keeps_its_gaps() {
+        let p = ReplayTransport::open(&fixture("restarted_session.jsonl"), Duration::ZERO).unwrap();
+        let times: Vec<u64> = p.chunks.iter().map(|(ms, _)| *ms).collect();
+        assert_eq!(times, [0, 9, 1009, 1009, 1017, 2017]);
+
+        let (frames, stats) = decode_fixture("restarted_session.jsonl");
+        assert_eq!(
+            frames,
+            [(HELLO, 0), (0, 0), (0, 0), (HELLO, 0), (0, 0), (0, 3)]
+        );
+        assert_eq!(stats.garbage_bytes, 0);
+    }
+}
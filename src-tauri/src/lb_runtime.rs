@@ -1,1202 +1,756 @@
 use serde::Serialize;
 use std::{
-    io::{Read, Write},
-    sync::{mpsc, Mutex},
-    thread,
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
-use tauri::{AppHandle, Emitter, State};
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::oneshot;
+
+use crate::lb_budget::{BudgetSnapshot, DutyCycleBudget};
+use crate::lb_capture::LoadBankCaptureState;
+use crate::lb_discovery::LoadBankDiscoveryState;
+use crate::lb_eventlog::LoadBankEventLogState;
+use crate::lb_failsafe::Failsafe;
+use crate::lb_link::{LinkState, LoadBankStatus, SerialPortInfo};
+use crate::lb_link_stats::LinkStatsEvent;
+use crate::lb_safety::{EstopEvent, EstopLatch, SafetyConfig};
+use crate::lb_sequence::{SequenceCmd, SequenceProgress};
+use crate::lb_serial::{LoadBankSerialState, SerialConfig};
+use crate::lb_sim::{LoadBankSimState, SimDevice};
+use crate::lb_worker::{
+    worker_task, CmdLatency, ContactorCmdError, HandshakeOutcome, KeepaliveConfig, PollConfig,
+    RuntimeCmd, RuntimeMode, WorkerConfig, WorkerEvent, WorkerHandle, WorkerHost,
+    DEFAULT_CONFIRM_RETRIES, DEFAULT_CONFIRM_TIMEOUT_MS,
+};
+use crate::load_model::RDP4000;
 
 // -----------------------------------------------------------------------------
-// Public state (single owner)
+// Public state (one worker per bank id)
 // -----------------------------------------------------------------------------
 
+/// Bank id used when a command doesn't name one (single-bank stations).
+const DEFAULT_BANK_ID: &str = "default";
+// lowest `lb_set_keepalive` probe delay
+const MIN_KEEPALIVE_MS: u64 = 50;
+
 #[derive(Default)]
 pub struct LoadBankRuntimeState {
-    inner: Mutex<Option<RuntimeHandle>>,
+    inner: Mutex<HashMap<String, RuntimeHandle>>,
+    // ports currently held by some worker (AUTO scans skip them)
+    claimed_ports: Arc<Mutex<HashSet<String>>>,
+    // survive runtime restarts (baud change etc.)
+    keepalive: Mutex<KeepaliveConfig>,
+    safety: Mutex<SafetyConfig>,
+    // per bank id; kept across restarts so a reconnect doesn't refill the budget
+    budgets: Mutex<HashMap<String, Arc<Mutex<DutyCycleBudget>>>>,
+    // per bank id; a latched stop outlives the runtime that saw it
+    estops: Mutex<HashMap<String, Arc<EstopLatch>>>,
+    // adopted ports, for the panic hook and app exit
+    failsafe: Failsafe,
 }
 
 struct RuntimeHandle {
     baud: u32,
     mode: RuntimeMode,
-    tx: mpsc::Sender<RuntimeCmd>,
-    join: thread::JoinHandle<()>,
+    serial: Option<SerialConfig>,
+    worker: WorkerHandle,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
-enum RuntimeMode {
-    Auto,
-    Fixed { port_name: String },
+impl RuntimeHandle {
+    fn send(&self, cmd: RuntimeCmd) -> Result<(), String> {
+        self.worker.send(cmd)
+    }
 }
 
-impl RuntimeMode {
-    fn from_port_name(port_name: &str) -> Self {
-        if port_name.trim().is_empty() {
-            RuntimeMode::Auto
-        } else {
-            RuntimeMode::Fixed {
-                port_name: port_name.trim().to_string(),
-            }
-        }
+impl LoadBankRuntimeState {
+    fn send(&self, bank_id: &str, cmd: RuntimeCmd) -> Result<(), String> {
+        let guard = self.inner.lock().unwrap();
+        let h = guard
+            .get(bank_id)
+            .ok_or_else(|| format!("Load bank runtime '{bank_id}' not running"))?;
+        h.send(cmd)
     }
 
-    fn key(&self) -> String {
-        match self {
-            RuntimeMode::Auto => "auto".to_string(),
-            RuntimeMode::Fixed { port_name } => format!("fixed:{port_name}"),
+    fn stop(&self, bank_id: &str) {
+        let old = self.inner.lock().unwrap().remove(bank_id);
+        if let Some(old) = old {
+            old.worker.stop();
         }
     }
-}
-
-enum RuntimeCmd {
-    Stop,
-    SetMode(RuntimeMode),
-    SetPolling {
-        enabled: bool,
-        interval_ms: u64,
-    },
-    /// Sends raw bytes as-is (used by DevEchoPcbTest).
-    WriteRaw(Vec<u8>),
-    /// Production command: set contactors mask, backend builds frame.
-    SetContactors(u16),
-}
-
-// -----------------------------------------------------------------------------
-// Payloads (events + query)
-// -----------------------------------------------------------------------------
-
-#[derive(Serialize, Clone, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct SerialPortInfo {
-    pub port_name: String,
-    pub port_type: String,
-    pub vid: Option<u16>,
-    pub pid: Option<u16>,
-    pub serial_number: Option<String>,
-    pub manufacturer: Option<String>,
-    pub product: Option<String>,
-}
-
-#[derive(Serialize, Clone, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct PortsEvent {
-    pub ports: Vec<String>,
-}
-
-#[derive(Serialize, Clone, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct LoadBankStatus {
-    pub port_name: String,
-    pub version: u8,
-    pub bank_power: u16,
-    pub bank_no: u8,
-    pub handshake: u8,
-    pub contactors_mask: u16,
-    pub err_contactors: u16,
-    pub err_fans: u16,
-    pub err_thermals: u16,
-    pub other_errors: u8,
-    pub raw_frame_hex: String,
-}
-
-#[derive(Serialize, Clone, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct LoadBankHealth {
-    pub port_name: String,
-    pub online: bool,
-    pub last_seen_ms: u128,
-    pub reason: Option<String>,
-}
-
-#[derive(Serialize, Clone, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct SerialRxChunk {
-    pub port_name: String,
-    pub bytes: Vec<u8>,
-    pub hex: String,
-}
-
-#[derive(Serialize, Clone, Debug)]
-#[serde(rename_all = "camelCase")]
-pub struct SerialTxChunk {
-    pub port_name: String,
-    pub bytes: Vec<u8>,
-    pub hex: String,
-}
-
-// -----------------------------------------------------------------------------
-// Protocol constants + helpers
-// -----------------------------------------------------------------------------
 
-// 15 bytes total:
-// [0..13] payload (14 bytes), [14] CRC
-const LB_FRAME_LEN: usize = 15;
-
-// OLD Handshake rules:
-// - send a byte frame where a specific byte is 0x00
-// - device replies with same frame but that byte becomes 0xFF
-// NEW Handshake rules:
-// - Device sends HELLO repeatedly until it receives ACK.
-// - After ACK, device replies with CONFIRM and then becomes mostly silent.
-const HANDSHAKE_BYTE_INDEX: usize = 4; // default = handshake
-                                       //const HANDSHAKE_REQ_VALUE: u8 = 0x00;
-                                       //const HANDSHAKE_RESP_VALUE: u8 = 0x00; // 0xFF - OLD
-const HANDSHAKE_HELLO_VALUE: u8 = 0xFF; // device -> app (hello / not paired)
-const HANDSHAKE_ACK_VALUE: u8 = 0x00; // app -> device (ack / pair)
-const HANDSHAKE_CONFIRM_VALUE: u8 = 0x00; // device -> app (confirm / paired)
-
-// offline_after kept for future keepalive-based offline detection (currently unused)
-const DEFAULT_SCAN_EVERY_MS: u64 = 600;
-//const DEFAULT_OFFLINE_AFTER_MS: u64 = 900;
-const DEFAULT_READ_TIMEOUT_MS: u64 = 30;
-
-// Dallas/Maxim CRC8
-const CRC8_TABLE: [u8; 256] = [
-    0, 94, 188, 226, 97, 63, 221, 131, 194, 156, 126, 32, 163, 253, 31, 65, 157, 195, 33, 127, 252,
-    162, 64, 30, 95, 1, 227, 189, 62, 96, 130, 220, 35, 125, 159, 193, 66, 28, 254, 160, 225, 191,
-    93, 3, 128, 222, 60, 98, 190, 224, 2, 92, 223, 129, 99, 61, 124, 34, 192, 158, 29, 67, 161,
-    255, 70, 24, 250, 164, 39, 121, 155, 197, 132, 218, 56, 102, 229, 187, 89, 7, 219, 133, 103,
-    57, 186, 228, 6, 88, 25, 71, 165, 251, 120, 38, 196, 154, 101, 59, 217, 135, 4, 90, 184, 230,
-    167, 249, 27, 69, 198, 152, 122, 36, 248, 166, 68, 26, 153, 199, 37, 123, 58, 100, 134, 216,
-    91, 5, 231, 185, 140, 210, 48, 110, 237, 179, 81, 15, 78, 16, 242, 172, 47, 113, 147, 205, 17,
-    79, 173, 243, 112, 46, 204, 146, 211, 141, 111, 49, 178, 236, 14, 80, 175, 241, 19, 77, 206,
-    144, 114, 44, 109, 51, 209, 143, 12, 82, 176, 238, 50, 108, 142, 208, 83, 13, 239, 177, 240,
-    174, 76, 18, 145, 207, 45, 115, 202, 148, 118, 40, 171, 245, 23, 73, 8, 86, 180, 234, 105, 55,
-    213, 139, 87, 9, 235, 181, 54, 104, 138, 212, 149, 203, 41, 119, 244, 170, 72, 22, 233, 183,
-    85, 11, 136, 214, 52, 106, 43, 117, 151, 201, 74, 20, 246, 168, 116, 42, 200, 150, 21, 75, 169,
-    247, 182, 232, 10, 84, 215, 137, 107, 53,
-];
-
-fn crc8_load_bank(frame: &[u8]) -> u8 {
-    let mut crc: u8 = 0;
-    for i in 0..(LB_FRAME_LEN - 1) {
-        crc = CRC8_TABLE[(crc ^ frame[i]) as usize];
+    /// App closing: stops every runtime (each opens its contactors first),
+    /// then writes all-open to any port still registered.
+    pub fn shutdown(&self, why: &str) {
+        let ids: Vec<String> = self.inner.lock().unwrap().keys().cloned().collect();
+        for id in ids {
+            self.stop(&id);
+        }
+        self.failsafe.open_all(why);
     }
-    crc
-}
-
-fn to_hex(data: &[u8]) -> String {
-    data.iter()
-        .map(|b| format!("{:02X}", b))
-        .collect::<Vec<_>>()
-        .join(" ")
-}
 
-fn u16_from_be(hi: u8, lo: u8) -> u16 {
-    ((hi as u16) << 8) | (lo as u16)
-}
-
-fn u16_to_be(v: u16) -> (u8, u8) {
-    ((v >> 8) as u8, (v & 0xFF) as u8)
-}
+    pub fn failsafe(&self) -> Failsafe {
+        self.failsafe.clone()
+    }
 
-#[derive(Clone, Debug, Default)]
-struct FrameFields {
-    version: u8,
-    bank_power: u16,
-    bank_no: u8,
-    handshake: u8,
-    contactors_mask: u16,
-    err_contactors: u16,
-    err_fans: u16,
-    err_thermals: u16,
-    other_errors: u8,
-}
+    pub fn budget(&self, bank_id: &str) -> Arc<Mutex<DutyCycleBudget>> {
+        self.budgets
+            .lock()
+            .unwrap()
+            .entry(bank_id.to_string())
+            .or_insert_with(|| Arc::new(Mutex::new(DutyCycleBudget::new(RDP4000))))
+            .clone()
+    }
 
-fn build_frame(f: &FrameFields) -> [u8; LB_FRAME_LEN] {
-    let mut out = [0u8; LB_FRAME_LEN];
-    out[0] = f.version;
-    let (p_hi, p_lo) = u16_to_be(f.bank_power);
-    out[1] = p_hi;
-    out[2] = p_lo;
-    out[3] = f.bank_no;
-    out[4] = f.handshake;
-    let (c_hi, c_lo) = u16_to_be(f.contactors_mask);
-    out[5] = c_hi;
-    out[6] = c_lo;
-    let (ec_hi, ec_lo) = u16_to_be(f.err_contactors);
-    out[7] = ec_hi;
-    out[8] = ec_lo;
-    let (ef_hi, ef_lo) = u16_to_be(f.err_fans);
-    out[9] = ef_hi;
-    out[10] = ef_lo;
-    let (et_hi, et_lo) = u16_to_be(f.err_thermals);
-    out[11] = et_hi;
-    out[12] = et_lo;
-    out[13] = f.other_errors;
-    out[14] = crc8_load_bank(&out);
-    out
-}
+    pub fn estop(&self, bank_id: &str) -> Arc<EstopLatch> {
+        self.estops
+            .lock()
+            .unwrap()
+            .entry(bank_id.to_string())
+            .or_default()
+            .clone()
+    }
 
-fn parse_frame(frame: &[u8], port_name: &str) -> Option<LoadBankStatus> {
-    if frame.len() != LB_FRAME_LEN {
-        return None;
+    pub fn budget_u2_v(&self) -> f64 {
+        self.safety.lock().unwrap().u2_max_v
     }
-    if frame[LB_FRAME_LEN - 1] != crc8_load_bank(frame) {
-        return None;
+
+    pub fn send_sequence(
+        &self,
+        bank_id: &str,
+        cmd: SequenceCmd,
+        reply: oneshot::Sender<Result<SequenceProgress, String>>,
+    ) -> Result<(), String> {
+        self.send(bank_id, RuntimeCmd::Sequence { cmd, reply })
     }
-    Some(LoadBankStatus {
-        port_name: port_name.to_string(),
-        version: frame[0],
-        bank_power: u16_from_be(frame[1], frame[2]),
-        bank_no: frame[3],
-        handshake: frame[4],
-        contactors_mask: u16_from_be(frame[5], frame[6]),
-        err_contactors: u16_from_be(frame[7], frame[8]),
-        err_fans: u16_from_be(frame[9], frame[10]),
-        err_thermals: u16_from_be(frame[11], frame[12]),
-        other_errors: frame[13],
-        raw_frame_hex: to_hex(frame),
-    })
-}
 
-fn find_first_valid_frame(buf: &[u8]) -> Option<(usize, [u8; LB_FRAME_LEN])> {
-    if buf.len() < LB_FRAME_LEN {
-        return None;
+    pub fn sequence_progress(&self, bank_id: &str) -> Result<Option<SequenceProgress>, String> {
+        let guard = self.inner.lock().unwrap();
+        let h = guard
+            .get(bank_id)
+            .ok_or_else(|| format!("Load bank runtime '{bank_id}' not running"))?;
+        let progress = h.worker.shared.lock().unwrap().sequence.clone();
+        Ok(progress)
     }
-    let max_i = buf.len() - LB_FRAME_LEN;
-    for i in 0..=max_i {
-        let slice = &buf[i..i + LB_FRAME_LEN];
-        if slice[LB_FRAME_LEN - 1] == crc8_load_bank(slice) {
-            let mut arr = [0u8; LB_FRAME_LEN];
-            arr.copy_from_slice(slice);
-            return Some((i, arr));
+
+    /// Hot-plug notice from the port watcher, forwarded to every worker.
+    pub fn ports_changed(&self, added: &[SerialPortInfo], removed: &[SerialPortInfo]) {
+        let guard = self.inner.lock().unwrap();
+        for h in guard.values() {
+            for p in removed {
+                let _ = h.send(RuntimeCmd::PortRemoved(p.port_name.clone()));
+            }
+            for p in added {
+                let _ = h.send(RuntimeCmd::PortAdded(p.port_name.clone()));
+            }
         }
     }
-    None
 }
 
-fn list_port_names_sorted() -> Vec<String> {
-    let mut out = serialport::available_ports()
-        .map(|v| v.into_iter().map(|p| p.port_name).collect::<Vec<_>>())
-        .unwrap_or_default();
-    out.sort();
-    out
+pub fn bank_key(bank_id: Option<String>) -> String {
+    bank_id
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| DEFAULT_BANK_ID.to_string())
 }
 
 // -----------------------------------------------------------------------------
-// Worker
+// Worker host (events + per-port settings from the app state)
 // -----------------------------------------------------------------------------
 
-struct Worker {
-    app: AppHandle,
-    baud: u32,
-
-    mode: RuntimeMode,
-    active_port: Option<String>,
-    port: Option<Box<dyn serialport::SerialPort>>,
-
-    // RX parsing
-    buf: Vec<u8>,
-    tmp: [u8; 512],
-
-    // health
-    online: bool,
-    last_seen: Instant,
-
-    // scan / retry
-    last_scan: Instant,
-    last_ports: Vec<String>,
-    scan_every: Duration,
-    //offline_after: Duration, // offline_after kept for future keepalive-based offline detection (currently unused)
-
-    // handshake
-    //handshake_req: [u8; LB_FRAME_LEN],
-    //handshake_resp: [u8; LB_FRAME_LEN],
-    handshake_ack_template: [u8; LB_FRAME_LEN], // generic ACK frame (byte4=0x00); we often derive ACK from a hello frame though
-
-    // polling
-    poll_enabled: bool,
-    poll_interval: Duration,
-    last_poll: Instant,
-
-    // last known status (for building commands)
-    last_status_fields: Option<FrameFields>,
-}
-
-impl Worker {
-    fn new(app: AppHandle, baud: u32, mode: RuntimeMode) -> Self {
-        let mut req_fields = FrameFields::default();
-        req_fields.version = 1;
-        //let mut handshake_req = build_frame(&req_fields);
-        let mut handshake_ack_template = build_frame(&req_fields);
-        // Apply handshake byte at the configured index (then recompute CRC)
-        if HANDSHAKE_BYTE_INDEX < LB_FRAME_LEN - 1 {
-            //handshake_req[HANDSHAKE_BYTE_INDEX] = HANDSHAKE_REQ_VALUE;
-            //handshake_req[LB_FRAME_LEN - 1] = crc8_load_bank(&handshake_req);
-            handshake_ack_template[HANDSHAKE_BYTE_INDEX] = HANDSHAKE_ACK_VALUE;
-            handshake_ack_template[LB_FRAME_LEN - 1] = crc8_load_bank(&handshake_ack_template);
-        }
-
-        /*
-        let mut handshake_resp = handshake_req;
-        if HANDSHAKE_BYTE_INDEX < LB_FRAME_LEN - 1 {
-            handshake_resp[HANDSHAKE_BYTE_INDEX] = HANDSHAKE_RESP_VALUE;
-            handshake_resp[LB_FRAME_LEN - 1] = crc8_load_bank(&handshake_resp);
-        }
-        */
+struct AppHost(AppHandle);
 
-        Self {
-            app,
-            baud,
-            mode,
-            active_port: None,
-            port: None,
-            buf: Vec::with_capacity(4096),
-            tmp: [0u8; 512],
-            online: false,
-            last_seen: Instant::now(),
-            last_scan: Instant::now() - Duration::from_millis(DEFAULT_SCAN_EVERY_MS),
-            last_ports: vec![],
-            scan_every: Duration::from_millis(DEFAULT_SCAN_EVERY_MS),
-            //offline_after: Duration::from_millis(DEFAULT_OFFLINE_AFTER_MS),
-            //handshake_req,
-            //handshake_resp,
-            handshake_ack_template,
-            poll_enabled: false, //true,
-            poll_interval: Duration::from_millis(400),
-            last_poll: Instant::now(),
-            last_status_fields: None,
-        }
+impl WorkerHost for AppHost {
+    fn emit(&self, event: WorkerEvent) {
+        let _ = self.0.emit(event.name(), event);
     }
 
-    fn emit_health(&self, online: bool, reason: Option<String>) {
-        let port_name = self.active_port.clone().unwrap_or_default();
-        let _ = self.app.emit(
-            "lb/health",
-            LoadBankHealth {
-                port_name,
-                online,
-                last_seen_ms: self.last_seen.elapsed().as_millis(),
-                reason,
-            },
-        );
+    fn sim_device(&self) -> Arc<Mutex<SimDevice>> {
+        self.0.state::<LoadBankSimState>().device.clone()
     }
 
-    fn emit_ports_if_changed(&mut self) {
-        let ports = list_port_names_sorted();
-        if ports != self.last_ports {
-            self.last_ports = ports.clone();
-            eprintln!("[LB] ports: {:?}", ports);
-            let _ = self.app.emit("lb/ports", PortsEvent { ports });
-        }
+    fn saved_serial(&self, port_name: &str) -> Option<SerialConfig> {
+        self.0
+            .state::<LoadBankSerialState>()
+            .saved(&self.0, port_name)
     }
 
-    fn set_mode(&mut self, mode: RuntimeMode) {
-        if self.mode == mode {
-            return;
-        }
-        eprintln!("[LB] mode change: {} -> {}", self.mode.key(), mode.key());
-        self.mode = mode;
-        self.drop_port(Some("mode changed".into()));
+    fn rank_ports(
+        &self,
+        ports: &[SerialPortInfo],
+        bank_id: &str,
+        bank_no: Option<u8>,
+        blind: bool,
+    ) -> Vec<String> {
+        self.0
+            .state::<LoadBankDiscoveryState>()
+            .rank(&self.0, ports, bank_id, bank_no, blind)
     }
 
-    fn set_polling(&mut self, enabled: bool, interval_ms: u64) {
-        self.poll_enabled = enabled;
-        self.poll_interval = Duration::from_millis(interval_ms.max(50));
-        self.last_poll = Instant::now();
+    fn remember_port(&self, port: &SerialPortInfo, bank_id: &str, bank_no: u8) {
+        self.0
+            .state::<LoadBankDiscoveryState>()
+            .remember(&self.0, port, bank_id, bank_no);
     }
+}
 
-    fn drop_port(&mut self, reason: Option<String>) {
-        if self.online {
-            self.emit_health(false, reason.or(Some("disconnected".into())));
-        }
-        self.port = None;
-        self.active_port = None;
-        self.online = false;
-        self.buf.clear();
-        self.last_status_fields = None;
-    }
+// -----------------------------------------------------------------------------
+// Payloads (query)
+// -----------------------------------------------------------------------------
 
-    fn send_tx(&mut self, bytes: &[u8]) {
-        let Some(p) = self.port.as_mut() else { return };
-        let port_name = self.active_port.clone().unwrap_or_default();
-
-        let _ = p.write_all(bytes);
-        let _ = p.flush();
-
-        let _ = self.app.emit(
-            "lb/tx",
-            SerialTxChunk {
-                port_name: port_name.clone(),
-                bytes: bytes.to_vec(),
-                hex: to_hex(bytes),
-            },
-        );
-        eprintln!("[LB/TX] {} {}", port_name, to_hex(bytes));
-    }
+/// `lb_get_state`: what the worker knows right now (no event history needed).
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct RuntimeSnapshot {
+    pub bank_id: String,
+    pub mode: String,
+    pub baud: u32,
+    pub port_name: Option<String>,
+    pub online: bool,
+    pub link: LinkState,
+    pub protocol_version: Option<u8>,
+    /// None until the first frame (any valid frame, HELLO included).
+    pub ms_since_frame: Option<u64>,
+    pub ms_since_status: Option<u64>,
+    pub status: Option<LoadBankStatus>,
+    pub poll: PollConfig,
+    pub frames_ok: u64,
+    pub crc_errors: u64,
+    pub resyncs: u64,
+    pub last_handshake: Option<HandshakeOutcome>,
+    pub cmd_latency: CmdLatency,
+    /// Last `lb/link-stats` sample.
+    pub link_stats: Option<LinkStatsEvent>,
+    /// Set while the emergency stop is latched.
+    pub estop: Option<EstopEvent>,
+}
 
-    fn emit_rx(&self, port_name: &str, bytes: &[u8]) {
-        let _ = self.app.emit(
-            "lb/rx",
-            SerialRxChunk {
-                port_name: port_name.to_string(),
-                bytes: bytes.to_vec(),
-                hex: to_hex(bytes),
-            },
-        );
-        eprintln!("[LB/RX] {} {}", port_name, to_hex(bytes));
-    }
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PortsEvent {
+    pub ports: Vec<String>,
+}
 
-    fn open_port(&self, port_name: &str) -> Result<Box<dyn serialport::SerialPort>, String> {
-        serialport::new(port_name, self.baud)
-            .timeout(Duration::from_millis(DEFAULT_READ_TIMEOUT_MS))
-            .open()
-            .map_err(|e| e.to_string())
-    }
+// -----------------------------------------------------------------------------
+// Commands
+// -----------------------------------------------------------------------------
 
-    /*
-    fn handshake_on_opened_port(
-        &mut self,
-        port_name: &str,
-        p: &mut Box<dyn serialport::SerialPort>,
-    ) -> bool {
-        // send handshake request
-        let req = self.handshake_req;
-        let resp = self.handshake_resp;
-
-        let _ = p.write_all(&req);
-        let _ = p.flush();
-
-        // read window
-        let deadline = Instant::now() + Duration::from_millis(250);
-        let mut probe_buf: Vec<u8> = Vec::with_capacity(1024);
-        let mut tmp = [0u8; 256];
-
-        while Instant::now() < deadline {
-            match p.read(&mut tmp) {
-                Ok(n) if n > 0 => {
-                    let chunk = &tmp[..n];
-                    probe_buf.extend_from_slice(chunk);
-                    self.emit_rx(port_name, chunk);
-
-                    // scan for any valid frames; accept when we see the exact handshake response
-                    loop {
-                        let Some((off, frame)) = find_first_valid_frame(&probe_buf) else {
-                            break;
-                        };
-                        let end = off + LB_FRAME_LEN;
-                        if end > probe_buf.len() {
-                            break;
-                        }
-
-                        // consume up to end so we can keep scanning in the same window
-                        probe_buf.drain(0..end);
-
-                        if frame == resp {
-                            return true;
-                        }
-                    }
-
-                    if probe_buf.len() > 4096 {
-                        let keep = 1024usize;
-                        let drop_n = probe_buf.len().saturating_sub(keep);
-                        if drop_n > 0 {
-                            probe_buf.drain(0..drop_n);
-                        }
-                    }
-                }
-                Ok(_) => {}
-                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
-                Err(_) => break,
-            }
+/// Start the backend supervisor for one bank (`bank_id`, default "default").
+/// - `port_name` empty => AUTO scan + adopt (ports held by other banks are skipped).
+/// - `port_name` "sim" / "sim://..." => SIMULATED (software load bank).
+/// - `port_name` "tcp://host:port" => TCP (serial-to-Ethernet converter).
+/// - `port_name` "replay://<capture>?speed=<x>" => REPLAY (recorded capture played back).
+/// - `port_name` non-empty => FIXED.
+///
+/// `serial` (data bits, parity, ...) is saved for a FIXED serial port; in the
+/// other modes it applies to ports without a saved config.
+#[tauri::command]
+pub fn lb_start_polling(
+    app: AppHandle,
+    state: State<LoadBankRuntimeState>,
+    serial_state: State<LoadBankSerialState>,
+    port_name: String,
+    baud: u32,
+    bank_id: Option<String>,
+    serial: Option<SerialConfig>,
+) -> Result<(), String> {
+    let bank_id = bank_key(bank_id);
+    let requested_mode = RuntimeMode::from_port_name(&port_name);
+    if let Some(cfg) = &serial {
+        cfg.validate()?;
+        if let RuntimeMode::Fixed { port_name } = &requested_mode {
+            serial_state.save(&app, port_name, cfg.clone())?;
         }
-
-        false
     }
-    */
-    // device-first handshake:
-    // 1) listen briefly for HELLO (byte4=0xFF) OR CONFIRM (byte4=0x00)
-    // 2) send ACK (byte4=0x00)
-    // 3) wait for CONFIRM (byte4=0x00)
-    /*
-    fn handshake_on_opened_port(
-        &mut self,
-        port_name: &str,
-        p: &mut Box<dyn serialport::SerialPort>,
-    ) -> Option<LoadBankStatus> {
-        let hello_window = Duration::from_millis(250);
-        let confirm_window = Duration::from_millis(250);
-
-        let mut probe_buf: Vec<u8> = Vec::with_capacity(1024);
-        let mut tmp = [0u8; 256];
-
-        // --- Step 1: listen for HELLO/CONFIRM briefly (read-only) ---
-        let t0 = Instant::now();
-        let mut hello_frame: Option<[u8; LB_FRAME_LEN]> = None;
-        let mut confirm_frame: Option<[u8; LB_FRAME_LEN]> = None;
-
-        while t0.elapsed() < hello_window {
-            match p.read(&mut tmp) {
-                Ok(n) if n > 0 => {
-                    let chunk = &tmp[..n];
-                    probe_buf.extend_from_slice(chunk);
-                    self.emit_rx(port_name, chunk);
-
-                    while let Some((off, frame)) = find_first_valid_frame(&probe_buf) {
-                        let end = off + LB_FRAME_LEN;
-                        if end > probe_buf.len() {
-                            break;
-                        }
-                        probe_buf.drain(0..end);
-
-                        // Inspect handshake byte
-                        match frame[HANDSHAKE_BYTE_INDEX] {
-                            HANDSHAKE_HELLO_VALUE => {
-                                hello_frame = Some(frame);
-                                // We can stop early; we have hello.
-                                break;
-                            }
-                            HANDSHAKE_CONFIRM_VALUE => {
-                                // Device already paired (or already confirming). This is sufficient evidence too.
-                                confirm_frame = Some(frame);
-                                break;
-                            }
-                            _ => {
-                                // ignore other frames during handshake
-                            }
-                        }
-                    }
-
-                    if hello_frame.is_some() || confirm_frame.is_some() {
-                        break;
-                    }
-
-                    if probe_buf.len() > 4096 {
-                        let keep = 1024usize;
-                        let drop_n = probe_buf.len().saturating_sub(keep);
-                        if drop_n > 0 {
-                            probe_buf.drain(0..drop_n);
-                        }
-                    }
-                }
-                Ok(_) => {}
-                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
-                Err(_) => break,
-            }
-        }
-
-        // --- Step 2: send ACK (0x00). Prefer deriving ACK from hello/confirm frame. ---
-        let ack = if let Some(f) = hello_frame {
-            Self::ack_from_frame(f)
-        } else if let Some(f) = confirm_frame {
-            Self::ack_from_frame(f)
-        } else {
-            // fallback: generic ack template
-            self.handshake_ack_template
-        };
 
-        let _ = p.write_all(&ack);
-        let _ = p.flush();
-        eprintln!("[LB] handshake ACK -> {} {}", port_name, to_hex(&ack));
-
-        // --- Step 3: wait for CONFIRM (0x00) ---
-        let t1 = Instant::now();
-        while t1.elapsed() < confirm_window {
-            match p.read(&mut tmp) {
-                Ok(n) if n > 0 => {
-                    let chunk = &tmp[..n];
-                    probe_buf.extend_from_slice(chunk);
-                    self.emit_rx(port_name, chunk);
-
-                    while let Some((off, frame)) = find_first_valid_frame(&probe_buf) {
-                        let end = off + LB_FRAME_LEN;
-                        if end > probe_buf.len() {
-                            break;
-                        }
-                        probe_buf.drain(0..end);
-
-                        if frame[HANDSHAKE_BYTE_INDEX] == HANDSHAKE_CONFIRM_VALUE {
-                            // Great: handshake confirmed.
-                            // Parse this frame as a status snapshot to seed UI.
-                            if let Some(status) = parse_frame(&frame, port_name) {
-                                return Some(status);
-                            } else {
-                                // CRC valid already; parse_frame should not fail, but keep safe
-                                return Some(LoadBankStatus {
-                                    port_name: port_name.to_string(),
-                                    version: frame[0],
-                                    bank_power: u16_from_be(frame[1], frame[2]),
-                                    bank_no: frame[3],
-                                    handshake: frame[4],
-                                    contactors_mask: u16_from_be(frame[5], frame[6]),
-                                    err_contactors: u16_from_be(frame[7], frame[8]),
-                                    err_fans: u16_from_be(frame[9], frame[10]),
-                                    err_thermals: u16_from_be(frame[11], frame[12]),
-                                    other_errors: frame[13],
-                                    raw_frame_hex: to_hex(&frame),
-                                });
-                            }
-                        }
-                    }
-                }
-                Ok(_) => {}
-                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
-                Err(_) => break,
-            }
-        }
-
-        None
-    }*/
-    fn handshake_on_opened_port(
-        &mut self,
-        port_name: &str,
-        p: &mut Box<dyn serialport::SerialPort>,
-    ) -> Option<LoadBankStatus> {
-        let hello_window = Duration::from_millis(300);
-        let confirm_window = Duration::from_millis(300);
-
-        let mut probe_buf: Vec<u8> = Vec::with_capacity(1024);
-        let mut tmp = [0u8; 256];
-
-        // 1) WAIT FOR HELLO (0xFF at byte 4). READ-ONLY.
-        let t0 = Instant::now();
-        let mut hello_frame: Option<[u8; LB_FRAME_LEN]> = None;
-
-        while t0.elapsed() < hello_window {
-            match p.read(&mut tmp) {
-                Ok(n) if n > 0 => {
-                    let chunk = &tmp[..n];
-                    probe_buf.extend_from_slice(chunk);
-                    self.emit_rx(port_name, chunk);
-
-                    while let Some((off, frame)) = find_first_valid_frame(&probe_buf) {
-                        let end = off + LB_FRAME_LEN;
-                        if end > probe_buf.len() {
-                            break;
-                        }
-                        probe_buf.drain(0..end);
-
-                        if frame[HANDSHAKE_BYTE_INDEX] == HANDSHAKE_HELLO_VALUE {
-                            hello_frame = Some(frame);
-                            break;
-                        }
-                    }
-
-                    if hello_frame.is_some() {
-                        break;
-                    }
-
-                    // cap growth during probe
-                    if probe_buf.len() > 4096 {
-                        let keep = 1024usize;
-                        let drop_n = probe_buf.len().saturating_sub(keep);
-                        if drop_n > 0 {
-                            probe_buf.drain(0..drop_n);
-                        }
-                    }
-                }
-                Ok(_) => {}
-                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
-                Err(_) => break,
+    {
+        let guard = state.inner.lock().unwrap();
+        // If running with same baud (and serial settings), just switch mode. | idempotent
+        if let Some(h) = guard.get(&bank_id) {
+            let same_serial = serial.is_none() || h.serial == serial;
+            if h.baud == baud && h.mode == requested_mode && same_serial {
+                let _ = h.send(RuntimeCmd::SetMode(requested_mode.clone()));
+                return Ok(());
             }
         }
-
-        // STRICT: if we didn't see HELLO, do NOT send anything.
-        let hello = hello_frame?;
-
-        // 2) SEND ACK derived from HELLO (0x00 at byte 4).
-        let ack = Self::ack_from_frame(hello);
-        let _ = p.write_all(&ack);
-        let _ = p.flush();
-        eprintln!("[LB] handshake ACK -> {} {}", port_name, to_hex(&ack));
-
-        // Clear buffer so leftover HELLO spam doesn't drown confirm scanning.
-        probe_buf.clear();
-
-        // 3) WAIT FOR CONFIRM (0x00 at byte 4).
-        let t1 = Instant::now();
-        while t1.elapsed() < confirm_window {
-            match p.read(&mut tmp) {
-                Ok(n) if n > 0 => {
-                    let chunk = &tmp[..n];
-                    probe_buf.extend_from_slice(chunk);
-                    self.emit_rx(port_name, chunk);
-
-                    while let Some((off, frame)) = find_first_valid_frame(&probe_buf) {
-                        let end = off + LB_FRAME_LEN;
-                        if end > probe_buf.len() {
-                            break;
-                        }
-                        probe_buf.drain(0..end);
-
-                        if frame[HANDSHAKE_BYTE_INDEX] == HANDSHAKE_CONFIRM_VALUE {
-                            // Seed UI with this frame as status
-                            return parse_frame(&frame, port_name).or_else(|| {
-                                Some(LoadBankStatus {
-                                    port_name: port_name.to_string(),
-                                    version: frame[0],
-                                    bank_power: u16_from_be(frame[1], frame[2]),
-                                    bank_no: frame[3],
-                                    handshake: frame[4],
-                                    contactors_mask: u16_from_be(frame[5], frame[6]),
-                                    err_contactors: u16_from_be(frame[7], frame[8]),
-                                    err_fans: u16_from_be(frame[9], frame[10]),
-                                    err_thermals: u16_from_be(frame[11], frame[12]),
-                                    other_errors: frame[13],
-                                    raw_frame_hex: to_hex(&frame),
-                                })
-                            });
-                        }
-                    }
-                }
-                Ok(_) => {}
-                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
-                Err(_) => break,
+        // A fixed endpoint can only be driven by one bank.
+        if requested_mode != RuntimeMode::Auto {
+            if let Some((other, _)) = guard
+                .iter()
+                .find(|(id, h)| **id != bank_id && h.mode == requested_mode)
+            {
+                return Err(format!(
+                    "{} already used by bank '{other}'",
+                    requested_mode.key()
+                ));
             }
         }
-
-        None
     }
 
-    fn ensure_connected(&mut self) {
-        if self.port.is_some() {
-            return;
-        }
+    // Different baud / not running => stop old and start new
+    state.stop(&bank_id);
+
+    let cfg = WorkerConfig {
+        serial: serial.clone(),
+        keepalive: *state.keepalive.lock().unwrap(),
+        safety: state.safety.lock().unwrap().clone(),
+        budget: state.budget(&bank_id),
+        estop: state.estop(&bank_id),
+        claimed_ports: state.claimed_ports.clone(),
+        failsafe: state.failsafe(),
+        capture: app.state::<LoadBankCaptureState>().sink(&app),
+        events: app.state::<LoadBankEventLogState>().sink(&app),
+        ..WorkerConfig::new(&bank_id, baud, requested_mode.clone())
+    };
+    let (worker, task) = worker_task(Arc::new(AppHost(app.clone())), cfg);
+    tauri::async_runtime::spawn(task);
 
-        // throttle scanning
-        if self.last_scan.elapsed() < self.scan_every {
-            return;
-        }
-        self.last_scan = Instant::now();
-
-        self.emit_ports_if_changed();
-
-        //match &self.mode {
-        let mode = self.mode.clone();
-        match mode {
-            //RuntimeMode::Fixed { port_name } => match self.open_port(port_name) {
-            RuntimeMode::Fixed { port_name } => match self.open_port(&port_name) {
-                Ok(mut p) => {
-                    eprintln!("[LB] opened fixed {} @ {}", port_name, self.baud);
-                    /*if self.handshake_on_opened_port(&port_name, &mut p) {
-                        eprintln!("[LB] handshake OK on {}", port_name);
-                        self.active_port = Some(port_name.clone());
-                        self.port = Some(p);
-                        self.online = true;
-                        self.last_seen = Instant::now();
-                        self.emit_health(true, Some("handshake ok".into()));
-                    } else {
-                        eprintln!("[LB] handshake FAILED on {}", port_name);
-                        self.emit_health(false, Some("handshake failed".into()));
-                    }*/
-                    if let Some(status) = self.handshake_on_opened_port(&port_name, &mut p) {
-                        eprintln!("[LB] handshake OK on {}", port_name);
-                        self.active_port = Some(port_name.clone());
-                        self.port = Some(p);
-                        self.online = true;
-                        self.last_seen = Instant::now();
-                        self.emit_health(true, Some("handshake ok".into()));
-
-                        // Seed status immediately (important if device goes silent after handshake)
-                        self.last_status_fields = Some(FrameFields {
-                            version: status.version,
-                            bank_power: status.bank_power,
-                            bank_no: status.bank_no,
-                            handshake: status.handshake,
-                            contactors_mask: status.contactors_mask,
-                            err_contactors: status.err_contactors,
-                            err_fans: status.err_fans,
-                            err_thermals: status.err_thermals,
-                            other_errors: status.other_errors,
-                        });
-
-                        let _ = self.app.emit("lb/status", status);
-                    } else {
-                        eprintln!("[LB] handshake FAILED on {}", port_name);
-                        self.emit_health(false, Some("handshake failed".into()));
-                    }
-                }
-                Err(e) => {
-                    self.emit_health(false, Some(format!("open failed: {e}")));
-                }
-            },
-            RuntimeMode::Auto => {
-                let ports = self.last_ports.clone();
-                for cand in ports {
-                    let Ok(mut p) = self.open_port(&cand) else {
-                        continue;
-                    };
-                    if let Some(status) = self.handshake_on_opened_port(&cand, &mut p) {
-                        //if self.handshake_on_opened_port(&cand, &mut p) {
-                        eprintln!("[LB] adopted {}", cand);
-                        self.active_port = Some(cand);
-                        self.port = Some(p);
-                        self.online = true;
-                        self.last_seen = Instant::now();
-                        self.emit_health(true, Some("handshake ok".into()));
-
-                        // Seed status immediately (important if device goes silent after handshake)
-                        self.last_status_fields = Some(FrameFields {
-                            version: status.version,
-                            bank_power: status.bank_power,
-                            bank_no: status.bank_no,
-                            handshake: status.handshake,
-                            contactors_mask: status.contactors_mask,
-                            err_contactors: status.err_contactors,
-                            err_fans: status.err_fans,
-                            err_thermals: status.err_thermals,
-                            other_errors: status.other_errors,
-                        });
-
-                        let _ = self.app.emit("lb/status", status);
-                        break;
-                    }
-                }
-            }
-        }
-    }
+    state.inner.lock().unwrap().insert(
+        bank_id,
+        RuntimeHandle {
+            baud,
+            mode: requested_mode,
+            serial,
+            worker,
+        },
+    );
 
-    fn poll_if_due(&mut self) {
-        if !self.poll_enabled {
-            return;
-        }
-        if self.port.is_none() {
-            return;
-        }
-        if self.last_poll.elapsed() < self.poll_interval {
-            return;
-        }
-        // Keepalive = handshake request // NEW Keepalive = send ACK (0x00) as a ping
-        //self.send_tx(&self.handshake_req);
-        //let req = self.handshake_ack_template; //handshake_req;
-        //self.send_tx(&req);
-
-        let ack = self.handshake_ack_template;
-        self.send_tx(&ack);
-        self.last_poll = Instant::now();
-    }
+    Ok(())
+}
 
-    fn read_once(&mut self) {
-        let Some(p) = self.port.as_mut() else { return };
-        let Some(port_name) = self.active_port.clone() else {
-            return;
-        };
+/// Snapshot of one bank's runtime (default bank when `bank_id` is omitted),
+/// e.g. to redraw after a webview reload without waiting for events.
+#[tauri::command]
+pub fn lb_get_state(
+    state: State<LoadBankRuntimeState>,
+    bank_id: Option<String>,
+) -> Result<RuntimeSnapshot, String> {
+    let bank_id = bank_key(bank_id);
+    let guard = state.inner.lock().unwrap();
+    let h = guard
+        .get(&bank_id)
+        .ok_or_else(|| format!("Load bank runtime '{bank_id}' not running"))?;
+    let shared = h.worker.shared.lock().unwrap();
+    let ms_since = |t: Option<Instant>| t.map(|t| t.elapsed().as_millis() as u64);
+    Ok(RuntimeSnapshot {
+        bank_id: bank_id.clone(),
+        mode: h.mode.key(),
+        baud: h.baud,
+        port_name: shared.port_name.clone(),
+        online: shared.online,
+        link: shared.link,
+        protocol_version: shared.protocol_version,
+        ms_since_frame: ms_since(shared.last_frame),
+        ms_since_status: ms_since(shared.last_status),
+        status: shared.status.clone(),
+        poll: shared.poll,
+        frames_ok: shared.decoder.frames,
+        crc_errors: shared.decoder.crc_errors,
+        resyncs: shared.decoder.resyncs,
+        last_handshake: shared.last_handshake.clone(),
+        cmd_latency: shared.cmd_latency,
+        link_stats: shared.link_stats.clone(),
+        estop: shared.estop.clone(),
+    })
+}
 
-        match p.read(&mut self.tmp) {
-            Ok(n) if n > 0 => {
-                let chunk = &self.tmp[..n];
-                self.buf.extend_from_slice(chunk);
-                self.emit_rx(&port_name, chunk);
-            }
-            Ok(_) => {}
-            Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {}
-            Err(e) => {
-                eprintln!("[LB] read error on {}: {}", port_name, e);
-                self.drop_port(Some(format!("read error: {e}")));
-            }
-        }
+/// Stop one bank, or every bank when `bank_id` is omitted.
+#[tauri::command]
+pub fn lb_stop_polling(
+    state: State<LoadBankRuntimeState>,
+    bank_id: Option<String>,
+) -> Result<(), String> {
+    let ids: Vec<String> = match bank_id {
+        Some(id) => vec![bank_key(Some(id))],
+        None => state.inner.lock().unwrap().keys().cloned().collect(),
+    };
+    for id in ids {
+        state.stop(&id);
     }
+    Ok(())
+}
 
-    fn parse_frames(&mut self) {
-        let Some(port_name) = self.active_port.clone() else {
-            return;
-        };
-
-        while let Some((offset, frame)) = find_first_valid_frame(&self.buf) {
-            let end = offset + LB_FRAME_LEN;
-            if end > self.buf.len() {
-                break;
-            }
-
-            // consume bytes up to end of frame
-            self.buf.drain(0..end);
-
-            // ignore handshake response frames
-            /*if frame == self.handshake_ack_template {
-                //handshake_resp {
-                self.last_seen = Instant::now();
-                continue;
-            } */
-            // If device starts sending HELLO again while connected, it likely reset.
-            // Re-ACK it and keep the port.
-            if frame[HANDSHAKE_BYTE_INDEX] == HANDSHAKE_HELLO_VALUE {
-                eprintln!(
-                    "[LB] hello detected while connected on {} -> re-ack",
-                    port_name
-                );
-
-                // derive ack from hello frame and send
-                let ack = Self::ack_from_frame(frame);
-                self.send_tx(&ack);
-
-                self.last_seen = Instant::now();
-                continue;
-            }
+#[tauri::command]
+pub fn lb_set_polling(
+    state: State<LoadBankRuntimeState>,
+    enabled: bool,
+    interval_ms: u64,
+    bank_id: Option<String>,
+) -> Result<(), String> {
+    state.send(
+        &bank_key(bank_id),
+        RuntimeCmd::SetPolling {
+            enabled,
+            interval_ms,
+        },
+    )
+}
 
-            let Some(status) = parse_frame(&frame, &port_name) else {
-                continue;
-            };
+/// Raw send (DevEchoPcbTest).
+#[tauri::command]
+pub fn lb_write_bytes(
+    state: State<LoadBankRuntimeState>,
+    data: Vec<u8>,
+    bank_id: Option<String>,
+) -> Result<(), String> {
+    state.send(&bank_key(bank_id), RuntimeCmd::WriteRaw(data))
+}
 
-            self.last_seen = Instant::now();
-            if !self.online {
-                self.online = true;
-                self.emit_health(true, None);
-            }
+/// One AUTO pass over every port (known adapters first), for stations whose
+/// adapter isn't known yet. The adopted adapter is remembered.
+#[tauri::command]
+pub fn lb_scan_all_ports(
+    state: State<LoadBankRuntimeState>,
+    bank_id: Option<String>,
+) -> Result<(), String> {
+    state.send(&bank_key(bank_id), RuntimeCmd::ScanAllPorts)
+}
 
-            self.last_status_fields = Some(FrameFields {
-                version: status.version,
-                bank_power: status.bank_power,
-                bank_no: status.bank_no,
-                handshake: status.handshake,
-                contactors_mask: status.contactors_mask,
-                err_contactors: status.err_contactors,
-                err_fans: status.err_fans,
-                err_thermals: status.err_thermals,
-                other_errors: status.other_errors,
-            });
-
-            let _ = self.app.emit("lb/status", status);
-        }
+/// Production command: backend builds the proper frame.
+#[tauri::command]
+pub fn lb_set_contactors(
+    state: State<LoadBankRuntimeState>,
+    mask: u16,
+    bank_id: Option<String>,
+) -> Result<(), String> {
+    state.send(&bank_key(bank_id), RuntimeCmd::SetContactors(mask))
+}
 
-        // cap buffer growth
-        if self.buf.len() > 8192 {
-            let keep = 2048usize;
-            let drop_n = self.buf.len().saturating_sub(keep);
-            if drop_n > 0 {
-                self.buf.drain(0..drop_n);
-            }
-        }
+/// Production command with confirmation: resolves with the status frame that
+/// reports exactly `mask`, retrying the write on timeout or mismatch.
+#[tauri::command]
+pub async fn lb_set_contactors_confirmed(
+    state: State<'_, LoadBankRuntimeState>,
+    mask: u16,
+    timeout_ms: Option<u64>,
+    retries: Option<u32>,
+    bank_id: Option<String>,
+) -> Result<LoadBankStatus, ContactorCmdError> {
+    let (reply, wait) = oneshot::channel();
+    {
+        let guard = state.inner.lock().unwrap();
+        let h = guard
+            .get(&bank_key(bank_id))
+            .ok_or(ContactorCmdError::NotRunning)?;
+        h.send(RuntimeCmd::SetContactorsConfirmed {
+            mask,
+            timeout: Duration::from_millis(timeout_ms.unwrap_or(DEFAULT_CONFIRM_TIMEOUT_MS)),
+            retries: retries.unwrap_or(DEFAULT_CONFIRM_RETRIES),
+            reply,
+        })
+        .map_err(|_| ContactorCmdError::NotRunning)?;
     }
+    // Sender dropped => worker stopped before answering.
+    wait.await.map_err(|_| ContactorCmdError::NotRunning)?
+}
 
-    /*
-    fn is_valid_frame(frame: &[u8]) -> bool {
-        frame.len() == LB_FRAME_LEN && frame[LB_FRAME_LEN - 1] == crc8_load_bank(frame)
+/// Liveness timeouts. Applies to every running bank and to future restarts.
+/// Silence longer than `offline_after_ms` drops the port and reconnects.
+/// `feed_watchdog` (off when omitted) is for the proposed firmware watchdog.
+#[tauri::command]
+pub fn lb_set_keepalive(
+    state: State<LoadBankRuntimeState>,
+    enabled: bool,
+    probe_after_ms: u64,
+    degraded_after_ms: u64,
+    offline_after_ms: u64,
+    feed_watchdog: Option<bool>,
+) -> Result<(), String> {
+    if probe_after_ms < MIN_KEEPALIVE_MS {
+        return Err(format!("probe_after_ms must be >= {MIN_KEEPALIVE_MS}"));
     }
-    */
-
-    fn ack_from_frame(mut frame: [u8; LB_FRAME_LEN]) -> [u8; LB_FRAME_LEN] {
-        // Convert any valid frame into an ACK by forcing byte4=0x00 and recomputing CRC.
-        if HANDSHAKE_BYTE_INDEX < LB_FRAME_LEN - 1 {
-            frame[HANDSHAKE_BYTE_INDEX] = HANDSHAKE_ACK_VALUE;
-            frame[LB_FRAME_LEN - 1] = crc8_load_bank(&frame);
-        }
-        frame
+    if !(probe_after_ms <= degraded_after_ms && degraded_after_ms < offline_after_ms) {
+        return Err("expected probe_after_ms <= degraded_after_ms < offline_after_ms".into());
     }
 
-    /*
-    fn handshake_byte(frame: &[u8]) -> Option<u8> {
-        if frame.len() != LB_FRAME_LEN {
-            return None;
-        }
-        Some(frame[HANDSHAKE_BYTE_INDEX])
+    let cfg = KeepaliveConfig {
+        enabled,
+        probe_after: Duration::from_millis(probe_after_ms),
+        degraded_after: Duration::from_millis(degraded_after_ms),
+        offline_after: Duration::from_millis(offline_after_ms),
+        feed_watchdog: feed_watchdog.unwrap_or(false),
+    };
+    *state.keepalive.lock().unwrap() = cfg;
+
+    for h in state.inner.lock().unwrap().values() {
+        h.send(RuntimeCmd::SetKeepalive(cfg))?;
     }
-    */
+    Ok(())
+}
 
-    fn offline_check(&mut self) {
-        /*
-        if self.port.is_none() {
-            return;
-        }
-        if self.last_seen.elapsed() > self.offline_after {
-            eprintln!("[LB] offline: no frames on {:?}", self.active_port);
-            self.drop_port(Some("no frames".into()));
-        }
-        */
-        // Device may be silent after handshake. "No frames" is NOT a disconnect signal.
-        // We only drop the port on read/write errors (handled elsewhere),
-        // or on explicit keepalive failure if you enable it.
-
-        // ---------------- KEEPALIVE (optional) ----------------
-        // If you later implement keepalive, do NOT use last_seen alone.
-        // Track keepalive misses instead:
-        //
-        // if self.keepalive_enabled && self.awaiting_keepalive && Instant::now() > self.keepalive_deadline {
-        //     self.keepalive_misses += 1;
-        //     self.awaiting_keepalive = false;
-        //     if self.keepalive_misses >= self.keepalive_miss_limit {
-        //         self.drop_port(Some("keepalive failed".into()));
-        //     }
-        // }
+/// Safety interlocks for every bank (set `maxPowerW` whenever the DUT changes).
+#[tauri::command]
+pub fn lb_set_safety(
+    state: State<LoadBankRuntimeState>,
+    config: SafetyConfig,
+) -> Result<(), String> {
+    if !config.u2_max_v.is_finite() || config.u2_max_v <= 0.0 {
+        return Err("u2_max_v must be > 0".into());
+    }
+    if config
+        .max_power_w
+        .is_some_and(|w| !w.is_finite() || w <= 0.0)
+    {
+        return Err("max_power_w must be > 0".into());
     }
+    eprintln!("[LB/SAFETY] config: {:?}", config);
+    *state.safety.lock().unwrap() = config.clone();
 
-    fn cmd_set_contactors(&mut self, mask: u16) {
-        // build from last known status if available, else from defaults
-        let mut f = self.last_status_fields.clone().unwrap_or_else(|| {
-            let mut d = FrameFields::default();
-            d.version = 1;
-            d
-        });
-
-        // Ensure command frames are in "paired" mode
-        f.handshake = HANDSHAKE_ACK_VALUE; // 0x00
-
-        f.contactors_mask = mask;
-        f.err_contactors = 0;
-        f.err_fans = 0;
-        f.err_thermals = 0;
-        f.other_errors = 0;
-
-        let frame = build_frame(&f);
-        self.send_tx(&frame);
+    for h in state.inner.lock().unwrap().values() {
+        h.send(RuntimeCmd::SetSafety(config.clone()))?;
     }
+    Ok(())
 }
 
-// -----------------------------------------------------------------------------
-// Commands
-// -----------------------------------------------------------------------------
+#[tauri::command]
+pub fn lb_get_safety(state: State<LoadBankRuntimeState>) -> SafetyConfig {
+    state.safety.lock().unwrap().clone()
+}
 
+/// Opens every contactor of one bank (every running bank when `bank_id` is
+/// omitted) ahead of anything queued, resending until a status frame confirms
+/// it. Contactor commands are refused until `lb_reset_estop`.
 #[tauri::command]
-pub fn list_ports_detailed() -> Vec<SerialPortInfo> {
-    let ports = match serialport::available_ports() {
-        Ok(p) => p,
-        Err(_) => return vec![],
+pub fn lb_emergency_stop(
+    state: State<LoadBankRuntimeState>,
+    bank_id: Option<String>,
+    reason: Option<String>,
+) -> Result<(), String> {
+    let reason = reason.unwrap_or_else(|| "emergency stop".into());
+    let mut ids: Vec<String> = match bank_id {
+        Some(id) => vec![bank_key(Some(id))],
+        None => state.inner.lock().unwrap().keys().cloned().collect(),
     };
-
-    ports
-        .into_iter()
-        .map(|p| match p.port_type {
-            serialport::SerialPortType::UsbPort(info) => SerialPortInfo {
-                port_name: p.port_name,
-                port_type: "usb".into(),
-                vid: Some(info.vid),
-                pid: Some(info.pid),
-                serial_number: info.serial_number,
-                manufacturer: info.manufacturer,
-                product: info.product,
-            },
-            serialport::SerialPortType::BluetoothPort => SerialPortInfo {
-                port_name: p.port_name,
-                port_type: "bluetooth".into(),
-                vid: None,
-                pid: None,
-                serial_number: None,
-                manufacturer: None,
-                product: None,
-            },
-            serialport::SerialPortType::PciPort => SerialPortInfo {
-                port_name: p.port_name,
-                port_type: "pci".into(),
-                vid: None,
-                pid: None,
-                serial_number: None,
-                manufacturer: None,
-                product: None,
-            },
-            serialport::SerialPortType::Unknown => SerialPortInfo {
-                port_name: p.port_name,
-                port_type: "unknown".into(),
-                vid: None,
-                pid: None,
-                serial_number: None,
-                manufacturer: None,
-                product: None,
-            },
-        })
-        .collect()
+    if ids.is_empty() {
+        // nothing running: the next start comes up latched
+        ids.push(DEFAULT_BANK_ID.to_string());
+    }
+    for id in ids {
+        eprintln!("[LB/ESTOP] bank {id}: {reason}");
+        state.estop(&id).trip(&reason);
+    }
+    Ok(())
 }
 
-/// Start the backend supervisor.
-/// - `port_name` empty => AUTO scan + adopt.
-/// - `port_name` non-empty => FIXED.
+/// Releases the emergency stop of one bank (every latched bank when
+/// `bank_id` is omitted). Contactors stay open until commanded.
 #[tauri::command]
-pub fn lb_start_polling(
-    app: AppHandle,
+pub fn lb_reset_estop(
     state: State<LoadBankRuntimeState>,
-    port_name: String,
-    baud: u32,
+    bank_id: Option<String>,
 ) -> Result<(), String> {
-    let requested_mode = RuntimeMode::from_port_name(&port_name);
-
-    // If running with same baud, just switch mode. | idempotent
-    {
-        let guard = state.inner.lock().unwrap();
-        if let Some(h) = guard.as_ref() {
-            if h.baud == baud && h.mode == requested_mode {
-                let _ = h.tx.send(RuntimeCmd::SetMode(requested_mode.clone()));
-                return Ok(());
-            }
+    let latches: Vec<(String, Arc<EstopLatch>)> = match bank_id {
+        Some(id) => {
+            let id = bank_key(Some(id));
+            vec![(id.clone(), state.estop(&id))]
+        }
+        None => state
+            .estops
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, l)| (id.clone(), l.clone()))
+            .collect(),
+    };
+    for (id, latch) in latches {
+        if !latch.release() {
+            continue;
         }
+        eprintln!("[LB/ESTOP] bank {id}: reset");
+        // not running: nothing to report
+        let _ = state.send(&id, RuntimeCmd::ResetEstop);
     }
+    Ok(())
+}
 
-    // Different baud / not running => stop old and start new
-    if let Some(old) = state.inner.lock().unwrap().take() {
-        let _ = old.tx.send(RuntimeCmd::Stop);
-        let _ = old.join.join();
-    }
+/// Remaining ON time per branch (at the safety `u2MaxV`). Also streamed as `lb/budget`.
+#[tauri::command]
+pub fn lb_get_budget(
+    state: State<LoadBankRuntimeState>,
+    bank_id: Option<String>,
+) -> BudgetSnapshot {
+    let bank_id = bank_key(bank_id);
+    let u2_v = state.budget_u2_v();
+    let budget = state.budget(&bank_id);
+    let snapshot = budget
+        .lock()
+        .unwrap()
+        .snapshot(&bank_id, u2_v, Instant::now());
+    snapshot
+}
 
-    let (tx, rx) = mpsc::channel::<RuntimeCmd>();
-    let app2 = app.clone();
-    let mode2 = requested_mode.clone();
-
-    let join = thread::spawn(move || {
-        let mut w = Worker::new(app2, baud, mode2);
-
-        loop {
-            // commands
-            while let Ok(cmd) = rx.try_recv() {
-                match cmd {
-                    RuntimeCmd::Stop => return,
-                    RuntimeCmd::SetMode(m) => w.set_mode(m),
-                    RuntimeCmd::SetPolling {
-                        enabled,
-                        interval_ms,
-                    } => w.set_polling(enabled, interval_ms),
-                    RuntimeCmd::WriteRaw(bytes) => w.send_tx(&bytes),
-                    RuntimeCmd::SetContactors(mask) => w.cmd_set_contactors(mask),
-                }
-            }
+// -----------------------------------------------------------------------------
+// Multi-bank aggregate
+// -----------------------------------------------------------------------------
 
-            // supervisor tick
-            w.ensure_connected();
-            w.poll_if_due();
-            w.read_once();
-            w.parse_frames();
-            w.offline_check();
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BankSummary {
+    pub bank_id: String,
+    pub mode: String,
+    pub port_name: Option<String>,
+    pub online: bool,
+    pub status: Option<LoadBankStatus>,
+    pub cmd_latency: CmdLatency,
+}
 
-            // avoid busy loop
-            thread::sleep(Duration::from_millis(8));
-        }
-    });
+/// Several banks seen as one resistor network. Banks are ordered by the
+/// `bank_no` they report; bank i owns bits `16*i .. 16*i+15` of `combined_mask`.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadBankAggregate {
+    pub banks: Vec<BankSummary>,
+    pub all_online: bool,
+    pub total_bank_power: u32,
+    pub combined_mask: u64,
+    pub has_errors: bool,
+}
 
-    *state.inner.lock().unwrap() = Some(RuntimeHandle {
-        baud,
-        mode: requested_mode,
-        tx,
-        join,
-    });
+// 3 x 16 bits keeps `combined_mask` exact as a JS number
+const MAX_COMBINED_BANKS: usize = 3;
 
+fn check_bank_count(banks: &[BankSummary]) -> Result<(), String> {
+    if banks.len() > MAX_COMBINED_BANKS {
+        return Err(format!(
+            "{} banks running, at most {MAX_COMBINED_BANKS} can be combined",
+            banks.len()
+        ));
+    }
     Ok(())
 }
 
-#[tauri::command]
-pub fn lb_stop_polling(state: State<LoadBankRuntimeState>) -> Result<(), String> {
-    if let Some(old) = state.inner.lock().unwrap().take() {
-        let _ = old.tx.send(RuntimeCmd::Stop);
-        let _ = old.join.join();
+/// Running banks sorted by reported `bank_no`; banks without a status yet
+/// come last so they don't shift the slices of the others.
+fn ordered_banks(state: &LoadBankRuntimeState) -> Vec<BankSummary> {
+    let guard = state.inner.lock().unwrap();
+    let mut banks: Vec<BankSummary> = guard
+        .iter()
+        .map(|(id, h)| {
+            let shared = h.worker.shared.lock().unwrap();
+            BankSummary {
+                bank_id: id.clone(),
+                mode: h.mode.key(),
+                port_name: shared.port_name.clone(),
+                online: shared.online,
+                status: shared.status.clone(),
+                cmd_latency: shared.cmd_latency,
+            }
+        })
+        .collect();
+    banks.sort_by_key(|b| {
+        let bank_no = b.status.as_ref().map(|s| s.bank_no);
+        (bank_no.is_none(), bank_no, b.bank_id.clone())
+    });
+    banks
+}
+
+// Bank i is slice i, whether or not it reported a status yet (slice 0 then).
+fn aggregate(banks: Vec<BankSummary>) -> Result<LoadBankAggregate, String> {
+    check_bank_count(&banks)?;
+
+    let mut combined_mask = 0u64;
+    let mut total_bank_power = 0u32;
+    let mut has_errors = false;
+    for (i, b) in banks.iter().enumerate() {
+        let Some(s) = b.status.as_ref() else {
+            continue;
+        };
+        combined_mask |= (s.contactors_mask as u64) << (16 * i);
+        total_bank_power += s.bank_power as u32;
+        has_errors |=
+            s.err_contactors != 0 || s.err_fans != 0 || s.err_thermals != 0 || s.other_errors != 0;
     }
-    Ok(())
+
+    Ok(LoadBankAggregate {
+        all_online: !banks.is_empty() && banks.iter().all(|b| b.online),
+        banks,
+        total_bank_power,
+        combined_mask,
+        has_errors,
+    })
 }
 
 #[tauri::command]
-pub fn lb_set_polling(
+pub fn lb_aggregate_status(
     state: State<LoadBankRuntimeState>,
-    enabled: bool,
-    interval_ms: u64,
-) -> Result<(), String> {
-    let guard = state.inner.lock().unwrap();
-    let h = guard.as_ref().ok_or("Load bank runtime not running")?;
-    h.tx.send(RuntimeCmd::SetPolling {
-        enabled,
-        interval_ms,
-    })
-    .map_err(|_| "runtime channel closed".to_string())
+) -> Result<LoadBankAggregate, String> {
+    aggregate(ordered_banks(&state))
 }
 
-/// Raw send (DevEchoPcbTest).
+/// Drive all banks as one network: splits `mask` in 16-bit slices (same order
+/// as `lb_aggregate_status`). Every running bank must be online.
 #[tauri::command]
-pub fn lb_write_bytes(state: State<LoadBankRuntimeState>, data: Vec<u8>) -> Result<(), String> {
-    let guard = state.inner.lock().unwrap();
-    let h = guard.as_ref().ok_or("Load bank runtime not running")?;
-    h.tx.send(RuntimeCmd::WriteRaw(data))
-        .map_err(|_| "runtime channel closed".to_string())
+pub fn lb_set_contactors_combined(
+    state: State<LoadBankRuntimeState>,
+    mask: u64,
+) -> Result<(), String> {
+    let banks = ordered_banks(&state);
+    if banks.is_empty() {
+        return Err("Load bank runtime not running".into());
+    }
+    if let Some(b) = banks.iter().find(|b| !b.online || b.status.is_none()) {
+        return Err(format!("bank '{}' is offline", b.bank_id));
+    }
+    check_bank_count(&banks)?;
+    if mask >> (16 * banks.len()) != 0 {
+        return Err(format!(
+            "mask 0x{mask:X} addresses more than {} bank(s)",
+            banks.len()
+        ));
+    }
+
+    for (i, b) in banks.iter().enumerate() {
+        let slice = ((mask >> (16 * i)) & 0xFFFF) as u16;
+        state.send(&b.bank_id, RuntimeCmd::SetContactors(slice))?;
+    }
+    Ok(())
 }
 
-/// Production command: backend builds the proper frame.
-#[tauri::command]
-pub fn lb_set_contactors(state: State<LoadBankRuntimeState>, mask: u16) -> Result<(), String> {
-    let guard = state.inner.lock().unwrap();
-    let h = guard.as_ref().ok_or("Load bank runtime not running")?;
-    h.tx.send(RuntimeCmd::SetContactors(mask))
-        .map_err(|_| "runtime channel closed".to_string())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lb_protocol::FrameFields;
+
+    fn bank(id: &str, fields: Option<FrameFields>) -> BankSummary {
+        BankSummary {
+            bank_id: id.into(),
+            mode: "sim".into(),
+            port_name: None,
+            online: fields.is_some(),
+            status: fields.map(|f| LoadBankStatus::from_fields(&f, &f.encode(), id, "sim")),
+            cmd_latency: CmdLatency::default(),
+        }
+    }
+
+    fn fields(contactors_mask: u16, bank_power: u16) -> Option<FrameFields> {
+        Some(FrameFields {
+            contactors_mask,
+            bank_power,
+            ..FrameFields::default()
+        })
+    }
+
+    #[test]
+    fn missing_status_keeps_its_slice() {
+        let agg = aggregate(vec![
+            bank("a", fields(0x0001, 10)),
+            bank("b", None),
+            bank("c", fields(0x0003, 20)),
+        ])
+        .unwrap();
+        assert_eq!(agg.combined_mask, 0x0003_0000_0001);
+        assert_eq!(agg.total_bank_power, 30);
+        assert!(!agg.all_online);
+        assert_eq!(agg.banks.len(), 3);
+    }
+
+    #[test]
+    fn refuses_more_banks_than_the_mask_holds() {
+        let banks: Vec<_> = ["a", "b", "c", "d"]
+            .into_iter()
+            .map(|id| bank(id, fields(0x0001, 0)))
+            .collect();
+        assert!(aggregate(banks[..MAX_COMBINED_BANKS].to_vec()).is_ok());
+        assert!(aggregate(banks).is_err());
+    }
 }
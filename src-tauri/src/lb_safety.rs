@@ -0,0 +1,401 @@
+// Contactor safety interlocks (backend side; the UI checks are convenience only).
+//
+// Every mask the runtime puts on the wire goes through `check_mask`:
+// - opening everything (mask 0) is always allowed
+// - closing anything needs an online bank without thermal / fan faults
+// - only wired branches may be closed, and the worst-case power drawn from the
+//   DUT must stay under the configured limit
+//
+// `trip_reason` is evaluated on every status frame: a fault reported while
+// contactors are closed makes the worker open them on its own.
+//
+// `lb_emergency_stop` trips an `EstopLatch`: the worker is woken ahead of its
+// command queue, sends all-open until a status frame reports it, and refuses
+// to close anything until `lb_reset_estop`.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+use tokio::sync::Notify;
+
+use crate::lb_protocol::FrameFields;
+use crate::load_model::{power_w, wired_mask};
+
+/// Highest U2 of the IEC load lines (MMA / MIG top out at 44 V).
+const DEFAULT_U2_MAX_V: f64 = 44.0;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SafetyConfig {
+    pub enabled: bool,
+    /// Open all contactors when the bank reports a thermal fault.
+    pub open_on_thermal: bool,
+    /// Open all contactors when the bank reports a fan fault.
+    pub open_on_fans: bool,
+    /// Contactors that may be closed at all.
+    pub allowed_mask: u16,
+    /// Max power the present DUT may deliver (None = no limit).
+    pub max_power_w: Option<f64>,
+    /// Voltage used for the worst-case power estimate.
+    pub u2_max_v: f64,
+}
+
+impl Default for SafetyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            open_on_thermal: true,
+            open_on_fans: true,
+            // only contactors with a modelled branch (C9..C16 not populated)
+            allowed_mask: wired_mask(),
+            max_power_w: None,
+            u2_max_v: DEFAULT_U2_MAX_V,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum Interlock {
+    Offline,
+    ThermalFault {
+        err_thermals: u16,
+    },
+    FanFault {
+        err_fans: u16,
+    },
+    UnwiredContactors {
+        mask: u16,
+        allowed_mask: u16,
+    },
+    OverPower {
+        mask: u16,
+        estimated_w: f64,
+        max_w: f64,
+    },
+    /// No overload window of the resistor covers this load factor.
+    Overload {
+        branch: String,
+        factor: f64,
+    },
+    /// Branch used up its ON time for the current cycle.
+    BudgetExhausted {
+        branch: String,
+        used_on_ms: f64,
+        allowed_on_ms: f64,
+    },
+    /// Emergency stop latched (released by `lb_reset_estop`).
+    EmergencyStop,
+}
+
+impl fmt::Display for Interlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Interlock::Offline => write!(f, "load bank offline"),
+            Interlock::ThermalFault { err_thermals } => {
+                write!(f, "thermal fault (0x{err_thermals:04X})")
+            }
+            Interlock::FanFault { err_fans } => write!(f, "fan fault (0x{err_fans:04X})"),
+            Interlock::UnwiredContactors { mask, allowed_mask } => write!(
+                f,
+                "mask 0x{mask:04X} closes contactors outside 0x{allowed_mask:04X}"
+            ),
+            Interlock::OverPower {
+                mask,
+                estimated_w,
+                max_w,
+            } => write!(
+                f,
+                "mask 0x{mask:04X} draws ~{estimated_w:.0} W (limit {max_w:.0} W)"
+            ),
+            Interlock::Overload { branch, factor } => {
+                write!(
+                    f,
+                    "{branch} at {factor:.2}x rated power has no overload window"
+                )
+            }
+            Interlock::BudgetExhausted {
+                branch,
+                used_on_ms,
+                allowed_on_ms,
+            } => write!(
+                f,
+                "{branch} ON-time budget used ({:.1} s of {:.1} s)",
+                used_on_ms / 1000.0,
+                allowed_on_ms / 1000.0
+            ),
+            Interlock::EmergencyStop => write!(f, "emergency stop latched"),
+        }
+    }
+}
+
+/// Fault that forces the contactors open, if any.
+pub fn fault_reason(cfg: &SafetyConfig, status: &FrameFields) -> Option<Interlock> {
+    if !cfg.enabled {
+        return None;
+    }
+    if cfg.open_on_thermal && status.err_thermals != 0 {
+        return Some(Interlock::ThermalFault {
+            err_thermals: status.err_thermals,
+        });
+    }
+    if cfg.open_on_fans && status.err_fans != 0 {
+        return Some(Interlock::FanFault {
+            err_fans: status.err_fans,
+        });
+    }
+    None
+}
+
+/// Status frame that requires an automatic open (fault while contactors closed).
+pub fn trip_reason(cfg: &SafetyConfig, status: &FrameFields) -> Option<Interlock> {
+    if status.contactors_mask == 0 {
+        return None;
+    }
+    fault_reason(cfg, status)
+}
+
+/// Validates a requested mask against the last known bank state.
+pub fn check_mask(
+    cfg: &SafetyConfig,
+    mask: u16,
+    online: bool,
+    last_status: Option<&FrameFields>,
+) -> Result<(), Interlock> {
+    if !cfg.enabled || mask == 0 {
+        return Ok(());
+    }
+    if !online {
+        return Err(Interlock::Offline);
+    }
+    if let Some(reason) = last_status.and_then(|s| fault_reason(cfg, s)) {
+        return Err(reason);
+    }
+    if mask & !cfg.allowed_mask != 0 {
+        return Err(Interlock::UnwiredContactors {
+            mask,
+            allowed_mask: cfg.allowed_mask,
+        });
+    }
+    if let Some(max_w) = cfg.max_power_w {
+        let estimated_w = power_w(mask, cfg.u2_max_v);
+        if estimated_w > max_w {
+            return Err(Interlock::OverPower {
+                mask,
+                estimated_w,
+                max_w,
+            });
+        }
+    }
+    Ok(())
+}
+
+// -----------------------------------------------------------------------------
+// Emergency stop
+// -----------------------------------------------------------------------------
+
+/// Emergency-stop latch of one bank, shared by the commands and the worker.
+/// Kept across runtime restarts (like the duty-cycle budget).
+#[derive(Default)]
+pub struct EstopLatch {
+    latched: AtomicBool,
+    reason: Mutex<Option<String>>,
+    /// Wakes the worker ahead of its command queue.
+    pub notify: Notify,
+}
+
+impl EstopLatch {
+    pub fn trip(&self, reason: &str) {
+        *self.reason.lock().unwrap() = Some(reason.to_string());
+        self.latched.store(true, Ordering::SeqCst);
+        self.notify.notify_one();
+    }
+
+    /// False if it wasn't latched.
+    #[cfg(any(test, feature = "gui"))]
+    pub fn release(&self) -> bool {
+        self.latched.swap(false, Ordering::SeqCst)
+    }
+
+    pub fn is_latched(&self) -> bool {
+        self.latched.load(Ordering::SeqCst)
+    }
+
+    pub fn reason(&self) -> Option<String> {
+        self.reason.lock().unwrap().clone()
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum EstopState {
+    /// All-open being sent.
+    Latched,
+    /// A status frame reported every contactor open.
+    Confirmed,
+    Released,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct EstopEvent {
+    pub t: DateTime<Local>,
+    pub bank_id: String,
+    pub port_name: Option<String>,
+    pub state: EstopState,
+    pub reason: Option<String>,
+    /// All-open frames sent so far.
+    pub sends: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `mask` closed, no faults
+    fn status(mask: u16) -> FrameFields {
+        FrameFields {
+            contactors_mask: mask,
+            ..FrameFields::default()
+        }
+    }
+
+    #[test]
+    fn opening_is_always_allowed() {
+        let cfg = SafetyConfig::default();
+        let faulted = FrameFields {
+            err_thermals: 0x0001,
+            ..status(0x0003)
+        };
+        assert_eq!(check_mask(&cfg, 0, false, Some(&faulted)), Ok(()));
+    }
+
+    #[test]
+    fn refuses_offline_and_faulted_banks() {
+        let cfg = SafetyConfig::default();
+        assert_eq!(
+            check_mask(&cfg, 0x0001, false, None),
+            Err(Interlock::Offline)
+        );
+        assert_eq!(check_mask(&cfg, 0x0001, true, Some(&status(0))), Ok(()));
+
+        let hot = FrameFields {
+            err_thermals: 0x0004,
+            ..status(0)
+        };
+        assert_eq!(
+            check_mask(&cfg, 0x0001, true, Some(&hot)),
+            Err(Interlock::ThermalFault {
+                err_thermals: 0x0004
+            })
+        );
+        let fans = FrameFields {
+            err_fans: 0x0002,
+            ..status(0)
+        };
+        assert_eq!(
+            check_mask(&cfg, 0x0001, true, Some(&fans)),
+            Err(Interlock::FanFault { err_fans: 0x0002 })
+        );
+
+        // faults only interlock when configured to
+        let cfg = SafetyConfig {
+            open_on_thermal: false,
+            open_on_fans: false,
+            ..SafetyConfig::default()
+        };
+        assert_eq!(check_mask(&cfg, 0x0001, true, Some(&hot)), Ok(()));
+        assert_eq!(check_mask(&cfg, 0x0001, true, Some(&fans)), Ok(()));
+    }
+
+    #[test]
+    fn refuses_unwired_contactors_and_over_power() {
+        let cfg = SafetyConfig {
+            max_power_w: Some(1000.0),
+            ..SafetyConfig::default()
+        };
+        assert!(matches!(
+            check_mask(&cfg, 0x0100, true, None),
+            Err(Interlock::UnwiredContactors { mask: 0x0100, .. })
+        ));
+        // R1 at 44 V: ~452 W
+        assert_eq!(check_mask(&cfg, 0x0001, true, None), Ok(()));
+        // R3 at 44 V: ~1936 W
+        assert!(matches!(
+            check_mask(&cfg, 0x0004, true, None),
+            Err(Interlock::OverPower { mask: 0x0004, estimated_w, max_w })
+                if estimated_w > 1900.0 && max_w == 1000.0
+        ));
+
+        let off = SafetyConfig {
+            enabled: false,
+            ..cfg
+        };
+        assert_eq!(check_mask(&off, 0xFFFF, false, None), Ok(()));
+    }
+
+    #[test]
+    fn trips_on_faults_while_closed() {
+        let cfg = SafetyConfig::default();
+        let hot = |mask| FrameFields {
+            err_thermals: 0x0001,
+            ..status(mask)
+        };
+        assert_eq!(
+            trip_reason(&cfg, &hot(0x0003)),
+            Some(Interlock::ThermalFault {
+                err_thermals: 0x0001
+            })
+        );
+        // already open: nothing to do
+        assert_eq!(trip_reason(&cfg, &hot(0)), None);
+        // thermal wins over fans
+        let both = FrameFields {
+            err_fans: 0x0001,
+            ..hot(0x0003)
+        };
+        assert!(matches!(
+            trip_reason(&cfg, &both),
+            Some(Interlock::ThermalFault { .. })
+        ));
+        let fans = FrameFields {
+            err_fans: 0x0001,
+            ..status(0x0003)
+        };
+        assert_eq!(
+            trip_reason(&cfg, &fans),
+            Some(Interlock::FanFault { err_fans: 0x0001 })
+        );
+        assert_eq!(trip_reason(&cfg, &status(0x0003)), None);
+
+        let off = SafetyConfig {
+            enabled: false,
+            ..SafetyConfig::default()
+        };
+        assert_eq!(trip_reason(&off, &both), None);
+    }
+
+    #[test]
+    fn estop_latch_holds_until_released() {
+        let latch = EstopLatch::default();
+        assert!(!latch.is_latched());
+        assert!(!latch.release());
+
+        latch.trip("operator");
+        assert!(latch.is_latched());
+        assert_eq!(latch.reason().as_deref(), Some("operator"));
+        assert!(latch.release());
+        assert!(!latch.is_latched());
+        assert!(!latch.release());
+    }
+}
@@ -0,0 +1,708 @@
+// Load-profile sequencer (calibration runs without webview timers).
+//
+// A `LoadProfile` is a list of steps. Each step closes a contactor mask (given,
+// or resolved from a target current like `lb_resolve_setpoint`), waits for a
+// status frame reporting it, lets the load settle for `settleMs` and then
+// holds it for `dwellMs`. `captureAtMs` offsets into the dwell fire
+// `lb/sequence-capture` with the latest status, so meters are read while the
+// load is known to be stable.
+//
+// The sequencer runs in the bank's worker: masks go through the same safety
+// interlocks and duty-cycle budget as any other command, a step whose
+// settle + dwell doesn't fit in the remaining ON time is refused before
+// closing, and an auto-open (trip, budget used up), a manual contactor
+// command or a dropped port aborts the run. Progress is emitted as
+// `lb/sequence` on every transition and every PROGRESS_EVERY_MS. Pause holds
+// the current load and stops the clock; abort and the end of the profile
+// open the contactors.
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+#[cfg(feature = "gui")]
+use tauri::State;
+#[cfg(feature = "gui")]
+use tokio::sync::oneshot;
+
+use crate::lb_link::LoadBankStatus;
+#[cfg(feature = "gui")]
+use crate::lb_runtime::{bank_key, LoadBankRuntimeState};
+use crate::load_model::Process;
+
+#[cfg(any(test, feature = "gui"))]
+const MAX_STEPS: usize = 1000;
+#[cfg(any(test, feature = "gui"))]
+const MAX_STEP_MS: u64 = 3_600_000;
+const PROGRESS_EVERY_MS: u64 = 500;
+// per attempt, like `lb_set_contactors_confirmed`
+const SWITCH_TIMEOUT_MS: u64 = 500;
+const SWITCH_ATTEMPTS: u32 = 3;
+
+// -----------------------------------------------------------------------------
+// Profile
+// -----------------------------------------------------------------------------
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum StepTarget {
+    Mask {
+        mask: u16,
+    },
+    /// Best combo for the current at the conventional load voltage of `process`,
+    /// resolved when the step starts (so it sees the budget used so far).
+    Current {
+        current_a: f64,
+        process: Process,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileStep {
+    #[serde(default)]
+    pub label: Option<String>,
+    pub target: StepTarget,
+    /// From the confirming status frame to the start of the dwell.
+    #[serde(default)]
+    pub settle_ms: u64,
+    pub dwell_ms: u64,
+    /// Offsets into the dwell at which `lb/sequence-capture` fires.
+    #[serde(default)]
+    pub capture_at_ms: Vec<u64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadProfile {
+    #[serde(default)]
+    pub name: String,
+    pub steps: Vec<ProfileStep>,
+    /// Tolerance of current targets (None => `DEFAULT_MAX_REL_ERROR`).
+    #[serde(default)]
+    pub max_rel_error: Option<f64>,
+}
+
+impl LoadProfile {
+    #[cfg(any(test, feature = "gui"))]
+    pub fn validate(&self) -> Result<(), String> {
+        if self.steps.is_empty() || self.steps.len() > MAX_STEPS {
+            return Err(format!("a profile has 1..{MAX_STEPS} steps"));
+        }
+        if self
+            .max_rel_error
+            .is_some_and(|e| !e.is_finite() || e <= 0.0)
+        {
+            return Err("maxRelError must be > 0".into());
+        }
+        for (i, s) in self.steps.iter().enumerate() {
+            let n = i + 1;
+            if let StepTarget::Current { current_a, .. } = s.target {
+                if !current_a.is_finite() || current_a <= 0.0 {
+                    return Err(format!("step {n}: invalid current {current_a}"));
+                }
+            }
+            if s.settle_ms + s.dwell_ms > MAX_STEP_MS {
+                return Err(format!("step {n}: longer than {MAX_STEP_MS} ms"));
+            }
+            if let Some(at) = s.capture_at_ms.iter().find(|&&at| at > s.dwell_ms) {
+                return Err(format!("step {n}: capture at {at} ms is past the dwell"));
+            }
+        }
+        Ok(())
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Progress
+// -----------------------------------------------------------------------------
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum SequenceState {
+    Running,
+    Paused,
+    Finished,
+    Aborted,
+    Failed,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum StepPhase {
+    /// Mask sent, waiting for a status frame reporting it.
+    Switching,
+    Settling,
+    Dwelling,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SequenceProgress {
+    pub t: DateTime<Local>,
+    pub bank_id: String,
+    pub profile: String,
+    pub state: SequenceState,
+    /// 0-based.
+    pub step: usize,
+    pub steps: usize,
+    pub label: Option<String>,
+    pub phase: StepPhase,
+    /// Mask of the current step, once resolved.
+    pub mask: Option<u16>,
+    /// None while switching.
+    pub phase_remaining_ms: Option<u64>,
+    /// Run time, pauses excluded.
+    pub elapsed_ms: u64,
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SequenceCapture {
+    pub t: DateTime<Local>,
+    pub bank_id: String,
+    pub profile: String,
+    pub step: usize,
+    pub label: Option<String>,
+    pub at_ms: u64,
+    pub mask: u16,
+    pub status: Option<LoadBankStatus>,
+}
+
+// -----------------------------------------------------------------------------
+// Sequencer (one per running profile, driven by the worker)
+// -----------------------------------------------------------------------------
+
+enum Phase {
+    /// Step not started: the worker resolves and closes its mask.
+    Start,
+    Switching {
+        mask: u16,
+        deadline: Instant,
+        attempts: u32,
+    },
+    Settling {
+        mask: u16,
+        until: Instant,
+    },
+    Dwelling {
+        mask: u16,
+        since: Instant,
+        until: Instant,
+        next_capture: usize,
+    },
+}
+
+/// What the worker has to do next.
+pub enum SequenceAction {
+    /// Resolve and close the mask of the current step (then `switched`).
+    StartStep,
+    Resend(u16),
+    Capture {
+        step: usize,
+        at_ms: u64,
+        mask: u16,
+    },
+    NoConfirm {
+        mask: u16,
+        attempts: u32,
+    },
+    Done,
+}
+
+pub struct Sequencer {
+    profile: LoadProfile,
+    step: usize,
+    phase: Phase,
+    started: Instant,
+    paused_at: Option<Instant>,
+    paused_total: Duration,
+    last_progress: Option<Instant>,
+}
+
+impl Sequencer {
+    pub fn new(mut profile: LoadProfile, now: Instant) -> Self {
+        for s in &mut profile.steps {
+            s.capture_at_ms.sort_unstable();
+        }
+        Self {
+            profile,
+            step: 0,
+            phase: Phase::Start,
+            started: now,
+            paused_at: None,
+            paused_total: Duration::ZERO,
+            last_progress: None,
+        }
+    }
+
+    pub fn profile(&self) -> &LoadProfile {
+        &self.profile
+    }
+
+    pub fn current_step(&self) -> &ProfileStep {
+        &self.profile.steps[self.step]
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    fn mask(&self) -> Option<u16> {
+        match self.phase {
+            Phase::Start => None,
+            Phase::Switching { mask, .. }
+            | Phase::Settling { mask, .. }
+            | Phase::Dwelling { mask, .. } => Some(mask),
+        }
+    }
+
+    // timers started while paused count from the pause (resume shifts them)
+    fn clock(&self, now: Instant) -> Instant {
+        self.paused_at.unwrap_or(now)
+    }
+
+    /// Next action due at `now`; call until None. Nothing is due while paused.
+    pub fn poll(&mut self, now: Instant) -> Option<SequenceAction> {
+        if self.paused_at.is_some() {
+            return None;
+        }
+        loop {
+            match &mut self.phase {
+                Phase::Start => return Some(SequenceAction::StartStep),
+                Phase::Switching {
+                    mask,
+                    deadline,
+                    attempts,
+                } => {
+                    if now < *deadline {
+                        return None;
+                    }
+                    if *attempts >= SWITCH_ATTEMPTS {
+                        return Some(SequenceAction::NoConfirm {
+                            mask: *mask,
+                            attempts: *attempts,
+                        });
+                    }
+                    *attempts += 1;
+                    *deadline = now + Duration::from_millis(SWITCH_TIMEOUT_MS);
+                    return Some(SequenceAction::Resend(*mask));
+                }
+                Phase::Settling { mask, until } => {
+                    if now < *until {
+                        return None;
+                    }
+                    let (mask, since) = (*mask, *until);
+                    let dwell = Duration::from_millis(self.current_step().dwell_ms);
+                    self.set_phase(Phase::Dwelling {
+                        mask,
+                        since,
+                        until: since + dwell,
+                        next_capture: 0,
+                    });
+                }
+                Phase::Dwelling {
+                    mask,
+                    since,
+                    until,
+                    next_capture,
+                } => {
+                    let step = &self.profile.steps[self.step];
+                    if let Some(&at_ms) = step.capture_at_ms.get(*next_capture) {
+                        if now >= *since + Duration::from_millis(at_ms) {
+                            *next_capture += 1;
+                            return Some(SequenceAction::Capture {
+                                step: self.step,
+                                at_ms,
+                                mask: *mask,
+                            });
+                        }
+                    }
+                    if now < *until {
+                        return None;
+                    }
+                    if self.step + 1 == self.profile.steps.len() {
+                        return Some(SequenceAction::Done);
+                    }
+                    self.step += 1;
+                    self.set_phase(Phase::Start);
+                }
+            }
+        }
+    }
+
+    fn set_phase(&mut self, phase: Phase) {
+        self.phase = phase;
+        // transitions are reported right away
+        self.last_progress = None;
+    }
+
+    /// Mask of the current step was sent.
+    pub fn switched(&mut self, mask: u16, now: Instant) {
+        self.set_phase(Phase::Switching {
+            mask,
+            deadline: self.clock(now) + Duration::from_millis(SWITCH_TIMEOUT_MS),
+            attempts: 1,
+        });
+    }
+
+    /// Mask reported by a status frame.
+    pub fn on_mask(&mut self, reported: u16, now: Instant) {
+        if let Phase::Switching { mask, .. } = self.phase {
+            if mask == reported {
+                let settle = Duration::from_millis(self.current_step().settle_ms);
+                self.set_phase(Phase::Settling {
+                    mask,
+                    until: self.clock(now) + settle,
+                });
+            }
+        }
+    }
+
+    pub fn pause(&mut self, now: Instant) -> Result<(), String> {
+        if self.paused_at.is_some() {
+            return Err("load profile already paused".into());
+        }
+        self.paused_at = Some(now);
+        self.last_progress = None;
+        Ok(())
+    }
+
+    pub fn resume(&mut self, now: Instant) -> Result<(), String> {
+        let Some(paused_at) = self.paused_at.take() else {
+            return Err("load profile not paused".into());
+        };
+        let d = now.saturating_duration_since(paused_at);
+        self.paused_total += d;
+        match &mut self.phase {
+            Phase::Start => {}
+            Phase::Switching { deadline, .. } => *deadline += d,
+            Phase::Settling { until, .. } => *until += d,
+            Phase::Dwelling { since, until, .. } => {
+                *since += d;
+                *until += d;
+            }
+        }
+        self.last_progress = None;
+        Ok(())
+    }
+
+    /// Earliest time `poll` or a periodic progress event can be due.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        if self.paused_at.is_some() {
+            return None;
+        }
+        let progress = self
+            .last_progress
+            .map(|t| t + Duration::from_millis(PROGRESS_EVERY_MS));
+        let phase = match &self.phase {
+            Phase::Start => None,
+            Phase::Switching { deadline, .. } => Some(*deadline),
+            Phase::Settling { until, .. } => Some(*until),
+            Phase::Dwelling {
+                since,
+                until,
+                next_capture,
+                ..
+            } => {
+                let capture = self
+                    .current_step()
+                    .capture_at_ms
+                    .get(*next_capture)
+                    .map(|&at| *since + Duration::from_millis(at));
+                Some(capture.map_or(*until, |c| c.min(*until)))
+            }
+        };
+        match (progress, phase) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// A transition happened or the periodic progress event is due.
+    pub fn progress_due(&self, now: Instant) -> bool {
+        match self.last_progress {
+            None => true,
+            Some(t) => {
+                self.paused_at.is_none() && now >= t + Duration::from_millis(PROGRESS_EVERY_MS)
+            }
+        }
+    }
+
+    pub fn progress(
+        &mut self,
+        bank_id: &str,
+        state: SequenceState,
+        reason: Option<String>,
+        now: Instant,
+    ) -> SequenceProgress {
+        self.last_progress = Some(now);
+        let clock = self.clock(now);
+        let left = |t: Instant| Some(t.saturating_duration_since(clock).as_millis() as u64);
+        let (phase, phase_remaining_ms) = match self.phase {
+            Phase::Start | Phase::Switching { .. } => (StepPhase::Switching, None),
+            Phase::Settling { until, .. } => (StepPhase::Settling, left(until)),
+            Phase::Dwelling { until, .. } => (StepPhase::Dwelling, left(until)),
+        };
+        let elapsed = clock
+            .saturating_duration_since(self.started)
+            .saturating_sub(self.paused_total);
+        SequenceProgress {
+            t: Local::now(),
+            bank_id: bank_id.to_string(),
+            profile: self.profile.name.clone(),
+            state,
+            step: self.step,
+            steps: self.profile.steps.len(),
+            label: self.current_step().label.clone(),
+            phase,
+            mask: self.mask(),
+            phase_remaining_ms,
+            elapsed_ms: elapsed.as_millis() as u64,
+            reason,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Commands
+// -----------------------------------------------------------------------------
+
+// only the app's commands send these (lbctl runs no profiles)
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub enum SequenceCmd {
+    Start(LoadProfile),
+    Pause,
+    Resume,
+    Abort,
+}
+
+#[cfg(feature = "gui")]
+async fn send(
+    state: &LoadBankRuntimeState,
+    bank_id: Option<String>,
+    cmd: SequenceCmd,
+) -> Result<SequenceProgress, String> {
+    let (reply, wait) = oneshot::channel();
+    state.send_sequence(&bank_key(bank_id), cmd, reply)?;
+    // Sender dropped => worker stopped before answering.
+    wait.await
+        .map_err(|_| "load bank runtime stopped".to_string())?
+}
+
+/// Runs `profile` on a connected bank; resolves once the first step is sent.
+/// Progress streams as `lb/sequence`, capture triggers as `lb/sequence-capture`.
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub async fn lb_sequence_start(
+    state: State<'_, LoadBankRuntimeState>,
+    bank_id: Option<String>,
+    profile: LoadProfile,
+) -> Result<SequenceProgress, String> {
+    profile.validate()?;
+    send(&state, bank_id, SequenceCmd::Start(profile)).await
+}
+
+/// Holds the current load and stops the step clock.
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub async fn lb_sequence_pause(
+    state: State<'_, LoadBankRuntimeState>,
+    bank_id: Option<String>,
+) -> Result<SequenceProgress, String> {
+    send(&state, bank_id, SequenceCmd::Pause).await
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub async fn lb_sequence_resume(
+    state: State<'_, LoadBankRuntimeState>,
+    bank_id: Option<String>,
+) -> Result<SequenceProgress, String> {
+    send(&state, bank_id, SequenceCmd::Resume).await
+}
+
+/// Stops the profile and opens the contactors.
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub async fn lb_sequence_abort(
+    state: State<'_, LoadBankRuntimeState>,
+    bank_id: Option<String>,
+) -> Result<SequenceProgress, String> {
+    send(&state, bank_id, SequenceCmd::Abort).await
+}
+
+/// Progress of the running profile, or how the last one ended.
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_sequence_status(
+    state: State<LoadBankRuntimeState>,
+    bank_id: Option<String>,
+) -> Result<Option<SequenceProgress>, String> {
+    state.sequence_progress(&bank_key(bank_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn step(mask: u16, settle_ms: u64, dwell_ms: u64, capture_at_ms: &[u64]) -> ProfileStep {
+        ProfileStep {
+            label: None,
+            target: StepTarget::Mask { mask },
+            settle_ms,
+            dwell_ms,
+            capture_at_ms: capture_at_ms.to_vec(),
+        }
+    }
+
+    fn sequencer(steps: Vec<ProfileStep>, t0: Instant) -> Sequencer {
+        let profile = LoadProfile {
+            name: "test".into(),
+            steps,
+            max_rel_error: None,
+        };
+        profile.validate().unwrap();
+        Sequencer::new(profile, t0)
+    }
+
+    fn at(t0: Instant, ms: u64) -> Instant {
+        t0 + Duration::from_millis(ms)
+    }
+
+    // StartStep, switched, confirmed at `ms`
+    fn start_step(seq: &mut Sequencer, mask: u16, t0: Instant, ms: u64) {
+        assert!(matches!(
+            seq.poll(at(t0, ms)),
+            Some(SequenceAction::StartStep)
+        ));
+        seq.switched(mask, at(t0, ms));
+        assert!(seq.poll(at(t0, ms)).is_none());
+        seq.on_mask(mask, at(t0, ms));
+    }
+
+    #[test]
+    fn runs_every_step_and_ends_with_done() {
+        let t0 = Instant::now();
+        let mut seq = sequencer(vec![step(0b01, 100, 200, &[]), step(0b10, 0, 50, &[])], t0);
+
+        start_step(&mut seq, 0b01, t0, 10);
+        assert!(seq.poll(at(t0, 109)).is_none());
+        assert_eq!(seq.next_deadline().map(|d| d >= at(t0, 110)), Some(true));
+        // settled at 110, dwell until 310
+        assert!(seq.poll(at(t0, 110)).is_none());
+        assert!(seq.poll(at(t0, 309)).is_none());
+        assert!(matches!(
+            seq.poll(at(t0, 310)),
+            Some(SequenceAction::StartStep)
+        ));
+        assert_eq!(seq.current_step().target, StepTarget::Mask { mask: 0b10 });
+
+        start_step(&mut seq, 0b10, t0, 320);
+        assert!(seq.poll(at(t0, 369)).is_none());
+        assert!(matches!(seq.poll(at(t0, 370)), Some(SequenceAction::Done)));
+        // stays done until the worker drops it
+        assert!(matches!(seq.poll(at(t0, 400)), Some(SequenceAction::Done)));
+    }
+
+    #[test]
+    fn captures_fire_once_in_order() {
+        let t0 = Instant::now();
+        let mut seq = sequencer(vec![step(0b11, 0, 300, &[150, 0, 50])], t0);
+
+        start_step(&mut seq, 0b11, t0, 0);
+        // a late poll still gets every due capture, oldest first
+        let mut fired = vec![];
+        while let Some(action) = seq.poll(at(t0, 200)) {
+            match action {
+                SequenceAction::Capture { step, at_ms, mask } => {
+                    assert_eq!((step, mask), (0, 0b11));
+                    fired.push(at_ms);
+                }
+                _ => panic!("unexpected action"),
+            }
+        }
+        assert_eq!(fired, [0, 50, 150]);
+        assert!(seq.poll(at(t0, 299)).is_none());
+        assert!(matches!(seq.poll(at(t0, 300)), Some(SequenceAction::Done)));
+    }
+
+    #[test]
+    fn resends_the_mask_then_gives_up() {
+        let t0 = Instant::now();
+        let mut seq = sequencer(vec![step(0b101, 0, 100, &[])], t0);
+
+        assert!(matches!(seq.poll(t0), Some(SequenceAction::StartStep)));
+        seq.switched(0b101, t0);
+        // another mask doesn't confirm the step
+        seq.on_mask(0b001, at(t0, 10));
+        let mut t = 0;
+        for _ in 1..SWITCH_ATTEMPTS {
+            assert!(seq.poll(at(t0, t + SWITCH_TIMEOUT_MS - 1)).is_none());
+            t += SWITCH_TIMEOUT_MS;
+            assert!(matches!(
+                seq.poll(at(t0, t)),
+                Some(SequenceAction::Resend(0b101))
+            ));
+        }
+        t += SWITCH_TIMEOUT_MS;
+        assert!(matches!(
+            seq.poll(at(t0, t)),
+            Some(SequenceAction::NoConfirm {
+                mask: 0b101,
+                attempts: SWITCH_ATTEMPTS
+            })
+        ));
+    }
+
+    #[test]
+    fn pause_stops_the_clock_and_resume_shifts_it() {
+        let t0 = Instant::now();
+        let mut seq = sequencer(vec![step(0b1, 0, 300, &[100])], t0);
+
+        start_step(&mut seq, 0b1, t0, 0);
+        seq.pause(at(t0, 50)).unwrap();
+        assert!(seq.pause(at(t0, 60)).is_err());
+        assert!(seq.poll(at(t0, 500)).is_none());
+        assert!(seq.next_deadline().is_none());
+
+        // paused for 1000 ms: capture at 1100, end at 1300
+        seq.resume(at(t0, 1050)).unwrap();
+        assert!(seq.resume(at(t0, 1060)).is_err());
+        assert!(seq.poll(at(t0, 1099)).is_none());
+        assert!(matches!(
+            seq.poll(at(t0, 1100)),
+            Some(SequenceAction::Capture { at_ms: 100, .. })
+        ));
+        assert!(seq.poll(at(t0, 1299)).is_none());
+        let progress = seq.progress("default", SequenceState::Running, None, at(t0, 1299));
+        assert_eq!(progress.phase, StepPhase::Dwelling);
+        assert_eq!(progress.phase_remaining_ms, Some(1));
+        assert_eq!(progress.elapsed_ms, 299);
+        assert!(matches!(seq.poll(at(t0, 1300)), Some(SequenceAction::Done)));
+    }
+
+    #[test]
+    fn settle_confirmed_while_paused_starts_at_resume() {
+        let t0 = Instant::now();
+        let mut seq = sequencer(vec![step(0b1, 200, 100, &[])], t0);
+
+        assert!(matches!(seq.poll(t0), Some(SequenceAction::StartStep)));
+        seq.switched(0b1, t0);
+        seq.pause(at(t0, 100)).unwrap();
+        // confirmation arrives during the pause: settling counts from the pause
+        seq.on_mask(0b1, at(t0, 400));
+        seq.resume(at(t0, 600)).unwrap();
+        // settled at 100 + 500 paused + 200
+        assert!(seq.poll(at(t0, 799)).is_none());
+        let progress = seq.progress("default", SequenceState::Running, None, at(t0, 799));
+        assert_eq!(progress.phase, StepPhase::Settling);
+        assert!(seq.poll(at(t0, 800)).is_none());
+        assert!(seq.poll(at(t0, 899)).is_none());
+        assert!(matches!(seq.poll(at(t0, 900)), Some(SequenceAction::Done)));
+    }
+}
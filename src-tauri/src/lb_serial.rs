@@ -0,0 +1,402 @@
+// Serial line settings beyond the baud rate.
+//
+// `SerialConfig` covers framing (data bits / parity / stop bits), flow
+// control, the DTR/RTS levels set right after opening and the read timeout.
+// Some USB-RS485 adapters and older boards need non-default values. Configs
+// are saved per port name in `<app data>/lb-serial-ports.json`; ports without
+// one use `SerialConfig::default()` (8N1, no flow control, 30 ms timeout, the
+// serialport library defaults the runtime used before).
+
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+#[cfg(feature = "gui")]
+use std::{collections::BTreeMap, fs, path::PathBuf, sync::Mutex};
+#[cfg(feature = "gui")]
+use tauri::{AppHandle, Manager, State};
+
+#[cfg(feature = "gui")]
+pub const SERIAL_CONFIG_FILE_NAME: &str = "lb-serial-ports.json";
+
+const DEFAULT_READ_TIMEOUT_MS: u64 = 30;
+const MAX_READ_TIMEOUT_MS: u64 = 1000;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum StopBits {
+    One,
+    Two,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum FlowControl {
+    None,
+    /// XON/XOFF
+    Software,
+    /// RTS/CTS
+    Hardware,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SerialConfig {
+    /// 5..=8
+    pub data_bits: u8,
+    pub parity: Parity,
+    pub stop_bits: StopBits,
+    pub flow_control: FlowControl,
+    /// Level set right after open; None => leave it to the driver.
+    pub dtr: Option<bool>,
+    pub rts: Option<bool>,
+    pub read_timeout_ms: u64,
+}
+
+impl Default for SerialConfig {
+    fn default() -> Self {
+        Self {
+            data_bits: 8,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+            dtr: None,
+            rts: None,
+            read_timeout_ms: DEFAULT_READ_TIMEOUT_MS,
+        }
+    }
+}
+
+impl SerialConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(5..=8).contains(&self.data_bits) {
+            return Err(format!("dataBits must be 5..8 (got {})", self.data_bits));
+        }
+        if !(1..=MAX_READ_TIMEOUT_MS).contains(&self.read_timeout_ms) {
+            return Err(format!(
+                "readTimeoutMs must be 1..{MAX_READ_TIMEOUT_MS} (got {})",
+                self.read_timeout_ms
+            ));
+        }
+        if self.flow_control == FlowControl::Hardware && self.rts.is_some() {
+            return Err("rts is driven by hardware flow control".into());
+        }
+        Ok(())
+    }
+
+    pub fn read_timeout(&self) -> Duration {
+        Duration::from_millis(self.read_timeout_ms)
+    }
+
+    /// Framing in the usual short form ("8N1").
+    #[cfg(any(test, feature = "gui"))]
+    pub fn label(&self) -> String {
+        let parity = match self.parity {
+            Parity::None => 'N',
+            Parity::Odd => 'O',
+            Parity::Even => 'E',
+        };
+        let stop = match self.stop_bits {
+            StopBits::One => 1,
+            StopBits::Two => 2,
+        };
+        format!("{}{parity}{stop}", self.data_bits)
+    }
+
+    /// Inverse of `label` (framing only, the rest is kept).
+    pub fn with_label(mut self, label: &str) -> Result<Self, String> {
+        let bad = || format!("bad serial format {label:?} (expected e.g. 8N1)");
+        let mut chars = label.trim().chars();
+        let (Some(d), Some(p), Some(s), None) =
+            (chars.next(), chars.next(), chars.next(), chars.next())
+        else {
+            return Err(bad());
+        };
+        self.data_bits = d.to_digit(10).ok_or_else(bad)? as u8;
+        self.parity = match p.to_ascii_uppercase() {
+            'N' => Parity::None,
+            'O' => Parity::Odd,
+            'E' => Parity::Even,
+            _ => return Err(bad()),
+        };
+        self.stop_bits = match s {
+            '1' => StopBits::One,
+            '2' => StopBits::Two,
+            _ => return Err(bad()),
+        };
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Opens `port_name` with these settings and sets DTR / RTS.
+    pub fn open(
+        &self,
+        port_name: &str,
+        baud: u32,
+    ) -> Result<Box<dyn serialport::SerialPort>, String> {
+        self.validate()?;
+        let data_bits = match self.data_bits {
+            5 => serialport::DataBits::Five,
+            6 => serialport::DataBits::Six,
+            7 => serialport::DataBits::Seven,
+            _ => serialport::DataBits::Eight,
+        };
+        let parity = match self.parity {
+            Parity::None => serialport::Parity::None,
+            Parity::Odd => serialport::Parity::Odd,
+            Parity::Even => serialport::Parity::Even,
+        };
+        let stop_bits = match self.stop_bits {
+            StopBits::One => serialport::StopBits::One,
+            StopBits::Two => serialport::StopBits::Two,
+        };
+        let flow_control = match self.flow_control {
+            FlowControl::None => serialport::FlowControl::None,
+            FlowControl::Software => serialport::FlowControl::Software,
+            FlowControl::Hardware => serialport::FlowControl::Hardware,
+        };
+        let mut port = serialport::new(port_name, baud)
+            .data_bits(data_bits)
+            .parity(parity)
+            .stop_bits(stop_bits)
+            .flow_control(flow_control)
+            .timeout(self.read_timeout())
+            .open()
+            .map_err(|e| e.to_string())?;
+        if let Some(level) = self.dtr {
+            port.write_data_terminal_ready(level)
+                .map_err(|e| format!("set DTR: {e}"))?;
+        }
+        if let Some(level) = self.rts {
+            port.write_request_to_send(level)
+                .map_err(|e| format!("set RTS: {e}"))?;
+        }
+        Ok(port)
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Per-port store
+// -----------------------------------------------------------------------------
+
+#[cfg(feature = "gui")]
+#[derive(Default)]
+pub struct LoadBankSerialState {
+    // loaded on first use
+    configs: Mutex<Option<BTreeMap<String, SerialConfig>>>,
+}
+
+#[cfg(feature = "gui")]
+fn store_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    Ok(dir.join(SERIAL_CONFIG_FILE_NAME))
+}
+
+#[cfg(feature = "gui")]
+fn load_store(app: &AppHandle) -> BTreeMap<String, SerialConfig> {
+    let Ok(path) = store_path(app) else {
+        return BTreeMap::new();
+    };
+    match fs::read_to_string(&path) {
+        Ok(text) => serde_json::from_str(&text).unwrap_or_else(|e| {
+            eprintln!("[LB/SERIAL] ignoring {}: {e}", path.display());
+            BTreeMap::new()
+        }),
+        Err(_) => BTreeMap::new(),
+    }
+}
+
+#[cfg(feature = "gui")]
+fn save_store(app: &AppHandle, configs: &BTreeMap<String, SerialConfig>) -> Result<(), String> {
+    let path = store_path(app)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("create {}: {e}", dir.display()))?;
+    }
+    let json = serde_json::to_string_pretty(configs).map_err(|e| e.to_string())?;
+    fs::write(&path, json).map_err(|e| format!("write {}: {e}", path.display()))
+}
+
+#[cfg(feature = "gui")]
+impl LoadBankSerialState {
+    fn with_configs<R>(
+        &self,
+        app: &AppHandle,
+        f: impl FnOnce(&mut BTreeMap<String, SerialConfig>) -> R,
+    ) -> R {
+        let mut guard = self.configs.lock().unwrap();
+        f(guard.get_or_insert_with(|| load_store(app)))
+    }
+
+    /// Saved config of `port_name`, if any.
+    pub fn saved(&self, app: &AppHandle, port_name: &str) -> Option<SerialConfig> {
+        self.with_configs(app, |c| c.get(port_name).cloned())
+    }
+
+    pub fn save(
+        &self,
+        app: &AppHandle,
+        port_name: &str,
+        config: SerialConfig,
+    ) -> Result<(), String> {
+        config.validate()?;
+        self.with_configs(app, |c| {
+            if c.get(port_name) == Some(&config) {
+                return Ok(());
+            }
+            eprintln!("[LB/SERIAL] {port_name}: {} {:?}", config.label(), config);
+            c.insert(port_name.to_string(), config);
+            save_store(app, c)
+        })
+    }
+
+    /// Config to open `port_name` with: `given` (saved for next time), else
+    /// the saved one, else the defaults.
+    pub fn resolve(
+        &self,
+        app: &AppHandle,
+        port_name: &str,
+        given: Option<SerialConfig>,
+    ) -> Result<SerialConfig, String> {
+        match given {
+            Some(config) => {
+                self.save(app, port_name, config.clone())?;
+                Ok(config)
+            }
+            None => Ok(self.saved(app, port_name).unwrap_or_default()),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Commands
+// -----------------------------------------------------------------------------
+
+/// Saved config of a port, or the defaults.
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_get_serial_config(
+    app: AppHandle,
+    state: State<LoadBankSerialState>,
+    port_name: String,
+) -> SerialConfig {
+    state.saved(&app, &port_name).unwrap_or_default()
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_list_serial_configs(
+    app: AppHandle,
+    state: State<LoadBankSerialState>,
+) -> BTreeMap<String, SerialConfig> {
+    state.with_configs(&app, |c| c.clone())
+}
+
+/// Saves the config of a port; used the next time the port is opened.
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_set_serial_config(
+    app: AppHandle,
+    state: State<LoadBankSerialState>,
+    port_name: String,
+    config: SerialConfig,
+) -> Result<SerialConfig, String> {
+    state.save(&app, &port_name, config.clone())?;
+    Ok(config)
+}
+
+/// Back to the defaults.
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_forget_serial_config(
+    app: AppHandle,
+    state: State<LoadBankSerialState>,
+    port_name: String,
+) -> Result<(), String> {
+    state.with_configs(&app, |c| {
+        if c.remove(&port_name).is_none() {
+            return Ok(());
+        }
+        eprintln!("[LB/SERIAL] {port_name}: back to defaults");
+        save_store(&app, c)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_round_trip() {
+        let base = SerialConfig {
+            dtr: Some(true),
+            ..SerialConfig::default()
+        };
+        assert_eq!(base.label(), "8N1");
+        for label in ["8N1", "7E1", "8O2", "5N2"] {
+            let config = base.clone().with_label(label).unwrap();
+            assert_eq!(config.label(), label);
+            // framing only
+            assert_eq!(config.dtr, Some(true));
+        }
+        assert_eq!(base.clone().with_label(" 7e2 ").unwrap().label(), "7E2");
+        for bad in ["", "8N", "8N11", "9N1", "4N1", "8X1", "8N3"] {
+            assert!(base.clone().with_label(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn validates_before_opening() {
+        assert!(SerialConfig::default().validate().is_ok());
+        let invalid = [
+            SerialConfig {
+                data_bits: 9,
+                ..SerialConfig::default()
+            },
+            SerialConfig {
+                read_timeout_ms: 0,
+                ..SerialConfig::default()
+            },
+            SerialConfig {
+                read_timeout_ms: MAX_READ_TIMEOUT_MS + 1,
+                ..SerialConfig::default()
+            },
+            SerialConfig {
+                flow_control: FlowControl::Hardware,
+                rts: Some(false),
+                ..SerialConfig::default()
+            },
+        ];
+        for config in invalid {
+            let err = config.validate().unwrap_err();
+            // the port isn't touched
+            assert_eq!(config.open("/dev/lb-missing", 115_200).err(), Some(err));
+        }
+        assert!(SerialConfig::default()
+            .open("/dev/lb-missing", 115_200)
+            .is_err());
+    }
+
+    #[test]
+    fn missing_fields_take_the_defaults() {
+        let config: SerialConfig =
+            serde_json::from_str(r#"{"parity":"even","flowControl":"software"}"#).unwrap();
+        assert_eq!(
+            config,
+            SerialConfig {
+                parity: Parity::Even,
+                flow_control: FlowControl::Software,
+                ..SerialConfig::default()
+            }
+        );
+        assert_eq!(
+            config.read_timeout(),
+            Duration::from_millis(DEFAULT_READ_TIMEOUT_MS)
+        );
+    }
+}
@@ -0,0 +1,410 @@
+// Software load bank (hardware-free development).
+//
+// `SimDevice` models the firmware side of the protocol:
+// - sends HELLO (byte4=0xFF) periodically until it receives an ACK
+// - answers the ACK with CONFIRM (byte4=0x00) and goes mostly silent
+// - answers every paired frame with a status frame and applies its contactor
+//   mask (a poll is a frame re-asserting the current mask, mask 0 opens all)
+// - opens its contactors after WATCHDOG_MS without a frame from the host (the
+//   proposed firmware watchdog, see lb_protocol)
+//
+// `connect_sim` runs a `SimDevice` on the far end of an in-memory transport,
+// so the runtime worker talks to it exactly like a real port.
+
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+#[cfg(feature = "gui")]
+use tauri::State;
+
+use crate::lb_protocol::{
+    Frame, FrameDecoder, FrameFields, DEFAULT_VERSION, HANDSHAKE_CONFIRM_VALUE,
+    HANDSHAKE_HELLO_VALUE, WATCHDOG_MS,
+};
+use crate::lb_transport::{memory_pair, MemoryTransport};
+
+pub const SIM_PORT_NAME: &str = "sim://loadbank";
+
+const DEFAULT_HELLO_EVERY_MS: u64 = 50;
+const DEVICE_TICK_MS: u64 = 2;
+const DEFAULT_SIM_BANK_POWER: u16 = 4000;
+const DEFAULT_SIM_BANK_NO: u8 = 1;
+
+// -----------------------------------------------------------------------------
+// Fault injection
+// -----------------------------------------------------------------------------
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SimFaults {
+    pub err_contactors: u16,
+    pub err_fans: u16,
+    pub err_thermals: u16,
+    pub other_errors: u8,
+    /// Corrupt the CRC of every Nth outgoing frame (0 = never).
+    pub corrupt_crc_every: u32,
+    /// Delay applied to every reply.
+    pub latency_ms: u64,
+    /// Port reads/writes fail as if the cable was pulled.
+    pub disconnected: bool,
+    /// Device stays attached but stops answering.
+    pub silent: bool,
+    /// Announce this protocol version instead of the default (firmware mismatch).
+    pub protocol_version: Option<u8>,
+}
+
+// -----------------------------------------------------------------------------
+// Device model
+// -----------------------------------------------------------------------------
+
+pub struct SimDevice {
+    status: FrameFields,
+    paired: bool,
+    faults: SimFaults,
+
+    rx: FrameDecoder,
+    pending: VecDeque<(Instant, Vec<u8>)>,
+    out: VecDeque<u8>,
+
+    hello_every: Duration,
+    last_hello: Instant,
+    sent_frames: u64,
+    // host watchdog
+    last_host_frame: Instant,
+}
+
+impl Default for SimDevice {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SimDevice {
+    pub fn new() -> Self {
+        Self {
+            status: FrameFields {
+                version: DEFAULT_VERSION,
+                bank_power: DEFAULT_SIM_BANK_POWER,
+                bank_no: DEFAULT_SIM_BANK_NO,
+                ..FrameFields::default()
+            },
+            paired: false,
+            faults: SimFaults::default(),
+            rx: FrameDecoder::new(),
+            pending: VecDeque::new(),
+            out: VecDeque::new(),
+            hello_every: Duration::from_millis(DEFAULT_HELLO_EVERY_MS),
+            last_hello: Instant::now() - Duration::from_millis(DEFAULT_HELLO_EVERY_MS),
+            sent_frames: 0,
+            last_host_frame: Instant::now(),
+        }
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn faults(&self) -> &SimFaults {
+        &self.faults
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn set_faults(&mut self, faults: SimFaults) {
+        self.faults = faults;
+    }
+
+    #[cfg(any(test, feature = "gui"))]
+    pub fn is_paired(&self) -> bool {
+        self.paired
+    }
+
+    #[cfg(any(test, feature = "gui"))]
+    pub fn contactors_mask(&self) -> u16 {
+        self.status.contactors_mask
+    }
+
+    /// Power-cycle: contactors open, pairing lost, HELLO spam resumes.
+    pub fn reset(&mut self) {
+        self.paired = false;
+        self.status.contactors_mask = 0;
+        self.rx.clear();
+        self.pending.clear();
+        self.out.clear();
+    }
+
+    /// Bytes written by the host.
+    pub fn receive(&mut self, bytes: &[u8], now: Instant) {
+        if self.faults.disconnected {
+            return;
+        }
+        self.rx.push(bytes);
+        while let Some(frame) = self.rx.next_frame() {
+            self.on_frame(frame, now);
+        }
+    }
+
+    /// Advance timers (HELLO spam, delayed replies).
+    pub fn tick(&mut self, now: Instant) {
+        let alive = !self.faults.disconnected && !self.faults.silent;
+        if alive && !self.paired && now.duration_since(self.last_hello) >= self.hello_every {
+            self.last_hello = now;
+            self.queue_status(HANDSHAKE_HELLO_VALUE, now);
+        }
+        let starved =
+            now.duration_since(self.last_host_frame) >= Duration::from_millis(WATCHDOG_MS);
+        if alive && self.paired && starved && self.status.contactors_mask != 0 {
+            self.status.contactors_mask = 0;
+            self.queue_status(HANDSHAKE_CONFIRM_VALUE, now);
+        }
+
+        while let Some((due, _)) = self.pending.front() {
+            if *due > now {
+                break;
+            }
+            let (_, bytes) = self.pending.pop_front().unwrap();
+            self.out.extend(bytes);
+        }
+    }
+
+    /// Bytes the device has put on the wire since the last call.
+    pub fn take_output(&mut self) -> Vec<u8> {
+        self.out.drain(..).collect()
+    }
+
+    fn on_frame(&mut self, frame: Frame, now: Instant) {
+        let Ok(f) = frame.decode() else {
+            return;
+        };
+        self.last_host_frame = now;
+
+        if !self.paired {
+            // Only the ACK (byte4=0x00) pairs the device.
+            if frame.is_confirm() {
+                self.paired = true;
+                self.queue_status(HANDSHAKE_CONFIRM_VALUE, now);
+            }
+            return;
+        }
+
+        self.status.contactors_mask = f.contactors_mask;
+        self.queue_status(HANDSHAKE_CONFIRM_VALUE, now);
+    }
+
+    fn queue_status(&mut self, handshake: u8, now: Instant) {
+        if self.faults.silent {
+            return;
+        }
+
+        let f = FrameFields {
+            version: self.faults.protocol_version.unwrap_or(self.status.version),
+            handshake,
+            err_contactors: self.faults.err_contactors,
+            err_fans: self.faults.err_fans,
+            err_thermals: self.faults.err_thermals,
+            other_errors: self.faults.other_errors,
+            ..self.status.clone()
+        };
+        let mut bytes = f.encode().as_bytes().to_vec();
+
+        self.sent_frames += 1;
+        let every = self.faults.corrupt_crc_every as u64;
+        if every > 0 && self.sent_frames.is_multiple_of(every) {
+            if let Some(crc) = bytes.last_mut() {
+                *crc ^= 0xA5;
+            }
+        }
+
+        let due = now + Duration::from_millis(self.faults.latency_ms);
+        self.pending.push_back((due, bytes));
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Device thread (far end of an in-memory pipe)
+// -----------------------------------------------------------------------------
+
+/// "Plug in" the simulated bank: spawns the device side and returns the host end.
+/// The device thread exits when the host end is dropped or `disconnected` is injected.
+pub fn connect_sim(
+    device: Arc<Mutex<SimDevice>>,
+    read_timeout: Duration,
+) -> Result<MemoryTransport, String> {
+    if device.lock().unwrap().faults.disconnected {
+        return Err("simulated load bank disconnected".into());
+    }
+
+    let (host, mut dev_end) = memory_pair(
+        (SIM_PORT_NAME, read_timeout),
+        ("sim-device", Duration::from_millis(DEVICE_TICK_MS)),
+    );
+
+    thread::spawn(move || {
+        let mut buf = [0u8; 256];
+        loop {
+            match dev_end.read(&mut buf) {
+                Ok(n) => device.lock().unwrap().receive(&buf[..n], Instant::now()),
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => {}
+                Err(_) => break, // host end dropped
+            }
+
+            let out = {
+                let mut dev = device.lock().unwrap();
+                if dev.faults.disconnected {
+                    eprintln!("[LB/SIM] unplugged");
+                    break;
+                }
+                dev.tick(Instant::now());
+                dev.take_output()
+            };
+            if !out.is_empty() && dev_end.write_all(&out).is_err() {
+                break;
+            }
+        }
+        // Link lost => firmware falls back to HELLO on the next connection.
+        device.lock().unwrap().reset();
+    });
+
+    Ok(host)
+}
+
+// -----------------------------------------------------------------------------
+// Shared state + commands
+// -----------------------------------------------------------------------------
+
+#[derive(Default)]
+pub struct LoadBankSimState {
+    pub device: Arc<Mutex<SimDevice>>,
+}
+
+#[cfg(feature = "gui")]
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SimSnapshot {
+    pub paired: bool,
+    pub contactors_mask: u16,
+    pub faults: SimFaults,
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_sim_set_faults(state: State<LoadBankSimState>, faults: SimFaults) -> SimSnapshot {
+    let mut dev = state.device.lock().unwrap();
+    eprintln!("[LB/SIM] faults: {:?}", faults);
+    dev.set_faults(faults);
+    SimSnapshot {
+        paired: dev.is_paired(),
+        contactors_mask: dev.contactors_mask(),
+        faults: dev.faults().clone(),
+    }
+}
+
+/// Simulated power-cycle (device drops pairing and starts sending HELLO again).
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_sim_reset(state: State<LoadBankSimState>) {
+    eprintln!("[LB/SIM] reset");
+    state.device.lock().unwrap().reset();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lb_link::{contactors_command, handshake};
+
+    fn ack() -> Frame {
+        FrameFields::command_template_for(DEFAULT_VERSION).encode()
+    }
+
+    fn mask_frame(mask: u16) -> Frame {
+        contactors_command(None, Some(DEFAULT_VERSION), mask)
+    }
+
+    fn statuses(dev: &mut SimDevice, now: Instant) -> Vec<FrameFields> {
+        dev.tick(now);
+        let mut decoder = FrameDecoder::new();
+        decoder.push(&dev.take_output());
+        std::iter::from_fn(|| decoder.next_frame())
+            .map(|f| f.decode().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn applies_the_mask_of_every_paired_frame() {
+        let t0 = Instant::now();
+        let mut dev = SimDevice::new();
+        // unpaired: the ACK only pairs
+        dev.receive(ack().as_bytes(), t0);
+        assert!(dev.is_paired());
+        assert_eq!(statuses(&mut dev, t0).last().unwrap().contactors_mask, 0);
+
+        dev.receive(mask_frame(0x0005).as_bytes(), t0);
+        assert_eq!(dev.contactors_mask(), 0x0005);
+        assert_eq!(statuses(&mut dev, t0)[0].contactors_mask, 0x0005);
+
+        // mask 0 opens everything, even byte for byte the ACK template
+        assert_eq!(mask_frame(0), ack());
+        dev.receive(mask_frame(0).as_bytes(), t0);
+        assert_eq!(dev.contactors_mask(), 0);
+        assert_eq!(statuses(&mut dev, t0)[0].contactors_mask, 0);
+    }
+
+    #[test]
+    fn watchdog_opens_without_host_frames() {
+        let t0 = Instant::now();
+        let mut dev = SimDevice::new();
+        dev.receive(ack().as_bytes(), t0);
+        dev.receive(mask_frame(0x0003).as_bytes(), t0);
+
+        let fed = t0 + Duration::from_millis(WATCHDOG_MS / 2);
+        dev.receive(mask_frame(0x0003).as_bytes(), fed);
+        dev.tick(t0 + Duration::from_millis(WATCHDOG_MS));
+        assert_eq!(dev.contactors_mask(), 0x0003);
+
+        dev.tick(fed + Duration::from_millis(WATCHDOG_MS));
+        assert_eq!(dev.contactors_mask(), 0);
+    }
+
+    // next status frame reporting `mask`, within a second
+    fn wait_mask(p: &mut MemoryTransport, decoder: &mut FrameDecoder, mask: u16) -> FrameFields {
+        let deadline = Instant::now() + Duration::from_secs(1);
+        let mut buf = [0u8; 256];
+        while Instant::now() < deadline {
+            while let Some(frame) = decoder.next_frame() {
+                let f = frame.decode().unwrap();
+                if f.handshake == HANDSHAKE_CONFIRM_VALUE && f.contactors_mask == mask {
+                    return f;
+                }
+            }
+            match p.read(&mut buf) {
+                Ok(n) => decoder.push(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => {}
+                Err(e) => panic!("read: {e}"),
+            }
+        }
+        panic!("no status reporting 0x{mask:04X}");
+    }
+
+    #[test]
+    fn handshake_set_mask_status_over_connect_sim() {
+        let device = Arc::new(Mutex::new(SimDevice::new()));
+        let mut p = connect_sim(device.clone(), Duration::from_millis(10)).unwrap();
+
+        let (_, fields) = handshake(&mut p, |_| {}, |_| {}).unwrap();
+        assert!(device.lock().unwrap().is_paired());
+        assert_eq!(fields.bank_no, DEFAULT_SIM_BANK_NO);
+
+        let mut decoder = FrameDecoder::new();
+        let cmd = contactors_command(Some(&fields), Some(fields.version), 0x0003);
+        p.write_all(cmd.as_bytes()).unwrap();
+        let status = wait_mask(&mut p, &mut decoder, 0x0003);
+        assert_eq!(status.bank_power, DEFAULT_SIM_BANK_POWER);
+        assert_eq!(device.lock().unwrap().contactors_mask(), 0x0003);
+
+        let open = contactors_command(Some(&status), Some(status.version), 0);
+        p.write_all(open.as_bytes()).unwrap();
+        wait_mask(&mut p, &mut decoder, 0);
+        assert_eq!(device.lock().unwrap().contactors_mask(), 0);
+    }
+}
@@ -0,0 +1,367 @@
+// Byte transports for the load-bank runtime.
+//
+// All transports follow serial-port read semantics:
+// - `read` blocks for at most the configured read timeout
+// - "no data yet" is reported as `ErrorKind::TimedOut`
+// - any other error means the link is gone (the runtime drops + reconnects)
+// - `try_clone_writer` gives a second handle, so writes don't wait for a
+//   read blocked in another thread
+
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::mpsc,
+    time::Duration,
+};
+
+use crate::lb_serial::SerialConfig;
+
+pub const TCP_SCHEME: &str = "tcp://";
+
+const TCP_CONNECT_TIMEOUT_MS: u64 = 1500;
+
+pub trait Transport: Read + Write + Send {
+    /// Name reported in events (`portName`).
+    fn name(&self) -> &str;
+
+    /// Write handle usable while another thread reads.
+    fn try_clone_writer(&self) -> io::Result<Box<dyn Write + Send>>;
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "timed out")
+}
+
+// -----------------------------------------------------------------------------
+// Serial (USB / RS-485 adapters)
+// -----------------------------------------------------------------------------
+
+pub struct SerialTransport {
+    name: String,
+    port: Box<dyn serialport::SerialPort>,
+}
+
+impl SerialTransport {
+    pub fn open(port_name: &str, baud: u32, config: &SerialConfig) -> Result<Self, String> {
+        let port = config.open(port_name, baud)?;
+        Ok(Self {
+            name: port_name.to_string(),
+            port,
+        })
+    }
+}
+
+impl Transport for SerialTransport {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn try_clone_writer(&self) -> io::Result<Box<dyn Write + Send>> {
+        Ok(Box::new(self.port.try_clone()?))
+    }
+}
+
+impl Read for SerialTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.port.read(buf)
+    }
+}
+
+impl Write for SerialTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.port.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.port.flush()
+    }
+}
+
+// -----------------------------------------------------------------------------
+// TCP (serial-to-Ethernet converters, raw socket mode)
+// -----------------------------------------------------------------------------
+
+pub struct TcpTransport {
+    name: String,
+    stream: TcpStream,
+}
+
+impl TcpTransport {
+    /// `addr` is `host:port` (without the `tcp://` scheme).
+    pub fn connect(addr: &str, read_timeout: Duration) -> Result<Self, String> {
+        let sock_addr = addr
+            .to_socket_addrs()
+            .map_err(|e| format!("resolve {addr}: {e}"))?
+            .next()
+            .ok_or_else(|| format!("resolve {addr}: no address"))?;
+
+        let stream =
+            TcpStream::connect_timeout(&sock_addr, Duration::from_millis(TCP_CONNECT_TIMEOUT_MS))
+                .map_err(|e| e.to_string())?;
+        stream
+            .set_read_timeout(Some(read_timeout))
+            .map_err(|e| e.to_string())?;
+        let _ = stream.set_nodelay(true);
+
+        Ok(Self {
+            name: format!("{TCP_SCHEME}{addr}"),
+            stream,
+        })
+    }
+}
+
+impl Transport for TcpTransport {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn try_clone_writer(&self) -> io::Result<Box<dyn Write + Send>> {
+        Ok(Box::new(self.stream.try_clone()?))
+    }
+}
+
+impl Read for TcpTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.stream.read(buf) {
+            // A zero-length read on a socket is EOF, not "no data".
+            Ok(0) if !buf.is_empty() => Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "connection closed by peer",
+            )),
+            Ok(n) => Ok(n),
+            // Unix reports socket read timeouts as WouldBlock.
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Err(timed_out()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Write for TcpTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+// -----------------------------------------------------------------------------
+// In-memory pipe
+// -----------------------------------------------------------------------------
+
+pub struct MemoryTransport {
+    name: String,
+    tx: mpsc::Sender<Vec<u8>>,
+    rx: mpsc::Receiver<Vec<u8>>,
+    pending: VecDeque<u8>,
+    read_timeout: Duration,
+}
+
+/// Two connected ends: bytes written on one are read on the other.
+/// Dropping either end makes the other fail with `BrokenPipe`.
+pub fn memory_pair(
+    a: (&str, Duration),
+    b: (&str, Duration),
+) -> (MemoryTransport, MemoryTransport) {
+    let (tx_ab, rx_ab) = mpsc::channel();
+    let (tx_ba, rx_ba) = mpsc::channel();
+    (
+        MemoryTransport {
+            name: a.0.to_string(),
+            tx: tx_ab,
+            rx: rx_ba,
+            pending: VecDeque::new(),
+            read_timeout: a.1,
+        },
+        MemoryTransport {
+            name: b.0.to_string(),
+            tx: tx_ba,
+            rx: rx_ab,
+            pending: VecDeque::new(),
+            read_timeout: b.1,
+        },
+    )
+}
+
+impl Transport for MemoryTransport {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn try_clone_writer(&self) -> io::Result<Box<dyn Write + Send>> {
+        Ok(Box::new(MemoryWriter {
+            tx: self.tx.clone(),
+        }))
+    }
+}
+
+impl Read for MemoryTransport {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pending.is_empty() {
+            match self.rx.recv_timeout(self.read_timeout) {
+                Ok(chunk) => self.pending.extend(chunk),
+                Err(mpsc::RecvTimeoutError::Timeout) => return Err(timed_out()),
+                Err(mpsc::RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::new(io::ErrorKind::BrokenPipe, "peer dropped"))
+                }
+            }
+        }
+        let n = buf.len().min(self.pending.len());
+        for (dst, src) in buf.iter_mut().zip(self.pending.drain(..n)) {
+            *dst = src;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for MemoryTransport {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        send_chunk(&self.tx, buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Write half of a `MemoryTransport`; the peer sees the pipe as open until
+/// both are dropped.
+pub struct MemoryWriter {
+    tx: mpsc::Sender<Vec<u8>>,
+}
+
+impl Write for MemoryWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        send_chunk(&self.tx, buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn send_chunk(tx: &mpsc::Sender<Vec<u8>>, buf: &[u8]) -> io::Result<usize> {
+    tx.send(buf.to_vec())
+        .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "peer dropped"))?;
+    Ok(buf.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lb_protocol::{FrameDecoder, FrameFields, DEFAULT_VERSION};
+    use std::{net::TcpListener, thread};
+
+    const READ_TIMEOUT: Duration = Duration::from_millis(50);
+
+    fn frame(mask: u16) -> Vec<u8> {
+        FrameFields {
+            contactors_mask: mask,
+            ..FrameFields::command_template_for(DEFAULT_VERSION)
+        }
+        .encode()
+        .as_bytes()
+        .to_vec()
+    }
+
+    // a listener and the client side connected to it
+    fn tcp_pair() -> (TcpTransport, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let client = TcpTransport::connect(&addr, READ_TIMEOUT).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        assert_eq!(client.name(), format!("{TCP_SCHEME}{addr}"));
+        (client, server)
+    }
+
+    // reads until `n` frames decoded (or the peer is gone)
+    fn read_frames(t: &mut impl Read, n: usize) -> Vec<u16> {
+        let mut decoder = FrameDecoder::new();
+        let mut masks = Vec::new();
+        let mut buf = [0u8; 64];
+        while masks.len() < n {
+            match t.read(&mut buf) {
+                Ok(k) => decoder.push(&buf[..k]),
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => continue,
+                Err(e) => panic!("read: {e}"),
+            }
+            masks.extend(
+                std::iter::from_fn(|| decoder.next_frame())
+                    .map(|f| f.decode().unwrap().contactors_mask),
+            );
+        }
+        masks
+    }
+
+    #[test]
+    fn tcp_frames_survive_segmentation() {
+        let (mut client, mut server) = tcp_pair();
+        let writer = thread::spawn(move || {
+            let a = frame(0x0001);
+            let b = frame(0x0002);
+            // split mid-frame, then garbage and a whole frame in one segment
+            server.write_all(&a[..6]).unwrap();
+            thread::sleep(READ_TIMEOUT * 2);
+            server.write_all(&a[6..]).unwrap();
+            server.write_all(&[0x00, 0x13]).unwrap();
+            server.write_all(&b).unwrap();
+            server
+        });
+        assert_eq!(read_frames(&mut client, 2), [0x0001, 0x0002]);
+        let mut server = writer.join().unwrap();
+
+        // the cloned writer works while the transport is borrowed for reading
+        let mut w = client.try_clone_writer().unwrap();
+        w.write_all(&frame(0x0004)).unwrap();
+        server.set_read_timeout(Some(READ_TIMEOUT)).unwrap();
+        assert_eq!(read_frames(&mut server, 1), [0x0004]);
+    }
+
+    #[test]
+    fn tcp_idle_times_out_and_eof_aborts() {
+        let (mut client, server) = tcp_pair();
+        let mut buf = [0u8; 16];
+        let err = client.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+        drop(server);
+        let err = client.read(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionAborted);
+    }
+
+    #[test]
+    fn tcp_connect_errors() {
+        assert!(TcpTransport::connect("no port", READ_TIMEOUT).is_err());
+        // bound but not listening: refused
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        assert!(TcpTransport::connect(&addr.to_string(), READ_TIMEOUT).is_err());
+    }
+
+    #[test]
+    fn memory_pair_pipes_both_ways() {
+        let (mut a, mut b) = memory_pair(("a", READ_TIMEOUT), ("b", READ_TIMEOUT));
+        a.write_all(&frame(0x0010)).unwrap();
+        assert_eq!(read_frames(&mut b, 1), [0x0010]);
+        b.try_clone_writer()
+            .unwrap()
+            .write_all(&frame(0x0020))
+            .unwrap();
+        assert_eq!(read_frames(&mut a, 1), [0x0020]);
+
+        let mut buf = [0u8; 16];
+        assert_eq!(
+            a.read(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::TimedOut
+        );
+        drop(b);
+        assert_eq!(
+            a.read(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::BrokenPipe
+        );
+    }
+}
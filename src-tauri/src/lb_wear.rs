@@ -0,0 +1,428 @@
+// Contactor wear counters (predictive maintenance).
+//
+// The worker feeds every contactor mask a real bank (serial or TCP, not the
+// simulator or a replay) reports into a `WearTracker`: a rising edge of a bit is one switch cycle, the time between
+// status frames with the bit set is energised time, and U2²/R of the wired
+// branch over that time is the energy it dissipated. U2 is the conventional
+// load voltage the closed mask settles at for the process of the last current
+// step (`load_model::mask_u2`, MMA until a step names one): the bank doesn't
+// measure it, and the safety `u2MaxV` would overstate most loads.
+// Deltas are added to `lb_contactor_wear` in the event-log database every few
+// seconds and whenever the port is dropped, keyed by bank id and the bank
+// number the firmware reports, so two banks that both report the same number
+// keep their own counters.
+//
+// `lb_maintenance_report` compares the counters against `WearThresholds`
+// (kept in `lb_meta`) so contactors can be replaced before they fail.
+
+use chrono::Local;
+#[cfg(feature = "gui")]
+use chrono::{DateTime, TimeZone};
+#[cfg(feature = "gui")]
+use rusqlite::OptionalExtension;
+use rusqlite::{params, Connection};
+#[cfg(feature = "gui")]
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+#[cfg(feature = "gui")]
+use tauri::{AppHandle, State};
+
+use crate::lb_eventlog::EventLogSink;
+#[cfg(feature = "gui")]
+use crate::lb_eventlog::LoadBankEventLogState;
+use crate::load_model::{mask_u2, Process, LB_BRANCHES};
+
+// created with the event-log tables when the database is opened
+pub const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS lb_contactor_wear (
+    bank_id    TEXT NOT NULL,
+    bank_no    INTEGER NOT NULL,
+    contactor  INTEGER NOT NULL,
+    cycles     INTEGER NOT NULL DEFAULT 0,
+    on_ms      INTEGER NOT NULL DEFAULT 0,
+    energy_j   REAL NOT NULL DEFAULT 0,
+    since_ms   INTEGER NOT NULL,
+    updated_ms INTEGER NOT NULL,
+    PRIMARY KEY (bank_id, bank_no, contactor)
+);
+";
+
+#[cfg(feature = "gui")]
+const THRESHOLDS_KEY: &str = "wear_thresholds";
+
+// -----------------------------------------------------------------------------
+// Tracker (one per worker)
+// -----------------------------------------------------------------------------
+
+#[derive(Clone, Copy, Debug, Default)]
+struct WearDelta {
+    cycles: u64,
+    on_ms: f64,
+    energy_j: f64,
+}
+
+// (bank_no, mask bit) -> not yet persisted
+type Deltas = BTreeMap<(u8, u8), WearDelta>;
+
+fn merge(into: &mut Deltas, from: Deltas) {
+    for (key, d) in from {
+        let e = into.entry(key).or_default();
+        e.cycles += d.cycles;
+        e.on_ms += d.on_ms;
+        e.energy_j += d.energy_j;
+    }
+}
+
+#[derive(Default)]
+pub struct WearTracker {
+    last: Option<(u8, u16, Instant)>,
+    pending: Deltas,
+    // deltas of a flush that failed on the writer thread, retried by the next
+    failed: Arc<Mutex<Deltas>>,
+}
+
+fn set_bits(mask: u16) -> impl Iterator<Item = u8> {
+    (0..16u8).filter(move |b| mask & (1 << b) != 0)
+}
+
+fn branch_ohm(bit: u8) -> Option<f64> {
+    LB_BRANCHES
+        .iter()
+        .find(|b| b.mask_bit == 1 << bit)
+        .map(|b| b.ohm)
+}
+
+impl WearTracker {
+    /// Call with every status frame. Only transitions seen on the wire count
+    /// as cycles: the first frame after a (re)connect just sets the baseline.
+    pub fn observe(&mut self, bank_no: u8, mask: u16, at: Instant, process: Process) {
+        if let Some((last_bank, last_mask, last_at)) = self.last {
+            if last_bank == bank_no {
+                let dt_ms = at.saturating_duration_since(last_at).as_secs_f64() * 1000.0;
+                let u2_v = mask_u2(process, last_mask).unwrap_or(0.0);
+                for bit in set_bits(last_mask) {
+                    let d = self.pending.entry((bank_no, bit)).or_default();
+                    d.on_ms += dt_ms;
+                    if let Some(ohm) = branch_ohm(bit) {
+                        d.energy_j += u2_v * u2_v / ohm * dt_ms / 1000.0;
+                    }
+                }
+                for bit in set_bits(mask & !last_mask) {
+                    self.pending.entry((bank_no, bit)).or_default().cycles += 1;
+                }
+            }
+        }
+        self.last = Some((bank_no, mask, at));
+    }
+
+    /// Link lost: the time until the next frame is unknown, don't count it.
+    pub fn disconnect(&mut self) {
+        self.last = None;
+    }
+
+    /// Queues the pending deltas for the database (kept for the next try on error).
+    pub fn flush(&mut self, bank_id: &str, events: &EventLogSink) {
+        let failed = std::mem::take(&mut *self.failed.lock().unwrap());
+        merge(&mut self.pending, failed);
+        if self.pending.is_empty() {
+            return;
+        }
+        let deltas = std::mem::take(&mut self.pending);
+        let failed = self.failed.clone();
+        let bank_id = bank_id.to_string();
+        events.with_conn(move |conn| {
+            let res = conn
+                .and_then(|conn| write_deltas(conn, &bank_id, &deltas).map_err(|e| e.to_string()));
+            if let Err(e) = res {
+                eprintln!("[LB/WEAR] flush failed: {e}");
+                merge(&mut failed.lock().unwrap(), deltas);
+            }
+        });
+    }
+}
+
+fn write_deltas(conn: &Connection, bank_id: &str, deltas: &Deltas) -> rusqlite::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    let now = Local::now().timestamp_millis();
+    for (&(bank_no, bit), d) in deltas {
+        tx.execute(
+            "INSERT INTO lb_contactor_wear
+                (bank_id, bank_no, contactor, cycles, on_ms, energy_j, since_ms, updated_ms)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?7)
+             ON CONFLICT (bank_id, bank_no, contactor) DO UPDATE SET
+                cycles = cycles + excluded.cycles,
+                on_ms = on_ms + excluded.on_ms,
+                energy_j = energy_j + excluded.energy_j,
+                updated_ms = excluded.updated_ms",
+            params![
+                bank_id,
+                bank_no,
+                bit,
+                d.cycles as i64,
+                d.on_ms.round() as i64,
+                d.energy_j,
+                now
+            ],
+        )?;
+    }
+    tx.commit()
+}
+
+// -----------------------------------------------------------------------------
+// Thresholds + report
+// -----------------------------------------------------------------------------
+
+/// Wear limits per contactor; `None` => not checked. Defaults are generic
+/// placeholders, set them from the contactor datasheet.
+#[cfg(feature = "gui")]
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WearThresholds {
+    pub max_cycles: Option<u64>,
+    pub max_on_hours: Option<f64>,
+    pub max_energy_kwh: Option<f64>,
+    /// Share of a limit from which a contactor is reported as due (0..1].
+    pub due_fraction: f64,
+}
+
+#[cfg(feature = "gui")]
+impl Default for WearThresholds {
+    fn default() -> Self {
+        Self {
+            max_cycles: Some(100_000),
+            max_on_hours: Some(10_000.0),
+            max_energy_kwh: None,
+            due_fraction: 0.8,
+        }
+    }
+}
+
+#[cfg(feature = "gui")]
+impl WearThresholds {
+    fn validate(&self) -> Result<(), String> {
+        if !(self.due_fraction > 0.0 && self.due_fraction <= 1.0) {
+            return Err("dueFraction must be in (0, 1]".into());
+        }
+        if self.max_cycles == Some(0) {
+            return Err("maxCycles must be > 0".into());
+        }
+        for (name, v) in [
+            ("maxOnHours", self.max_on_hours),
+            ("maxEnergyKwh", self.max_energy_kwh),
+        ] {
+            if v.is_some_and(|v| !v.is_finite() || v <= 0.0) {
+                return Err(format!("{name} must be > 0"));
+            }
+        }
+        Ok(())
+    }
+
+    /// Highest used share over the configured limits.
+    fn wear(&self, cycles: u64, on_hours: f64, energy_kwh: f64) -> f64 {
+        [
+            self.max_cycles.map(|m| cycles as f64 / m as f64),
+            self.max_on_hours.map(|m| on_hours / m),
+            self.max_energy_kwh.map(|m| energy_kwh / m),
+        ]
+        .into_iter()
+        .flatten()
+        .fold(0.0, f64::max)
+    }
+}
+
+#[cfg(feature = "gui")]
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum WearStatus {
+    Ok,
+    Due,
+    Replace,
+}
+
+#[cfg(feature = "gui")]
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ContactorWear {
+    pub bank_id: String,
+    pub bank_no: u8,
+    /// Mask bit (0 => C1).
+    pub bit: u8,
+    pub contactor: String,
+    pub branch: Option<&'static str>,
+    pub cycles: u64,
+    pub on_hours: f64,
+    pub energy_kwh: f64,
+    /// First counted cycle, or the last reset.
+    pub since: DateTime<Local>,
+    pub updated: DateTime<Local>,
+    /// Highest used share of a configured limit (1.0 = limit reached).
+    pub wear: f64,
+    pub status: WearStatus,
+}
+
+#[cfg(feature = "gui")]
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MaintenanceReport {
+    pub generated: DateTime<Local>,
+    pub thresholds: WearThresholds,
+    /// Most worn first.
+    pub contactors: Vec<ContactorWear>,
+    pub due: usize,
+    pub replace: usize,
+}
+
+#[cfg(feature = "gui")]
+fn local_ms(ms: i64) -> DateTime<Local> {
+    Local.timestamp_millis_opt(ms).single().unwrap_or_default()
+}
+
+#[cfg(feature = "gui")]
+fn load_thresholds(conn: &Connection) -> rusqlite::Result<WearThresholds> {
+    let json: Option<String> = conn
+        .query_row(
+            "SELECT value FROM lb_meta WHERE key = ?1",
+            params![THRESHOLDS_KEY],
+            |r| r.get(0),
+        )
+        .optional()?;
+    Ok(json
+        .and_then(|j| serde_json::from_str(&j).ok())
+        .unwrap_or_default())
+}
+
+#[cfg(feature = "gui")]
+fn build_report(
+    conn: &Connection,
+    bank_id: Option<&str>,
+    bank_no: Option<u8>,
+) -> rusqlite::Result<MaintenanceReport> {
+    let thresholds = load_thresholds(conn)?;
+    let mut stmt = conn.prepare(
+        "SELECT bank_id, bank_no, contactor, cycles, on_ms, energy_j, since_ms, updated_ms
+         FROM lb_contactor_wear
+         WHERE (?1 IS NULL OR bank_id = ?1) AND (?2 IS NULL OR bank_no = ?2)",
+    )?;
+    let rows = stmt.query_map(params![bank_id, bank_no], |r| {
+        let bank_id: String = r.get(0)?;
+        let bank_no: u8 = r.get(1)?;
+        let bit: u8 = r.get(2)?;
+        let cycles = r.get::<_, i64>(3)?.max(0) as u64;
+        let on_hours = r.get::<_, i64>(4)? as f64 / 3_600_000.0;
+        let energy_kwh = r.get::<_, f64>(5)? / 3_600_000.0;
+        let wear = thresholds.wear(cycles, on_hours, energy_kwh);
+        let status = if wear >= 1.0 {
+            WearStatus::Replace
+        } else if wear >= thresholds.due_fraction {
+            WearStatus::Due
+        } else {
+            WearStatus::Ok
+        };
+        Ok(ContactorWear {
+            bank_id,
+            bank_no,
+            bit,
+            contactor: format!("C{}", bit + 1),
+            branch: LB_BRANCHES
+                .iter()
+                .find(|b| b.mask_bit == 1 << bit)
+                .map(|b| b.id),
+            cycles,
+            on_hours,
+            energy_kwh,
+            since: local_ms(r.get(6)?),
+            updated: local_ms(r.get(7)?),
+            wear,
+            status,
+        })
+    })?;
+    let mut contactors = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+    contactors.sort_by(|a, b| b.wear.total_cmp(&a.wear));
+
+    let count = |s: WearStatus| contactors.iter().filter(|c| c.status == s).count();
+    Ok(MaintenanceReport {
+        generated: Local::now(),
+        due: count(WearStatus::Due),
+        replace: count(WearStatus::Replace),
+        thresholds,
+        contactors,
+    })
+}
+
+// -----------------------------------------------------------------------------
+// Commands
+// -----------------------------------------------------------------------------
+
+/// Wear per contactor of the matching banks (all when both filters are None).
+/// Counters are flushed by the runtime every few seconds, so the last seconds
+/// of activity may be missing.
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_maintenance_report(
+    app: AppHandle,
+    state: State<LoadBankEventLogState>,
+    bank_id: Option<String>,
+    bank_no: Option<u8>,
+) -> Result<MaintenanceReport, String> {
+    state.with_conn(&app, |conn| build_report(conn, bank_id.as_deref(), bank_no))
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_get_wear_thresholds(
+    app: AppHandle,
+    state: State<LoadBankEventLogState>,
+) -> Result<WearThresholds, String> {
+    state.with_conn(&app, load_thresholds)
+}
+
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_set_wear_thresholds(
+    app: AppHandle,
+    state: State<LoadBankEventLogState>,
+    thresholds: WearThresholds,
+) -> Result<WearThresholds, String> {
+    thresholds.validate()?;
+    let json = serde_json::to_string(&thresholds).map_err(|e| e.to_string())?;
+    state.with_conn(&app, |conn| {
+        conn.execute(
+            "INSERT INTO lb_meta (key, value) VALUES (?1, ?2)
+             ON CONFLICT (key) DO UPDATE SET value = excluded.value",
+            params![THRESHOLDS_KEY, json],
+        )
+    })?;
+    eprintln!("[LB/WEAR] thresholds: {:?}", thresholds);
+    Ok(thresholds)
+}
+
+/// Restarts the counters of a replaced contactor (`bit` 0 => C1).
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_reset_contactor_wear(
+    app: AppHandle,
+    state: State<LoadBankEventLogState>,
+    bank_id: String,
+    bank_no: u8,
+    bit: u8,
+) -> Result<(), String> {
+    if bit >= 16 {
+        return Err(format!("no contactor bit {bit}"));
+    }
+    let n = state.with_conn(&app, |conn| {
+        conn.execute(
+            "DELETE FROM lb_contactor_wear
+             WHERE bank_id = ?1 AND bank_no = ?2 AND contactor = ?3",
+            params![bank_id, bank_no, bit],
+        )
+    })?;
+    eprintln!(
+        "[LB/WEAR] {bank_id} bank {bank_no} C{}: counters reset ({n} row)",
+        bit + 1
+    );
+    Ok(())
+}
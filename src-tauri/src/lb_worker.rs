@@ -0,0 +1,2613 @@
+// Load-bank worker: one task per bank, owning its port.
+//
+// Connects (AUTO scan, fixed serial port, TCP, simulator or replay) and pairs,
+// polls and keeps the link alive, puts every contactor command through the
+// safety interlocks, the duty-cycle budget and the emergency-stop latch, and
+// runs load profiles. The app runs one per bank id (lb_runtime), `lbctl` one
+// per command (lb_cli); whoever runs it provides a `WorkerHost` for the
+// events and the per-port settings kept outside the worker.
+
+use chrono::{DateTime, Local};
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    future::Future,
+    io::{self, Read, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use tokio::sync::{
+    mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    oneshot,
+};
+
+use crate::lb_budget::{BudgetSnapshot, DutyCycleBudget};
+use crate::lb_capture::{CaptureEvent, CaptureSink};
+use crate::lb_eventlog::{EventLogSink, LogEvent};
+use crate::lb_failsafe::{worker_scope, Failsafe, SharedWriter};
+use crate::lb_faults::{fault_edges, Fault, FaultEvent};
+use crate::lb_link::{
+    contactors_command, handshake, list_ports_detailed, open_transport, HandshakeError, LinkState,
+    LoadBankStatus, SerialPortInfo,
+};
+use crate::lb_link_stats::{LinkStatsEvent, LinkStatsTracker};
+use crate::lb_protocol::{
+    is_supported, to_hex, DecoderStats, Frame, FrameDecoder, FrameFields, HOST_KEEPALIVE_MS,
+};
+use crate::lb_replay::REPLAY_SCHEME;
+use crate::lb_safety::{
+    check_mask, trip_reason, EstopEvent, EstopLatch, EstopState, Interlock, SafetyConfig,
+};
+use crate::lb_sequence::{
+    SequenceAction, SequenceCapture, SequenceCmd, SequenceProgress, SequenceState, Sequencer,
+    StepTarget,
+};
+use crate::lb_serial::SerialConfig;
+use crate::lb_sim::{SimDevice, SIM_PORT_NAME};
+use crate::lb_transport::{Transport, TCP_SCHEME};
+use crate::lb_wear::WearTracker;
+use crate::load_model::{explain_mask, rank_combos, Process, DEFAULT_MAX_REL_ERROR, RDP4000};
+
+// -----------------------------------------------------------------------------
+// Host
+// -----------------------------------------------------------------------------
+
+/// What the worker needs from whoever runs it.
+pub trait WorkerHost: Send + Sync + 'static {
+    fn emit(&self, event: WorkerEvent);
+
+    /// Device behind `sim` / `sim://...`.
+    fn sim_device(&self) -> Arc<Mutex<SimDevice>>;
+
+    /// Serial settings saved for `port_name`, if any.
+    fn saved_serial(&self, _port_name: &str) -> Option<SerialConfig> {
+        None
+    }
+
+    /// AUTO: ports worth a handshake, best first (blocking). Without a
+    /// discovery store every port is tried, in listing order.
+    fn rank_ports(
+        &self,
+        ports: &[SerialPortInfo],
+        _bank_id: &str,
+        _bank_no: Option<u8>,
+        _blind: bool,
+    ) -> Vec<String> {
+        ports.iter().map(|p| p.port_name.clone()).collect()
+    }
+
+    /// Adapter that paired with `bank_no`, for the next AUTO scan (blocking).
+    fn remember_port(&self, _port: &SerialPortInfo, _bank_id: &str, _bank_no: u8) {}
+}
+
+/// Everything the worker reports; the app emits each one as `name()`.
+#[derive(Serialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum WorkerEvent {
+    Rx(SerialRxChunk),
+    Tx(SerialTxChunk),
+    Health(LoadBankHealth),
+    Status(LoadBankStatus),
+    Fault(FaultEvent),
+    Budget(BudgetSnapshot),
+    LinkStats(LinkStatsEvent),
+    Interlock(InterlockEvent),
+    Estop(EstopEvent),
+    Sequence(SequenceProgress),
+    SequenceCapture(SequenceCapture),
+}
+
+impl WorkerEvent {
+    #[cfg(feature = "gui")]
+    pub fn name(&self) -> &'static str {
+        match self {
+            WorkerEvent::Rx(_) => "lb/rx",
+            WorkerEvent::Tx(_) => "lb/tx",
+            WorkerEvent::Health(_) => "lb/health",
+            WorkerEvent::Status(_) => "lb/status",
+            WorkerEvent::Fault(_) => "lb/fault",
+            WorkerEvent::Budget(_) => "lb/budget",
+            WorkerEvent::LinkStats(_) => "lb/link-stats",
+            WorkerEvent::Interlock(_) => "lb/interlock",
+            WorkerEvent::Estop(_) => "lb/estop",
+            WorkerEvent::Sequence(_) => "lb/sequence",
+            WorkerEvent::SequenceCapture(_) => "lb/sequence-capture",
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Commands
+// -----------------------------------------------------------------------------
+
+/// Command + the time it was queued (for the command-to-wire latency).
+type QueuedCmd = (Instant, RuntimeCmd);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RuntimeMode {
+    Auto,
+    Fixed { port_name: String },
+    /// In-process software load bank (`sim` / `sim://...`).
+    Simulated,
+    /// Serial-to-Ethernet converter (`tcp://host:port`).
+    Tcp { addr: String },
+    /// Recorded capture played back (`replay://<file>?speed=<x>`).
+    Replay { spec: String },
+}
+
+impl RuntimeMode {
+    pub fn from_port_name(port_name: &str) -> Self {
+        let port_name = port_name.trim();
+        if port_name.is_empty() {
+            RuntimeMode::Auto
+        } else if port_name == "sim" || port_name.starts_with("sim://") {
+            RuntimeMode::Simulated
+        } else if let Some(addr) = port_name.strip_prefix(TCP_SCHEME) {
+            RuntimeMode::Tcp {
+                addr: addr.to_string(),
+            }
+        } else if let Some(spec) = port_name.strip_prefix(REPLAY_SCHEME) {
+            RuntimeMode::Replay {
+                spec: spec.to_string(),
+            }
+        } else {
+            RuntimeMode::Fixed {
+                port_name: port_name.trim().to_string(),
+            }
+        }
+    }
+
+    pub fn key(&self) -> String {
+        match self {
+            RuntimeMode::Auto => "auto".to_string(),
+            RuntimeMode::Fixed { port_name } => format!("fixed:{port_name}"),
+            RuntimeMode::Simulated => "sim".to_string(),
+            RuntimeMode::Tcp { addr } => format!("tcp:{addr}"),
+            RuntimeMode::Replay { spec } => format!("replay:{spec}"),
+        }
+    }
+
+    /// Serial port or TCP converter: real contactors behind the link.
+    pub fn is_real_link(&self) -> bool {
+        !matches!(self, RuntimeMode::Simulated | RuntimeMode::Replay { .. })
+    }
+}
+
+// lbctl only sends a few of these
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+pub enum RuntimeCmd {
+    Stop,
+    SetMode(RuntimeMode),
+    SetPolling {
+        enabled: bool,
+        interval_ms: u64,
+    },
+    /// Sends raw bytes as-is (used by DevEchoPcbTest).
+    WriteRaw(Vec<u8>),
+    /// Production command: set contactors mask, backend builds frame.
+    SetContactors(u16),
+    /// Set contactors and report back once a status frame confirms the mask.
+    SetContactorsConfirmed {
+        mask: u16,
+        timeout: Duration,
+        retries: u32,
+        reply: oneshot::Sender<Result<LoadBankStatus, ContactorCmdError>>,
+    },
+    SetKeepalive(KeepaliveConfig),
+    SetSafety(SafetyConfig),
+    /// AUTO: try every port once, known adapters first.
+    ScanAllPorts,
+    /// From the port watcher (hot-plug).
+    PortRemoved(String),
+    PortAdded(String),
+    /// Latch already released by `lb_reset_estop`; the worker reports it.
+    ResetEstop,
+    /// Load-profile sequencer; answered with the resulting progress.
+    Sequence {
+        cmd: SequenceCmd,
+        reply: oneshot::Sender<Result<SequenceProgress, String>>,
+    },
+}
+
+struct PendingConfirm {
+    mask: u16,
+    timeout: Duration,
+    deadline: Instant,
+    attempts: u32,
+    max_attempts: u32,
+    // last status reporting another mask (a stale frame may still be in flight)
+    last_status: Option<LoadBankStatus>,
+    reply: oneshot::Sender<Result<LoadBankStatus, ContactorCmdError>>,
+}
+
+/// Liveness timing. While no frame arrives the worker probes the bank
+/// (a status request re-asserting the current mask), then reports `degraded`,
+/// then `offline` and reconnects.
+#[derive(Clone, Copy, Debug)]
+pub struct KeepaliveConfig {
+    pub enabled: bool,
+    pub probe_after: Duration,
+    pub degraded_after: Duration,
+    pub offline_after: Duration,
+    /// Write at least every HOST_KEEPALIVE_MS, for the proposed firmware
+    /// watchdog. Off until the firmware has it.
+    pub feed_watchdog: bool,
+}
+
+impl Default for KeepaliveConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            probe_after: Duration::from_millis(DEFAULT_PROBE_AFTER_MS),
+            degraded_after: Duration::from_millis(DEFAULT_DEGRADED_AFTER_MS),
+            offline_after: Duration::from_millis(DEFAULT_OFFLINE_AFTER_MS),
+            feed_watchdog: false,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Payloads
+// -----------------------------------------------------------------------------
+
+#[derive(Serialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct PollConfig {
+    pub enabled: bool,
+    pub interval_ms: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct HandshakeOutcome {
+    pub t: DateTime<Local>,
+    pub port_name: String,
+    pub ok: bool,
+    pub error: Option<String>,
+    pub protocol_version: Option<u8>,
+}
+
+/// Time from a command being queued to its bytes being written.
+#[derive(Serialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CmdLatency {
+    pub count: u64,
+    pub last_us: u64,
+    pub max_us: u64,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LoadBankHealth {
+    pub bank_id: String,
+    pub port_name: String,
+    pub online: bool,
+    pub link: LinkState,
+    pub last_seen_ms: u128,
+    pub reason: Option<String>,
+    /// Protocol version negotiated with the connected bank.
+    pub protocol_version: Option<u8>,
+    /// Version announced by a bank we refused to pair with.
+    pub unsupported_version: Option<u8>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "kind", rename_all = "camelCase", rename_all_fields = "camelCase")]
+pub enum ContactorCmdError {
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    NotRunning,
+    Offline {
+        reason: String,
+    },
+    /// A newer contactor command replaced this one before it was confirmed.
+    Superseded,
+    Timeout {
+        requested: u16,
+        attempts: u32,
+    },
+    /// Refused by the safety interlocks (nothing was sent).
+    Interlock {
+        interlock: Interlock,
+        message: String,
+    },
+    /// Every attempt timed out with the bank reporting a different mask.
+    Mismatch {
+        requested: u16,
+        reported: u16,
+        attempts: u32,
+        err_contactors: u16,
+        status: LoadBankStatus,
+    },
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SerialRxChunk {
+    pub bank_id: String,
+    pub port_name: String,
+    pub bytes: Vec<u8>,
+    pub hex: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SerialTxChunk {
+    pub bank_id: String,
+    pub port_name: String,
+    pub bytes: Vec<u8>,
+    pub hex: String,
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum InterlockAction {
+    /// Requested mask was not sent.
+    Refused,
+    /// Worker opened all contactors on its own.
+    AutoOpen,
+}
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct InterlockEvent {
+    pub bank_id: String,
+    pub port_name: String,
+    pub action: InterlockAction,
+    pub mask: u16,
+    pub interlock: Interlock,
+    pub message: String,
+}
+
+// -----------------------------------------------------------------------------
+// Worker constants
+// -----------------------------------------------------------------------------
+
+const DEFAULT_SCAN_EVERY_MS: u64 = 600;
+const DEFAULT_POLL_INTERVAL_MS: u64 = 400;
+// `WorkerHandle::stop` waits this long for the worker to release its port
+const STOP_WAIT_MS: u64 = 3000;
+// budget exhaustion is checked this often while any branch is closed
+const BUDGET_CHECK_MS: u64 = 100;
+
+// keepalive
+const DEFAULT_PROBE_AFTER_MS: u64 = 300;
+const DEFAULT_DEGRADED_AFTER_MS: u64 = 900;
+const DEFAULT_OFFLINE_AFTER_MS: u64 = 2500;
+
+// confirmed contactor commands
+pub const DEFAULT_CONFIRM_TIMEOUT_MS: u64 = 500;
+pub const DEFAULT_CONFIRM_RETRIES: u32 = 2;
+
+// auto-open is re-sent while the fault persists, at most this often
+const TRIP_RESEND_MS: u64 = 250;
+// emergency stop: all-open is re-sent this often until a status reports it
+const ESTOP_RESEND_MS: u64 = 100;
+// `lb/budget` while any branch is closed
+const BUDGET_EMIT_EVERY_MS: u64 = 1000;
+// contactor wear counters are written to the database at most this often
+const WEAR_FLUSH_EVERY_MS: u64 = 10_000;
+
+// -----------------------------------------------------------------------------
+// Port I/O
+// -----------------------------------------------------------------------------
+
+enum LinkRead {
+    Data(Vec<u8>),
+    /// Replay finished.
+    Eof,
+    Failed(String),
+}
+
+/// Adopted port: a reader thread forwards what it reads to the worker task,
+/// writes go straight out through a second handle.
+struct Link {
+    rx: UnboundedReceiver<LinkRead>,
+    writer: SharedWriter,
+    stop: Arc<AtomicBool>,
+    reader: Option<thread::JoinHandle<()>>,
+}
+
+impl Link {
+    fn start(mut port: Box<dyn Transport>) -> io::Result<Self> {
+        let writer = Arc::new(Mutex::new(port.try_clone_writer()?));
+        let (tx, rx) = unbounded_channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_reader = stop.clone();
+        let reader = thread::spawn(move || {
+            let mut buf = [0u8; 512];
+            while !stop_reader.load(Ordering::Relaxed) {
+                let read = match port.read(&mut buf) {
+                    Ok(0) => continue,
+                    Ok(n) => LinkRead::Data(buf[..n].to_vec()),
+                    Err(ref e) if e.kind() == io::ErrorKind::TimedOut => continue,
+                    Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof => LinkRead::Eof,
+                    Err(e) => LinkRead::Failed(e.to_string()),
+                };
+                let last = !matches!(read, LinkRead::Data(_));
+                if tx.send(read).is_err() || last {
+                    break;
+                }
+            }
+        });
+        Ok(Self {
+            rx,
+            writer,
+            stop,
+            reader: Some(reader),
+        })
+    }
+
+    fn write_all(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let mut w = self.writer.lock().unwrap();
+        w.write_all(bytes)?;
+        w.flush()
+    }
+
+    /// Stops the reader without waiting for it (that takes up to one read
+    /// timeout): join the handle off the async runtime before reopening.
+    fn release(mut self) -> Option<thread::JoinHandle<()>> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.reader.take()
+    }
+}
+
+impl Drop for Link {
+    // Waits for the reader to let go of the port (at most one read timeout),
+    // so the port can be reopened right away.
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(reader) = self.reader.take() {
+            let _ = reader.join();
+        }
+    }
+}
+
+/// Next read of the adopted port; never resolves without one.
+async fn next_read(port: &mut Option<Link>) -> LinkRead {
+    match port {
+        Some(link) => link
+            .rx
+            .recv()
+            .await
+            .unwrap_or_else(|| LinkRead::Failed("reader stopped".into())),
+        None => std::future::pending().await,
+    }
+}
+
+fn emit_rx(
+    host: &dyn WorkerHost,
+    capture: &CaptureSink,
+    bank_id: &str,
+    port_name: &str,
+    bytes: &[u8],
+) {
+    capture.record(bank_id, port_name, CaptureEvent::Rx { hex: to_hex(bytes) });
+    host.emit(WorkerEvent::Rx(SerialRxChunk {
+        bank_id: bank_id.to_string(),
+        port_name: port_name.to_string(),
+        bytes: bytes.to_vec(),
+        hex: to_hex(bytes),
+    }));
+    eprintln!("[LB/RX] {} {}", port_name, to_hex(bytes));
+}
+
+/// Opens + pairs ports. Both block (a handshake takes up to a few hundred ms
+/// per port), so attempts run on a blocking thread with a clone of this.
+#[derive(Clone)]
+struct Connector {
+    host: Arc<dyn WorkerHost>,
+    bank_id: String,
+    baud: u32,
+    // ports without a saved config (AUTO scans) are opened with this
+    serial: Option<SerialConfig>,
+    capture: CaptureSink,
+    claimed_ports: Arc<Mutex<HashSet<String>>>,
+}
+
+struct Adopted {
+    port: Box<dyn Transport>,
+    fields: FrameFields,
+    status: LoadBankStatus,
+}
+
+enum ConnectFailure {
+    InUse,
+    Open(String),
+    Handshake(HandshakeError),
+}
+
+/// Ports to try, in order, for one connection attempt.
+struct ConnectPlan {
+    connector: Connector,
+    // set_mode bumps the worker's epoch; older outcomes are dropped
+    epoch: u64,
+    ports: Vec<String>,
+    // AUTO: list and rank the ports first (enumeration blocks too)
+    discover: Option<Discover>,
+    // fixed endpoint: every failure is news; AUTO scan: only unknown firmware
+    report_all: bool,
+    // reader of the previous port, still letting go of it
+    released: Option<thread::JoinHandle<()>>,
+}
+
+struct Discover {
+    bank_no: Option<u8>,
+    blind: bool,
+}
+
+struct ConnectOutcome {
+    epoch: u64,
+    // AUTO: (ports listed, candidates, blind)
+    discovered: Option<(usize, usize, bool)>,
+    adopted: Option<Adopted>,
+    failures: Vec<(String, ConnectFailure)>,
+    // last handshake that failed without being reported (still shown by `lb_get_state`)
+    quiet_failure: Option<(String, String)>,
+    // every failed handshake, reported or not (link stats)
+    handshake_failures: u64,
+}
+
+impl Connector {
+    fn open(&self, port_name: &str) -> Result<Box<dyn Transport>, String> {
+        let serial = self
+            .host
+            .saved_serial(port_name)
+            .or_else(|| self.serial.clone())
+            .unwrap_or_default();
+        open_transport(port_name, self.baud, &serial, &self.host.sim_device())
+    }
+
+    fn handshake(
+        &self,
+        port_name: &str,
+        p: &mut Box<dyn Transport>,
+    ) -> Result<(FrameFields, LoadBankStatus), HandshakeError> {
+        let (frame, fields) = handshake(
+            p.as_mut(),
+            |chunk| emit_rx(&*self.host, &self.capture, &self.bank_id, port_name, chunk),
+            |ack| {
+                self.capture.record(
+                    &self.bank_id,
+                    port_name,
+                    CaptureEvent::Tx { hex: ack.to_hex() },
+                )
+            },
+        )?;
+        let status = LoadBankStatus::from_fields(&fields, &frame, &self.bank_id, port_name);
+        Ok((fields, status))
+    }
+
+    fn try_port(&self, port_name: &str) -> Result<Adopted, ConnectFailure> {
+        if self.claimed_ports.lock().unwrap().contains(port_name) {
+            return Err(ConnectFailure::InUse);
+        }
+        let mut port = self.open(port_name).map_err(ConnectFailure::Open)?;
+        eprintln!("[LB] opened {} @ {}", port_name, self.baud);
+        let (fields, status) = self
+            .handshake(port_name, &mut port)
+            .map_err(ConnectFailure::Handshake)?;
+        Ok(Adopted {
+            port,
+            fields,
+            status,
+        })
+    }
+}
+
+impl ConnectPlan {
+    /// Tries the ports in order until one pairs (blocking).
+    fn run(mut self) -> ConnectOutcome {
+        if let Some(reader) = self.released.take() {
+            let _ = reader.join();
+        }
+        let mut discovered = None;
+        if let Some(d) = &self.discover {
+            let c = &self.connector;
+            let infos = list_ports_detailed();
+            let ports = c.host.rank_ports(&infos, &c.bank_id, d.bank_no, d.blind);
+            discovered = Some((infos.len(), ports.len(), d.blind));
+            // ports held by other banks aren't even opened
+            let claimed = c.claimed_ports.lock().unwrap();
+            self.ports = ports.into_iter().filter(|p| !claimed.contains(p)).collect();
+        }
+
+        let mut failures = vec![];
+        let mut quiet_failure = None;
+        let mut handshake_failures = 0;
+        for port_name in self.ports {
+            let result = self.connector.try_port(&port_name);
+            if matches!(result, Err(ConnectFailure::Handshake(_))) {
+                handshake_failures += 1;
+            }
+            match result {
+                Ok(adopted) => {
+                    eprintln!("[LB] handshake OK on {}", port_name);
+                    return ConnectOutcome {
+                        epoch: self.epoch,
+                        discovered,
+                        adopted: Some(adopted),
+                        failures,
+                        quiet_failure,
+                        handshake_failures,
+                    };
+                }
+                // a board we can't talk to is worth telling about; silence isn't
+                Err(e @ ConnectFailure::Handshake(HandshakeError::UnsupportedVersion(_))) => {
+                    failures.push((port_name, e))
+                }
+                Err(e) if self.report_all => failures.push((port_name, e)),
+                Err(ConnectFailure::Handshake(e)) => {
+                    quiet_failure = Some((port_name, e.to_string()))
+                }
+                Err(_) => {}
+            }
+        }
+        ConnectOutcome {
+            epoch: self.epoch,
+            discovered,
+            adopted: None,
+            failures,
+            quiet_failure,
+            handshake_failures,
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Worker
+// -----------------------------------------------------------------------------
+
+/// Latest worker view, readable without going through the command channel.
+#[derive(Default)]
+pub struct BankShared {
+    pub port_name: Option<String>,
+    pub online: bool,
+    pub link: LinkState,
+    pub protocol_version: Option<u8>,
+    pub status: Option<LoadBankStatus>,
+    pub last_frame: Option<Instant>,
+    pub last_status: Option<Instant>,
+    pub poll: PollConfig,
+    pub decoder: DecoderStats,
+    pub last_handshake: Option<HandshakeOutcome>,
+    pub cmd_latency: CmdLatency,
+    pub link_stats: Option<LinkStatsEvent>,
+    // running load profile, or how the last one ended
+    pub sequence: Option<SequenceProgress>,
+    // None unless the emergency stop is latched
+    pub estop: Option<EstopEvent>,
+}
+
+/// What a worker starts with. `new` is a bank of its own (fresh budget and
+/// latch, nothing recorded or logged); the app hands in the pieces its
+/// registry keeps across restarts.
+pub struct WorkerConfig {
+    pub bank_id: String,
+    pub baud: u32,
+    // ports without a saved config (AUTO scans) are opened with this
+    pub serial: Option<SerialConfig>,
+    pub mode: RuntimeMode,
+    pub keepalive: KeepaliveConfig,
+    pub safety: SafetyConfig,
+    pub budget: Arc<Mutex<DutyCycleBudget>>,
+    pub estop: Arc<EstopLatch>,
+    // ports currently held by some worker (AUTO scans skip them)
+    pub claimed_ports: Arc<Mutex<HashSet<String>>>,
+    pub failsafe: Failsafe,
+    pub capture: CaptureSink,
+    pub events: EventLogSink,
+}
+
+impl WorkerConfig {
+    pub fn new(bank_id: &str, baud: u32, mode: RuntimeMode) -> Self {
+        Self {
+            bank_id: bank_id.to_string(),
+            baud,
+            serial: None,
+            mode,
+            keepalive: KeepaliveConfig::default(),
+            safety: SafetyConfig::default(),
+            budget: Arc::new(Mutex::new(DutyCycleBudget::new(RDP4000))),
+            estop: Arc::default(),
+            claimed_ports: Arc::default(),
+            failsafe: Failsafe::default(),
+            capture: CaptureSink::default(),
+            events: EventLogSink::default(),
+        }
+    }
+}
+
+/// Command side of a running worker.
+pub struct WorkerHandle {
+    tx: UnboundedSender<QueuedCmd>,
+    // disconnected once the worker task has exited (port released)
+    done: mpsc::Receiver<()>,
+    // read by the app (`lb_get_state`, the aggregate)
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub shared: Arc<Mutex<BankShared>>,
+}
+
+impl WorkerHandle {
+    pub fn send(&self, cmd: RuntimeCmd) -> Result<(), String> {
+        self.tx
+            .send((Instant::now(), cmd))
+            .map_err(|_| "runtime channel closed".to_string())
+    }
+
+    /// Stops the worker (it opens the contactors first) and waits for it to
+    /// release its port. Blocking: not from the runtime running the task.
+    pub fn stop(&self) {
+        let _ = self.send(RuntimeCmd::Stop);
+        let _ = self.done.recv_timeout(Duration::from_millis(STOP_WAIT_MS));
+    }
+}
+
+/// A worker for `cfg` and the task running it until `RuntimeCmd::Stop`;
+/// spawn the task on a tokio runtime.
+pub fn worker_task(
+    host: Arc<dyn WorkerHost>,
+    cfg: WorkerConfig,
+) -> (WorkerHandle, impl Future<Output = ()> + Send) {
+    let (tx, rx) = unbounded_channel::<QueuedCmd>();
+    let (done_tx, done) = mpsc::channel::<()>();
+    let shared = Arc::new(Mutex::new(BankShared::default()));
+    let w = Worker::new(host, cfg, shared.clone());
+    let task = worker_scope(
+        w.bank_id.clone(),
+        w.estop.clone(),
+        run_worker(w, rx, done_tx),
+    );
+    (WorkerHandle { tx, done, shared }, task)
+}
+
+/// Latched emergency stop, as seen by the worker.
+#[derive(Default)]
+struct EstopRun {
+    // None when latched before the runtime started
+    since: Option<Instant>,
+    last_send: Option<Instant>,
+    sends: u32,
+    // a status frame reported everything open since the last send
+    confirmed: bool,
+}
+
+struct Worker {
+    host: Arc<dyn WorkerHost>,
+    bank_id: String,
+    connector: Connector,
+
+    mode: RuntimeMode,
+    active_port: Option<String>,
+    port: Option<Link>,
+    // reader of the dropped port, joined by the next connection attempt
+    released: Option<thread::JoinHandle<()>>,
+    connect_epoch: u64,
+    // queue time of the command being handled (latency of its write)
+    cmd_at: Option<Instant>,
+
+    // RX parsing
+    decoder: FrameDecoder,
+    link_stats: LinkStatsTracker,
+
+    // health
+    online: bool,
+    link: LinkState,
+    last_seen: Instant,
+
+    // keepalive
+    keepalive: KeepaliveConfig,
+    last_probe: Instant,
+    // for `feed_watchdog`
+    last_tx: Instant,
+
+    // scan / retry
+    last_scan: Instant,
+    scan_every: Duration,
+    // AUTO discovery: one blind pass requested, last "nothing to try" note
+    blind_once: bool,
+    discovery_note: Option<String>,
+
+    // handshake
+    protocol_version: Option<u8>,
+    unsupported_version: Option<u8>,
+
+    // polling
+    poll_enabled: bool,
+    poll_interval: Duration,
+    last_poll: Instant,
+
+    // last known status (for building commands)
+    last_status_fields: Option<FrameFields>,
+    // last mask sent on this port, re-asserted by polls and probes
+    commanded_mask: Option<u16>,
+    // faults of the last status (kept across reconnects, so only real edges are emitted)
+    active_faults: Vec<Fault>,
+
+    // contactor command waiting for a confirming status frame
+    pending_confirm: Option<PendingConfirm>,
+    // load profile being run
+    sequence: Option<Sequencer>,
+
+    // safety interlocks
+    safety: SafetyConfig,
+    last_trip: Option<Instant>,
+    estop: Arc<EstopLatch>,
+    estop_run: Option<EstopRun>,
+    budget: Arc<Mutex<DutyCycleBudget>>,
+    last_budget_emit: Instant,
+
+    capture: CaptureSink,
+    replay_done: bool,
+
+    // event log (health / handshake changes are only logged when they differ)
+    events: EventLogSink,
+    bank_no: Option<u8>,
+    last_logged_health: Option<(bool, LinkState, Option<String>)>,
+    last_handshake_failure: Option<String>,
+
+    // contactor wear counters (persisted through the event log database)
+    wear: WearTracker,
+    last_wear_flush: Instant,
+    // process of the last current step, for the U2 of the wear energy
+    wear_process: Process,
+
+    // registry plumbing
+    shared: Arc<Mutex<BankShared>>,
+    claimed_ports: Arc<Mutex<HashSet<String>>>,
+    failsafe: Failsafe,
+}
+
+impl Worker {
+    fn new(host: Arc<dyn WorkerHost>, cfg: WorkerConfig, shared: Arc<Mutex<BankShared>>) -> Self {
+        let WorkerConfig {
+            bank_id,
+            baud,
+            serial,
+            mode,
+            keepalive,
+            safety,
+            budget,
+            estop,
+            claimed_ports,
+            failsafe,
+            capture,
+            events,
+        } = cfg;
+        // latched before this runtime started: open as soon as a port is adopted
+        let estop_run = estop.is_latched().then(EstopRun::default);
+        shared.lock().unwrap().poll = PollConfig {
+            enabled: false,
+            interval_ms: DEFAULT_POLL_INTERVAL_MS,
+        };
+        let connector = Connector {
+            host: host.clone(),
+            bank_id: bank_id.clone(),
+            baud,
+            serial,
+            capture: capture.clone(),
+            claimed_ports: claimed_ports.clone(),
+        };
+
+        Self {
+            host,
+            bank_id,
+            connector,
+            mode,
+            active_port: None,
+            port: None,
+            released: None,
+            connect_epoch: 0,
+            cmd_at: None,
+            decoder: FrameDecoder::new(),
+            link_stats: LinkStatsTracker::new(Instant::now()),
+            online: false,
+            link: LinkState::Offline,
+            last_seen: Instant::now(),
+            keepalive,
+            last_probe: Instant::now(),
+            last_tx: Instant::now(),
+            last_scan: Instant::now() - Duration::from_millis(DEFAULT_SCAN_EVERY_MS),
+            blind_once: false,
+            discovery_note: None,
+            scan_every: Duration::from_millis(DEFAULT_SCAN_EVERY_MS),
+            protocol_version: None,
+            unsupported_version: None,
+            poll_enabled: false, //true, (also in BankShared above)
+            poll_interval: Duration::from_millis(DEFAULT_POLL_INTERVAL_MS),
+            last_poll: Instant::now(),
+            last_status_fields: None,
+            commanded_mask: None,
+            active_faults: vec![],
+            pending_confirm: None,
+            sequence: None,
+            safety,
+            last_trip: None,
+            estop,
+            estop_run,
+            budget,
+            last_budget_emit: Instant::now(),
+            capture,
+            replay_done: false,
+            events,
+            bank_no: None,
+            last_logged_health: None,
+            last_handshake_failure: None,
+            wear: WearTracker::default(),
+            last_wear_flush: Instant::now(),
+            wear_process: Process::Mma,
+            shared,
+            claimed_ports,
+            failsafe,
+        }
+    }
+
+    // maintenance history of real banks only (not the simulator or a replay)
+    fn log_event(&self, port_name: &str, event: LogEvent) {
+        if self.mode.is_real_link() {
+            self.events
+                .log(&self.bank_id, self.bank_no, port_name, event);
+        }
+    }
+
+    // port being tried when nothing is connected
+    fn log_port_name(&self) -> String {
+        self.active_port.clone().unwrap_or_else(|| self.mode.key())
+    }
+
+    fn emit_health(&mut self, online: bool, reason: Option<String>) {
+        let logged = (online, self.link, reason.clone());
+        if self.last_logged_health.as_ref() != Some(&logged) {
+            let port_name = self.log_port_name();
+            let event = LogEvent::health(online, self.link, reason.as_deref());
+            self.log_event(&port_name, event);
+            self.last_logged_health = Some(logged);
+        }
+
+        let port_name = self.active_port.clone().unwrap_or_default();
+        {
+            let mut shared = self.shared.lock().unwrap();
+            shared.online = online;
+            shared.link = self.link;
+            shared.protocol_version = self.protocol_version;
+            shared.port_name = self.active_port.clone();
+        }
+        self.capture.record(
+            &self.bank_id,
+            &port_name,
+            CaptureEvent::Health {
+                online,
+                link: self.link,
+                reason: reason.clone(),
+            },
+        );
+        self.host.emit(WorkerEvent::Health(LoadBankHealth {
+            bank_id: self.bank_id.clone(),
+            port_name,
+            online,
+            link: self.link,
+            last_seen_ms: self.last_seen.elapsed().as_millis(),
+            reason,
+            protocol_version: self.protocol_version,
+            unsupported_version: self.unsupported_version,
+        }));
+    }
+
+    fn set_mode(&mut self, mode: RuntimeMode) {
+        if self.mode == mode {
+            return;
+        }
+        eprintln!("[LB] mode change: {} -> {}", self.mode.key(), mode.key());
+        // dropped under the old mode (its wear is still flushed)
+        self.drop_port(Some("mode changed".into()));
+        self.mode = mode;
+        self.replay_done = false;
+        self.connect_epoch += 1;
+    }
+
+    fn set_polling(&mut self, enabled: bool, interval_ms: u64) {
+        self.poll_enabled = enabled;
+        self.poll_interval = Duration::from_millis(interval_ms.max(50));
+        self.last_poll = Instant::now();
+        self.share_poll();
+    }
+
+    fn share_poll(&self) {
+        self.shared.lock().unwrap().poll = PollConfig {
+            enabled: self.poll_enabled,
+            interval_ms: self.poll_interval.as_millis() as u64,
+        };
+    }
+
+    fn share_decoder(&self, got_frame: bool) {
+        let mut shared = self.shared.lock().unwrap();
+        if got_frame {
+            shared.last_frame = Some(Instant::now());
+        }
+        shared.decoder = self.decoder.stats().clone();
+    }
+
+    fn share_handshake(&self, port_name: &str, error: Option<String>) {
+        self.shared.lock().unwrap().last_handshake = Some(HandshakeOutcome {
+            t: Local::now(),
+            port_name: port_name.to_string(),
+            ok: error.is_none(),
+            error,
+            protocol_version: self.protocol_version,
+        });
+    }
+
+    fn publish_status(&mut self, status: LoadBankStatus) {
+        self.bank_no = Some(status.bank_no);
+        self.emit_fault_edges(&status);
+        self.estop_status(status.contactors_mask);
+        {
+            let mut shared = self.shared.lock().unwrap();
+            shared.status = Some(status.clone());
+            shared.last_status = Some(Instant::now());
+        }
+        self.host.emit(WorkerEvent::Status(status));
+    }
+
+    fn emit_fault_edges(&mut self, status: &LoadBankStatus) {
+        let edges = fault_edges(&self.active_faults, &status.faults);
+        if edges.is_empty() {
+            return;
+        }
+        self.active_faults = status.faults.clone();
+        for (edge, fault) in edges {
+            self.log_event(&status.port_name, LogEvent::fault(edge, &fault));
+            eprintln!(
+                "[LB/FAULT] {} {:?} {:?}: {}",
+                self.bank_id, edge, fault.severity, fault.message
+            );
+            self.host.emit(WorkerEvent::Fault(FaultEvent {
+                t: Local::now(),
+                bank_id: self.bank_id.clone(),
+                port_name: status.port_name.clone(),
+                edge,
+                fault,
+            }));
+        }
+    }
+
+    fn set_keepalive(&mut self, cfg: KeepaliveConfig) {
+        eprintln!("[LB] keepalive: {:?}", cfg);
+        self.keepalive = cfg;
+    }
+
+    fn drop_port(&mut self, reason: Option<String>) {
+        let why = reason.clone().unwrap_or_else(|| "disconnected".into());
+        self.end_sequence(SequenceState::Aborted, why);
+        self.link = LinkState::Offline;
+        if self.online {
+            self.emit_health(false, reason.or(Some("disconnected".into())));
+        }
+        if let Some(port_name) = self.active_port.as_ref() {
+            self.claimed_ports.lock().unwrap().remove(port_name);
+        }
+        self.shared.lock().unwrap().status = None;
+        self.failsafe.unregister(&self.bank_id);
+        if let Some(link) = self.port.take() {
+            self.released = link.release();
+        }
+        self.active_port = None;
+        self.online = false;
+        self.protocol_version = None;
+        self.decoder.clear();
+        self.last_status_fields = None;
+        self.commanded_mask = None;
+        self.wear.disconnect();
+        self.flush_wear();
+        if let Some(run) = self.estop_run.as_mut() {
+            // unknown contactor state on the next port: open it again
+            run.confirmed = false;
+            run.last_send = None;
+        }
+
+        if let Some(p) = self.pending_confirm.take() {
+            let _ = p.reply.send(Err(ContactorCmdError::Offline {
+                reason: "port dropped before confirmation".into(),
+            }));
+        }
+    }
+
+    fn send_tx(&mut self, bytes: &[u8]) {
+        let Some(link) = self.port.as_mut() else {
+            return;
+        };
+        let port_name = self.active_port.clone().unwrap_or_default();
+
+        if let Err(e) = link.write_all(bytes) {
+            eprintln!("[LB/TX] {} write failed: {}", port_name, e);
+            self.cmd_at = None;
+            self.drop_port(Some(format!("write failed: {e}")));
+            return;
+        }
+        self.last_tx = Instant::now();
+        if let Some(at) = self.cmd_at.take() {
+            self.record_latency(at.elapsed());
+        }
+
+        self.capture.record(
+            &self.bank_id,
+            &port_name,
+            CaptureEvent::Tx { hex: to_hex(bytes) },
+        );
+        self.host.emit(WorkerEvent::Tx(SerialTxChunk {
+            bank_id: self.bank_id.clone(),
+            port_name: port_name.clone(),
+            bytes: bytes.to_vec(),
+            hex: to_hex(bytes),
+        }));
+        eprintln!("[LB/TX] {} {}", port_name, to_hex(bytes));
+    }
+
+    fn record_latency(&self, latency: Duration) {
+        let us = latency.as_micros() as u64;
+        let mut shared = self.shared.lock().unwrap();
+        let l = &mut shared.cmd_latency;
+        l.count += 1;
+        l.last_us = us;
+        l.max_us = l.max_us.max(us);
+    }
+
+    fn adopt_port(&mut self, adopted: Adopted) {
+        let Adopted {
+            port,
+            fields,
+            status,
+        } = adopted;
+        let port_name = port.name().to_string();
+        let link = match Link::start(port) {
+            Ok(link) => link,
+            Err(e) => {
+                self.emit_health(false, Some(format!("open failed: {e}")));
+                return;
+            }
+        };
+        self.claimed_ports.lock().unwrap().insert(port_name.clone());
+        let writer = link.writer.clone();
+        self.active_port = Some(port_name.clone());
+        self.port = Some(link);
+        self.last_tx = Instant::now();
+        self.online = true;
+        self.link = LinkState::Online;
+        self.last_seen = Instant::now();
+        self.last_handshake_failure = None;
+        self.link_stats.adopted();
+        self.set_protocol_version(fields.version);
+        self.share_handshake(&port_name, None);
+        self.shared.lock().unwrap().last_frame = Some(self.last_seen);
+        self.emit_health(true, Some("handshake ok".into()));
+        self.remember_host(fields.bank_no);
+
+        // Seed status immediately (important if device goes silent after handshake)
+        self.last_status_fields = Some(fields);
+        self.failsafe
+            .register(&self.bank_id, &port_name, &writer, self.open_frame());
+
+        self.publish_status(status);
+    }
+
+    // USB serial number -> bank, so AUTO finds the bank again after a rename
+    fn remember_host(&self, bank_no: u8) {
+        let Some(port_name) = self.active_port.clone() else {
+            return;
+        };
+        let (host, bank_id) = (self.host.clone(), self.bank_id.clone());
+        // enumeration and the store file block
+        tokio::task::spawn_blocking(move || {
+            if let Some(info) = list_ports_detailed()
+                .into_iter()
+                .find(|p| p.port_name == port_name)
+            {
+                host.remember_port(&info, &bank_id, bank_no);
+            }
+        });
+    }
+
+    fn set_protocol_version(&mut self, version: u8) {
+        eprintln!("[LB] {:?} speaks protocol v{}", self.active_port, version);
+        self.protocol_version = Some(version);
+        self.unsupported_version = None;
+    }
+
+    fn handshake_failed(&mut self, port_name: &str, e: HandshakeError) {
+        eprintln!("[LB] handshake FAILED on {}: {}", port_name, e);
+        let failure = e.to_string();
+        self.share_handshake(port_name, Some(failure.clone()));
+        if self.last_handshake_failure.as_ref() != Some(&failure) {
+            self.log_event(port_name, LogEvent::handshake_failed(&failure));
+            self.last_handshake_failure = Some(failure);
+        }
+        let reason = match e {
+            HandshakeError::UnsupportedVersion(v) => {
+                self.unsupported_version = Some(v);
+                format!("{port_name}: {e}")
+            }
+            _ => "handshake failed".into(),
+        };
+        self.emit_health(false, Some(reason));
+    }
+
+    /// Next connection attempt, if one is due (not connected, scan throttle).
+    fn connect_plan(&mut self) -> Option<ConnectPlan> {
+        if self.port.is_some() || self.last_scan.elapsed() < self.scan_every {
+            return None;
+        }
+        self.last_scan = Instant::now();
+
+        let mut discover = None;
+        let (ports, report_all) = match self.mode.clone() {
+            RuntimeMode::Fixed { port_name } => (vec![port_name], true),
+            RuntimeMode::Simulated => (vec![SIM_PORT_NAME.to_string()], true),
+            RuntimeMode::Tcp { addr } => (vec![format!("{TCP_SCHEME}{addr}")], true),
+            // a finished replay stays offline until the mode is set again
+            RuntimeMode::Replay { spec } if !self.replay_done => {
+                (vec![format!("{REPLAY_SCHEME}{spec}")], true)
+            }
+            RuntimeMode::Replay { .. } => return None,
+            RuntimeMode::Auto => {
+                discover = Some(Discover {
+                    bank_no: self.bank_no,
+                    blind: std::mem::take(&mut self.blind_once),
+                });
+                (vec![], false)
+            }
+        };
+        Some(ConnectPlan {
+            connector: self.connector.clone(),
+            epoch: self.connect_epoch,
+            ports,
+            discover,
+            report_all,
+            released: self.released.take(),
+        })
+    }
+
+    fn finish_connect(&mut self, outcome: ConnectOutcome) {
+        // mode changed while connecting: dropping the outcome closes its port
+        if outcome.epoch != self.connect_epoch || self.port.is_some() {
+            return;
+        }
+        if let Some((ports, candidates, blind)) = outcome.discovered {
+            self.note_discovery(ports, candidates, blind);
+        }
+        self.link_stats.handshake_failed(outcome.handshake_failures);
+        if let Some((port_name, error)) = outcome.quiet_failure {
+            self.share_handshake(&port_name, Some(error));
+        }
+        for (port_name, failure) in outcome.failures {
+            match failure {
+                ConnectFailure::InUse => {
+                    self.emit_health(false, Some(format!("{port_name} in use by another bank")))
+                }
+                ConnectFailure::Open(e) => {
+                    self.emit_health(false, Some(format!("open failed: {e}")))
+                }
+                ConnectFailure::Handshake(e) => self.handshake_failed(&port_name, e),
+            }
+        }
+        if let Some(adopted) = outcome.adopted {
+            self.adopt_port(adopted);
+        }
+    }
+
+    // Tell once (not every scan) when AUTO has nothing it may try.
+    fn note_discovery(&mut self, ports: usize, candidates: usize, blind: bool) {
+        if blind {
+            eprintln!("[LB] blind scan: {candidates} port(s)");
+        }
+        let note = (candidates == 0 && ports > 0)
+            .then(|| format!("no known load-bank adapter among {ports} port(s), blind scan off"));
+        if note != self.discovery_note {
+            self.discovery_note = note.clone();
+            if let Some(note) = note {
+                eprintln!("[LB] {note}");
+                self.emit_health(false, Some(note));
+            }
+        }
+    }
+
+    fn scan_all_ports(&mut self) {
+        if self.mode != RuntimeMode::Auto {
+            eprintln!("[LB] scan-all ignored: {} is not AUTO", self.bank_id);
+            return;
+        }
+        self.blind_once = true;
+        // scan on the next tick
+        self.last_scan = Instant::now() - self.scan_every;
+    }
+
+    fn port_removed(&mut self, port_name: &str) {
+        if self.active_port.as_deref() == Some(port_name) {
+            eprintln!("[LB] {port_name} unplugged");
+            self.drop_port(Some(format!("{port_name} removed")));
+        }
+    }
+
+    fn port_added(&mut self, port_name: &str) {
+        if self.port.is_some() {
+            return;
+        }
+        let wanted = match &self.mode {
+            RuntimeMode::Auto => true,
+            RuntimeMode::Fixed { port_name: fixed } => fixed == port_name,
+            _ => false,
+        };
+        if wanted {
+            // try it on the next tick instead of after the scan throttle
+            self.last_scan = Instant::now() - self.scan_every;
+        }
+    }
+
+    fn poll_if_due(&mut self) {
+        if !self.poll_enabled {
+            return;
+        }
+        if self.port.is_none() {
+            return;
+        }
+        if self.last_poll.elapsed() < self.poll_interval {
+            return;
+        }
+        let poll = self.hold_frame();
+        self.send_tx(poll.as_bytes());
+        self.last_poll = Instant::now();
+    }
+
+    /// `feed_watchdog`: re-assert the current mask when nothing else was
+    /// written for HOST_KEEPALIVE_MS.
+    fn watchdog_keepalive(&mut self) {
+        let every = Duration::from_millis(HOST_KEEPALIVE_MS);
+        if self.port.is_none() || !self.keepalive.feed_watchdog || self.last_tx.elapsed() < every {
+            return;
+        }
+        let frame = self.hold_frame();
+        self.send_tx(frame.as_bytes());
+    }
+
+    fn on_read(&mut self, read: LinkRead) {
+        let Some(port_name) = self.active_port.clone() else {
+            return;
+        };
+
+        match read {
+            LinkRead::Data(chunk) => {
+                self.link_stats.rx(chunk.len());
+                self.decoder.push(&chunk);
+                emit_rx(
+                    &*self.host,
+                    &self.capture,
+                    &self.bank_id,
+                    &port_name,
+                    &chunk,
+                );
+                self.parse_frames();
+                // counters also move on chunks without a frame
+                self.share_decoder(false);
+            }
+            LinkRead::Eof => {
+                eprintln!("[LB/REPLAY] {} finished", port_name);
+                self.replay_done = true;
+                self.drop_port(Some("replay finished".into()));
+            }
+            LinkRead::Failed(e) => {
+                eprintln!("[LB] read error on {}: {}", port_name, e);
+                self.drop_port(Some(format!("read error: {e}")));
+            }
+        }
+    }
+
+    fn parse_frames(&mut self) {
+        let Some(port_name) = self.active_port.clone() else {
+            return;
+        };
+
+        while let Some(frame) = self.decoder.next_frame() {
+            self.share_decoder(true);
+            self.capture_frame(&port_name, &frame);
+
+            // If device starts sending HELLO again while connected, it likely reset.
+            // Re-ACK it and keep the port (unless it came back with other firmware).
+            if frame.is_hello() {
+                eprintln!(
+                    "[LB] hello detected while connected on {} -> re-ack",
+                    port_name
+                );
+                let version = frame.version();
+                if !is_supported(version) {
+                    let e = HandshakeError::UnsupportedVersion(version);
+                    self.unsupported_version = Some(version);
+                    self.log_event(&port_name, LogEvent::handshake_failed(&e.to_string()));
+                    self.drop_port(Some(format!("{port_name}: {e}")));
+                    return;
+                }
+                if self.protocol_version != Some(version) {
+                    self.set_protocol_version(version);
+                }
+
+                // derive ack from hello frame and send
+                let ack = frame.to_ack();
+                self.send_tx(ack.as_bytes());
+
+                self.last_seen = Instant::now();
+                continue;
+            }
+
+            let fields = match frame.decode() {
+                Ok(f) => f,
+                Err(e) => {
+                    eprintln!("[LB] dropped frame on {}: {}", port_name, e);
+                    continue;
+                }
+            };
+            let status = LoadBankStatus::from_fields(&fields, &frame, &self.bank_id, &port_name);
+
+            self.last_seen = Instant::now();
+            if !self.online || self.link != LinkState::Online {
+                let reason = (self.link == LinkState::Degraded).then(|| "link recovered".into());
+                self.online = true;
+                self.link = LinkState::Online;
+                self.emit_health(true, reason);
+            }
+
+            let mask = fields.contactors_mask;
+            let mask_changed = self
+                .last_status_fields
+                .as_ref()
+                .is_none_or(|f| f.contactors_mask != mask);
+            // simulated or replayed contactors neither heat a resistor nor wear
+            if self.mode.is_real_link() {
+                self.budget.lock().unwrap().set_mask(mask, self.last_seen);
+                self.wear
+                    .observe(fields.bank_no, mask, self.last_seen, self.wear_process);
+            }
+
+            let trip = trip_reason(&self.safety, &fields);
+            self.last_status_fields = Some(fields);
+            self.failsafe
+                .set_open_frame(&self.bank_id, self.open_frame());
+
+            // published first: whoever awaits the confirmation sees it in `lb_get_state`
+            self.publish_status(status.clone());
+            self.resolve_confirm(&status);
+            if let Some(seq) = self.sequence.as_mut() {
+                seq.on_mask(mask, self.last_seen);
+            }
+
+            if let Some(interlock) = trip {
+                self.auto_open(interlock);
+            }
+            if mask_changed {
+                self.log_event(&port_name, LogEvent::contactors(mask));
+                self.emit_budget();
+            }
+        }
+    }
+
+    fn capture_frame(&self, port_name: &str, frame: &Frame) {
+        let hex = frame.to_hex();
+        let event = match frame.decode() {
+            Ok(fields) => CaptureEvent::Frame { hex, fields },
+            Err(e) => CaptureEvent::FrameError {
+                hex,
+                error: e.to_string(),
+            },
+        };
+        self.capture.record(&self.bank_id, port_name, event);
+    }
+
+    fn emit_budget(&mut self) {
+        self.last_budget_emit = Instant::now();
+        let snapshot = self.budget.lock().unwrap().snapshot(
+            &self.bank_id,
+            self.safety.u2_max_v,
+            self.last_budget_emit,
+        );
+        self.host.emit(WorkerEvent::Budget(snapshot));
+    }
+
+    // Closed branches spend budget between frames too: open them once it runs out.
+    fn budget_check(&mut self) {
+        if self
+            .last_status_fields
+            .as_ref()
+            .is_none_or(|f| f.contactors_mask == 0)
+        {
+            return;
+        }
+        let exhausted = self
+            .budget
+            .lock()
+            .unwrap()
+            .exhausted(self.safety.u2_max_v, Instant::now());
+        if let Some(interlock) = exhausted.filter(|_| self.safety.enabled) {
+            self.auto_open(interlock);
+        }
+        if self.last_budget_emit.elapsed() >= Duration::from_millis(BUDGET_EMIT_EVERY_MS) {
+            self.emit_budget();
+        }
+    }
+
+    fn flush_wear(&mut self) {
+        self.last_wear_flush = Instant::now();
+        if self.mode.is_real_link() {
+            self.wear.flush(&self.bank_id, &self.events);
+        }
+    }
+
+    fn wear_check(&mut self) {
+        if self.last_wear_flush.elapsed() >= Duration::from_millis(WEAR_FLUSH_EVERY_MS) {
+            self.flush_wear();
+        }
+    }
+
+    fn link_stats_check(&mut self) {
+        let Some((counters, rates)) = self.link_stats.sample(self.decoder.stats(), Instant::now())
+        else {
+            return;
+        };
+        let port_name = self.active_port.clone();
+        // an idle runtime doesn't fill the capture with identical samples
+        if self.link_stats.take_capture(&counters) {
+            self.capture.record(
+                &self.bank_id,
+                port_name.as_deref().unwrap_or_default(),
+                CaptureEvent::LinkStats { counters, rates },
+            );
+        }
+        let event = LinkStatsEvent {
+            t: Local::now(),
+            bank_id: self.bank_id.clone(),
+            port_name,
+            counters,
+            rates,
+        };
+        self.shared.lock().unwrap().link_stats = Some(event.clone());
+        self.host.emit(WorkerEvent::LinkStats(event));
+    }
+
+    // Device may be silent after handshake, so silence alone is not a disconnect:
+    // probe first (it answers the ACK template with a status), and only give up
+    // once the probes have gone unanswered for `offline_after`.
+    fn offline_check(&mut self) {
+        if self.port.is_none() || !self.keepalive.enabled {
+            return;
+        }
+
+        let silent_for = self.last_seen.elapsed();
+
+        if silent_for >= self.keepalive.offline_after {
+            eprintln!(
+                "[LB] offline: no frames on {:?} for {} ms",
+                self.active_port,
+                silent_for.as_millis()
+            );
+            self.drop_port(Some(format!(
+                "keepalive failed: no frames for {} ms",
+                silent_for.as_millis()
+            )));
+            return;
+        }
+
+        if silent_for >= self.keepalive.degraded_after && self.link == LinkState::Online {
+            self.link = LinkState::Degraded;
+            self.emit_health(true, Some("no frames, probing".into()));
+        }
+
+        if silent_for >= self.keepalive.probe_after
+            && self.last_probe.elapsed() >= self.keepalive.probe_after
+        {
+            let probe = self.hold_frame();
+            self.send_tx(probe.as_bytes());
+            self.last_probe = Instant::now();
+        }
+    }
+
+    fn cmd_set_contactors_confirmed(
+        &mut self,
+        mask: u16,
+        timeout: Duration,
+        retries: u32,
+        reply: oneshot::Sender<Result<LoadBankStatus, ContactorCmdError>>,
+    ) {
+        if self.port.is_none() || !self.online {
+            let _ = reply.send(Err(ContactorCmdError::Offline {
+                reason: "load bank not connected".into(),
+            }));
+            return;
+        }
+        if let Err(interlock) = self.cmd_set_contactors(mask) {
+            let _ = reply.send(Err(ContactorCmdError::Interlock {
+                message: interlock.to_string(),
+                interlock,
+            }));
+            return;
+        }
+        if self.port.is_none() {
+            // the write failed and dropped the port
+            let _ = reply.send(Err(ContactorCmdError::Offline {
+                reason: "write failed".into(),
+            }));
+            return;
+        }
+        if let Some(old) = self.pending_confirm.take() {
+            let _ = old.reply.send(Err(ContactorCmdError::Superseded));
+        }
+
+        self.pending_confirm = Some(PendingConfirm {
+            mask,
+            timeout,
+            deadline: Instant::now() + timeout,
+            attempts: 1,
+            max_attempts: retries + 1,
+            last_status: None,
+            reply,
+        });
+    }
+
+    fn retry_confirm(&mut self) -> bool {
+        let Some(p) = self.pending_confirm.as_mut() else {
+            return false;
+        };
+        if p.attempts >= p.max_attempts {
+            return false;
+        }
+        p.attempts += 1;
+        p.deadline = Instant::now() + p.timeout;
+        let mask = p.mask;
+        eprintln!("[LB] contactors 0x{:04X}: retry {}", mask, p.attempts);
+        self.send_contactors(mask);
+        true
+    }
+
+    // Another mask is only remembered: the status may predate the command
+    // (or a slow contactor), `confirm_check` decides at the deadline.
+    fn resolve_confirm(&mut self, status: &LoadBankStatus) {
+        let Some(p) = self.pending_confirm.as_mut() else {
+            return;
+        };
+        if status.contactors_mask == p.mask {
+            let p = self.pending_confirm.take().unwrap();
+            let _ = p.reply.send(Ok(status.clone()));
+            return;
+        }
+        p.last_status = Some(status.clone());
+    }
+
+    fn confirm_check(&mut self) {
+        let Some(p) = self.pending_confirm.as_ref() else {
+            return;
+        };
+        if Instant::now() < p.deadline || self.retry_confirm() {
+            return;
+        }
+        let p = self.pending_confirm.take().unwrap();
+        if let Some(status) = p.last_status {
+            eprintln!(
+                "[LB] contactors mismatch: requested 0x{:04X}, reported 0x{:04X} (err 0x{:04X})",
+                p.mask, status.contactors_mask, status.err_contactors
+            );
+            let _ = p.reply.send(Err(ContactorCmdError::Mismatch {
+                requested: p.mask,
+                reported: status.contactors_mask,
+                attempts: p.attempts,
+                err_contactors: status.err_contactors,
+                status,
+            }));
+            return;
+        }
+        eprintln!(
+            "[LB] contactors 0x{:04X}: no confirmation after {} attempts",
+            p.mask, p.attempts
+        );
+        let _ = p.reply.send(Err(ContactorCmdError::Timeout {
+            requested: p.mask,
+            attempts: p.attempts,
+        }));
+    }
+
+    fn emit_interlock(&self, action: InterlockAction, mask: u16, interlock: Interlock) {
+        self.host.emit(WorkerEvent::Interlock(InterlockEvent {
+            bank_id: self.bank_id.clone(),
+            port_name: self.active_port.clone().unwrap_or_default(),
+            action,
+            mask,
+            message: interlock.to_string(),
+            interlock,
+        }));
+    }
+
+    /// Fault reported with contactors closed: open everything, fail any pending close.
+    fn auto_open(&mut self, interlock: Interlock) {
+        self.end_sequence(SequenceState::Aborted, interlock.to_string());
+        if self
+            .last_trip
+            .is_some_and(|t| t.elapsed() < Duration::from_millis(TRIP_RESEND_MS))
+        {
+            return;
+        }
+        self.last_trip = Some(Instant::now());
+
+        eprintln!(
+            "[LB/SAFETY] bank {}: opening contactors: {}",
+            self.bank_id, interlock
+        );
+        if let Some(p) = self.pending_confirm.take_if(|p| p.mask != 0) {
+            let _ = p.reply.send(Err(ContactorCmdError::Interlock {
+                message: interlock.to_string(),
+                interlock: interlock.clone(),
+            }));
+        }
+        self.send_contactors(0);
+        self.emit_interlock(InterlockAction::AutoOpen, 0, interlock);
+    }
+
+    /// Woken by `lb_emergency_stop` (ahead of the command queue).
+    fn emergency_stop(&mut self) {
+        if !self.estop.is_latched() {
+            // stale wakeup: reset before the worker got to it
+            return;
+        }
+        let reason = self
+            .estop
+            .reason()
+            .unwrap_or_else(|| "emergency stop".into());
+        if let Some(run) = self.estop_run.as_mut() {
+            // pressed again: resend right away, confirmed or not
+            run.confirmed = false;
+            run.last_send = None;
+            self.estop_check();
+            return;
+        }
+        eprintln!("[LB/ESTOP] bank {}: latched: {reason}", self.bank_id);
+        self.estop_run = Some(EstopRun {
+            since: Some(Instant::now()),
+            ..Default::default()
+        });
+        self.end_sequence(SequenceState::Aborted, "emergency stop".into());
+        if let Some(p) = self.pending_confirm.take_if(|p| p.mask != 0) {
+            let _ = p.reply.send(Err(ContactorCmdError::Interlock {
+                message: Interlock::EmergencyStop.to_string(),
+                interlock: Interlock::EmergencyStop,
+            }));
+        }
+        self.estop_check();
+        self.emit_estop(EstopState::Latched, reason);
+    }
+
+    fn estop_resend_at(&self) -> Option<Instant> {
+        let run = self.estop_run.as_ref()?;
+        if run.confirmed || self.port.is_none() {
+            return None;
+        }
+        let resend = Duration::from_millis(ESTOP_RESEND_MS);
+        Some(run.last_send.map_or_else(Instant::now, |t| t + resend))
+    }
+
+    /// Sends all-open while the latch isn't confirmed by a status frame.
+    fn estop_check(&mut self) {
+        if self.estop_resend_at().is_none_or(|t| Instant::now() < t) {
+            return;
+        }
+        let run = self.estop_run.as_mut().unwrap();
+        run.last_send = Some(Instant::now());
+        run.sends += 1;
+        self.send_contactors(0);
+    }
+
+    fn estop_status(&mut self, mask: u16) {
+        let Some(run) = self.estop_run.as_mut() else {
+            return;
+        };
+        if mask != 0 {
+            // closed behind our back (or a stale frame): keep sending
+            run.confirmed = false;
+            return;
+        }
+        if run.confirmed {
+            return;
+        }
+        run.confirmed = true;
+        let took = run.since.map(|t| t.elapsed().as_millis()).unwrap_or(0);
+        let message = format!("all open after {} send(s), {took} ms", run.sends);
+        eprintln!("[LB/ESTOP] bank {}: {message}", self.bank_id);
+        self.emit_estop(EstopState::Confirmed, message);
+    }
+
+    fn estop_released(&mut self) {
+        // tripped again after the reset was queued: that trip stands
+        if self.estop.is_latched() || self.estop_run.take().is_none() {
+            return;
+        }
+        eprintln!("[LB/ESTOP] bank {}: reset", self.bank_id);
+        self.emit_estop(EstopState::Released, "reset".into());
+    }
+
+    fn emit_estop(&self, state: EstopState, message: String) {
+        self.log_event(
+            &self.log_port_name(),
+            LogEvent::emergency_stop(state, message),
+        );
+        let event = EstopEvent {
+            t: Local::now(),
+            bank_id: self.bank_id.clone(),
+            port_name: self.active_port.clone(),
+            state,
+            reason: self.estop.reason(),
+            sends: self.estop_run.as_ref().map_or(0, |r| r.sends),
+        };
+        self.shared.lock().unwrap().estop = (state != EstopState::Released).then(|| event.clone());
+        self.host.emit(WorkerEvent::Estop(event));
+    }
+
+    /// Safety-checked contactor command (refusals are logged and emitted).
+    fn cmd_set_contactors(&mut self, mask: u16) -> Result<(), Interlock> {
+        let online = self.port.is_some() && self.online;
+        // the latch holds even with the interlocks disabled
+        let checked = if mask != 0 && self.estop.is_latched() {
+            Err(Interlock::EmergencyStop)
+        } else {
+            check_mask(&self.safety, mask, online, self.last_status_fields.as_ref()).and_then(
+                |_| {
+                    if !self.safety.enabled {
+                        return Ok(());
+                    }
+                    self.budget
+                        .lock()
+                        .unwrap()
+                        .check(mask, self.safety.u2_max_v, Instant::now())
+                },
+            )
+        };
+        if let Err(interlock) = checked {
+            eprintln!(
+                "[LB/SAFETY] bank {}: refused mask 0x{:04X}: {}",
+                self.bank_id, mask, interlock
+            );
+            self.log_event(
+                &self.log_port_name(),
+                LogEvent::interlock(InterlockAction::Refused, mask, &interlock),
+            );
+            self.emit_interlock(InterlockAction::Refused, mask, interlock.clone());
+            return Err(interlock);
+        }
+        eprintln!(
+            "[LB] bank {}: contactors 0x{:04X}: {}",
+            self.bank_id,
+            mask,
+            explain_mask(mask, self.safety.u2_max_v).label()
+        );
+        self.send_contactors(mask);
+        Ok(())
+    }
+
+    fn sequence_cmd(&mut self, cmd: SequenceCmd) -> Result<SequenceProgress, String> {
+        let now = Instant::now();
+        match cmd {
+            SequenceCmd::Start(profile) => {
+                if self.estop.is_latched() {
+                    return Err(Interlock::EmergencyStop.to_string());
+                }
+                if self.sequence.is_some() {
+                    return Err("a load profile is already running".into());
+                }
+                if self.port.is_none() || !self.online {
+                    return Err("load bank not connected".into());
+                }
+                let port_name = self.log_port_name();
+                let message = format!("{}: {} step(s)", profile.name, profile.steps.len());
+                eprintln!("[LB/SEQ] bank {}: start {message}", self.bank_id);
+                self.log_event(
+                    &port_name,
+                    LogEvent::sequence(SequenceState::Running, message),
+                );
+                self.sequence = Some(Sequencer::new(profile, now));
+                // closes the first step right away (or fails it)
+                self.sequence_check();
+                if self.sequence.is_none() {
+                    let progress = self.shared.lock().unwrap().sequence.clone();
+                    return Err(progress
+                        .and_then(|p| p.reason)
+                        .unwrap_or_else(|| "load profile failed".into()));
+                }
+            }
+            SequenceCmd::Pause => {
+                let seq = self.sequence.as_mut().ok_or("no load profile running")?;
+                seq.pause(now)?;
+                eprintln!("[LB/SEQ] bank {}: paused", self.bank_id);
+                self.emit_sequence();
+            }
+            SequenceCmd::Resume => {
+                let seq = self.sequence.as_mut().ok_or("no load profile running")?;
+                seq.resume(now)?;
+                eprintln!("[LB/SEQ] bank {}: resumed", self.bank_id);
+                self.emit_sequence();
+            }
+            SequenceCmd::Abort => {
+                if self.sequence.is_none() {
+                    return Err("no load profile running".into());
+                }
+                self.end_sequence(SequenceState::Aborted, "aborted by operator".into());
+            }
+        }
+        let progress = self.shared.lock().unwrap().sequence.clone();
+        progress.ok_or_else(|| "no load profile running".into())
+    }
+
+    fn sequence_check(&mut self) {
+        let now = Instant::now();
+        while let Some(action) = self.sequence.as_mut().and_then(|s| s.poll(now)) {
+            match action {
+                SequenceAction::StartStep => {
+                    if let Err(reason) = self.sequence_switch(now) {
+                        self.end_sequence(SequenceState::Failed, reason);
+                    }
+                }
+                SequenceAction::Resend(mask) => {
+                    eprintln!("[LB/SEQ] contactors 0x{:04X}: resend", mask);
+                    self.send_contactors(mask);
+                }
+                SequenceAction::Capture { step, at_ms, mask } => {
+                    self.emit_sequence_capture(step, at_ms, mask)
+                }
+                SequenceAction::NoConfirm { mask, attempts } => self.end_sequence(
+                    SequenceState::Failed,
+                    format!("contactors 0x{mask:04X}: no confirmation after {attempts} attempts"),
+                ),
+                SequenceAction::Done => {
+                    self.end_sequence(SequenceState::Finished, "profile done".into())
+                }
+            }
+        }
+        if self.sequence.as_ref().is_some_and(|s| s.progress_due(now)) {
+            self.emit_sequence();
+        }
+    }
+
+    // Resolves the mask of the current step and closes it.
+    fn sequence_switch(&mut self, now: Instant) -> Result<(), String> {
+        let Some(seq) = self.sequence.as_ref() else {
+            return Ok(());
+        };
+        let step = seq.current_step();
+        let step_ms = (step.settle_ms + step.dwell_ms) as f64;
+        let mask = match step.target.clone() {
+            StepTarget::Mask { mask } => mask,
+            StepTarget::Current { current_a, process } => {
+                let max_rel_error = seq.profile().max_rel_error.unwrap_or(DEFAULT_MAX_REL_ERROR);
+                let budget = self.budget.lock().unwrap();
+                let best = rank_combos(process, current_a, max_rel_error, &|bit| {
+                    budget.used_on_ms(bit, now)
+                });
+                let mask = best
+                    .first()
+                    .map(|c| c.mask)
+                    .ok_or_else(|| format!("no feasible combo for {current_a} A"))?;
+                self.wear_process = process;
+                mask
+            }
+        };
+        // the whole step has to fit in the ON time left, not just its start
+        if self.safety.enabled {
+            let left = self
+                .budget
+                .lock()
+                .unwrap()
+                .remaining_on_ms(mask, self.safety.u2_max_v, now);
+            if let Some(left) = left.filter(|&left| left < step_ms) {
+                return Err(format!(
+                    "0x{mask:04X} has {:.1} s of ON time left, the step needs {:.1} s",
+                    left / 1000.0,
+                    step_ms / 1000.0
+                ));
+            }
+        }
+        self.cmd_set_contactors(mask).map_err(|i| i.to_string())?;
+
+        let reported = self.last_status_fields.as_ref().map(|f| f.contactors_mask);
+        if let Some(seq) = self.sequence.as_mut() {
+            seq.switched(mask, now);
+            // same mask as the previous step: already confirmed
+            if let Some(reported) = reported {
+                seq.on_mask(reported, now);
+            }
+        }
+        Ok(())
+    }
+
+    fn emit_sequence(&mut self) {
+        let Some(seq) = self.sequence.as_mut() else {
+            return;
+        };
+        let state = if seq.is_paused() {
+            SequenceState::Paused
+        } else {
+            SequenceState::Running
+        };
+        let progress = seq.progress(&self.bank_id, state, None, Instant::now());
+        self.shared.lock().unwrap().sequence = Some(progress.clone());
+        self.host.emit(WorkerEvent::Sequence(progress));
+    }
+
+    fn emit_sequence_capture(&self, step: usize, at_ms: u64, mask: u16) {
+        let Some(seq) = self.sequence.as_ref() else {
+            return;
+        };
+        let status = self.shared.lock().unwrap().status.clone();
+        self.host
+            .emit(WorkerEvent::SequenceCapture(SequenceCapture {
+                t: Local::now(),
+                bank_id: self.bank_id.clone(),
+                profile: seq.profile().name.clone(),
+                step,
+                label: seq.current_step().label.clone(),
+                at_ms,
+                mask,
+                status,
+            }));
+    }
+
+    /// Ends the running profile (if any) and opens the contactors.
+    fn end_sequence(&mut self, state: SequenceState, reason: String) {
+        let Some(mut seq) = self.sequence.take() else {
+            return;
+        };
+        if self.port.is_some() {
+            self.send_contactors(0);
+        }
+        let progress = seq.progress(&self.bank_id, state, Some(reason.clone()), Instant::now());
+        eprintln!(
+            "[LB/SEQ] bank {}: {:?} at step {}/{}: {reason}",
+            self.bank_id,
+            state,
+            progress.step + 1,
+            progress.steps
+        );
+        let message = format!(
+            "{}: step {}/{}: {reason}",
+            progress.profile,
+            progress.step + 1,
+            progress.steps
+        );
+        self.log_event(&self.log_port_name(), LogEvent::sequence(state, message));
+        self.shared.lock().unwrap().sequence = Some(progress.clone());
+        self.host.emit(WorkerEvent::Sequence(progress));
+    }
+
+    /// Returns false on `Stop`.
+    fn handle_cmd(&mut self, queued_at: Instant, cmd: RuntimeCmd) -> bool {
+        self.cmd_at = Some(queued_at);
+        match cmd {
+            RuntimeCmd::Stop => return false,
+            RuntimeCmd::SetMode(m) => self.set_mode(m),
+            RuntimeCmd::SetPolling {
+                enabled,
+                interval_ms,
+            } => self.set_polling(enabled, interval_ms),
+            // raw bytes may close contactors too
+            RuntimeCmd::WriteRaw(_) if self.estop.is_latched() => {
+                eprintln!("[LB/ESTOP] bank {}: raw write refused", self.bank_id);
+            }
+            RuntimeCmd::WriteRaw(bytes) => self.send_tx(&bytes),
+            RuntimeCmd::SetContactors(mask) => {
+                self.end_sequence(SequenceState::Aborted, "manual contactor command".into());
+                let _ = self.cmd_set_contactors(mask);
+            }
+            RuntimeCmd::SetContactorsConfirmed {
+                mask,
+                timeout,
+                retries,
+                reply,
+            } => {
+                self.end_sequence(SequenceState::Aborted, "manual contactor command".into());
+                self.cmd_set_contactors_confirmed(mask, timeout, retries, reply)
+            }
+            RuntimeCmd::SetKeepalive(cfg) => self.set_keepalive(cfg),
+            RuntimeCmd::SetSafety(cfg) => self.safety = cfg,
+            RuntimeCmd::ScanAllPorts => self.scan_all_ports(),
+            RuntimeCmd::PortRemoved(port_name) => self.port_removed(&port_name),
+            RuntimeCmd::PortAdded(port_name) => self.port_added(&port_name),
+            RuntimeCmd::ResetEstop => self.estop_released(),
+            RuntimeCmd::Sequence { cmd, reply } => {
+                let _ = reply.send(self.sequence_cmd(cmd));
+            }
+        }
+        // only a write right away counts (retries, polls don't)
+        self.cmd_at = None;
+        true
+    }
+
+    /// Timer driven checks; each one knows whether it is due.
+    fn supervise(&mut self) {
+        self.poll_if_due();
+        self.watchdog_keepalive();
+        self.offline_check();
+        self.confirm_check();
+        self.estop_check();
+        self.budget_check();
+        self.wear_check();
+        self.link_stats_check();
+        self.sequence_check();
+    }
+
+    /// Earliest supervisor deadline (reads and commands wake the task anyway).
+    fn next_wakeup(&self, connecting: bool) -> Instant {
+        let now = Instant::now();
+        let mut next = self.last_wear_flush + Duration::from_millis(WEAR_FLUSH_EVERY_MS);
+        let mut at = |t: Instant| next = next.min(t);
+        at(self.link_stats.next_due());
+
+        if self.port.is_none() {
+            if !connecting {
+                at(self.last_scan + self.scan_every);
+            }
+        } else {
+            if self.keepalive.feed_watchdog {
+                at(self.last_tx + Duration::from_millis(HOST_KEEPALIVE_MS));
+            }
+            if self.poll_enabled {
+                at(self.last_poll + self.poll_interval);
+            }
+            let k = &self.keepalive;
+            if k.enabled {
+                at(self.last_seen + k.offline_after);
+                if self.link == LinkState::Online {
+                    at(self.last_seen + k.degraded_after);
+                }
+                at((self.last_seen + k.probe_after).max(self.last_probe + k.probe_after));
+            }
+        }
+        if let Some(p) = &self.pending_confirm {
+            at(p.deadline);
+        }
+        if let Some(t) = self.estop_resend_at() {
+            at(t);
+        }
+        if let Some(t) = self.sequence.as_ref().and_then(|s| s.next_deadline()) {
+            at(t);
+        }
+        if self
+            .last_status_fields
+            .as_ref()
+            .is_some_and(|f| f.contactors_mask != 0)
+        {
+            at(now + Duration::from_millis(BUDGET_CHECK_MS));
+        }
+        // a deadline that is already past must not spin the loop
+        next.max(now + Duration::from_millis(1))
+    }
+
+    /// All-open for the connected firmware (also what the fail-safe sends).
+    fn open_frame(&self) -> Frame {
+        contactors_command(self.last_status_fields.as_ref(), self.protocol_version, 0)
+    }
+
+    /// Polls, probes and keepalives: the device answers any paired frame with
+    /// a status and applies its mask, so send the one it should already have
+    /// (last commanded, else last reported).
+    fn hold_frame(&self) -> Frame {
+        let mask = self
+            .commanded_mask
+            .or(self.last_status_fields.as_ref().map(|f| f.contactors_mask))
+            .unwrap_or(0);
+        contactors_command(
+            self.last_status_fields.as_ref(),
+            self.protocol_version,
+            mask,
+        )
+    }
+
+    /// Runtime stopping: the firmware would keep its last mask otherwise.
+    fn open_before_release(&mut self) {
+        if self.port.is_none() {
+            return;
+        }
+        eprintln!(
+            "[LB] bank {}: opening contactors before release",
+            self.bank_id
+        );
+        let frame = self.open_frame();
+        self.send_tx(frame.as_bytes());
+    }
+
+    fn send_contactors(&mut self, mask: u16) {
+        let frame = contactors_command(
+            self.last_status_fields.as_ref(),
+            self.protocol_version,
+            mask,
+        );
+        self.commanded_mask = Some(mask);
+        self.send_tx(frame.as_bytes());
+    }
+}
+
+// Event driven: wakes on a command, a read, a finished connection attempt or
+// the next supervisor deadline (poll, keepalive, confirm, budget, scan).
+async fn run_worker(
+    mut w: Worker,
+    mut cmds: UnboundedReceiver<QueuedCmd>,
+    // dropped on return: tells `stop` the port is released
+    _done: mpsc::Sender<()>,
+) {
+    let mut connecting: Option<tokio::task::JoinHandle<ConnectOutcome>> = None;
+    let estop = w.estop.clone();
+    loop {
+        if connecting.is_none() {
+            if let Some(plan) = w.connect_plan() {
+                connecting = Some(tokio::task::spawn_blocking(move || plan.run()));
+            }
+        }
+        w.supervise();
+
+        let wake = tokio::time::Instant::from_std(w.next_wakeup(connecting.is_some()));
+        tokio::select! {
+            // emergency stop first, whatever is queued
+            biased;
+            _ = estop.notify.notified() => w.emergency_stop(),
+            cmd = cmds.recv() => {
+                // None: every sender is gone (state dropped)
+                let Some((queued_at, cmd)) = cmd else { break };
+                if !w.handle_cmd(queued_at, cmd) {
+                    break;
+                }
+            }
+            read = next_read(&mut w.port) => w.on_read(read),
+            outcome = async { connecting.as_mut().unwrap().await }, if connecting.is_some() => {
+                connecting = None;
+                match outcome {
+                    Ok(outcome) => w.finish_connect(outcome),
+                    Err(e) => eprintln!("[LB] connect task failed: {e}"),
+                }
+            }
+            _ = tokio::time::sleep_until(wake) => {}
+        }
+    }
+
+    w.open_before_release();
+    w.drop_port(Some("runtime stopped".into()));
+    // a running attempt holds its port until it returns
+    if let Some(job) = connecting {
+        let _ = job.await;
+    }
+    if let Some(reader) = w.released.take() {
+        let _ = tokio::task::spawn_blocking(move || reader.join()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{net::TcpListener, path::PathBuf};
+
+    const WAIT: Duration = Duration::from_secs(5);
+
+    struct TestHost {
+        sim: Arc<Mutex<SimDevice>>,
+        events: mpsc::Sender<WorkerEvent>,
+    }
+
+    impl WorkerHost for TestHost {
+        fn emit(&self, event: WorkerEvent) {
+            let _ = self.events.send(event);
+        }
+
+        fn sim_device(&self) -> Arc<Mutex<SimDevice>> {
+            self.sim.clone()
+        }
+    }
+
+    // a worker on its own current-thread runtime, as in lbctl
+    struct TestBank {
+        worker: WorkerHandle,
+        events: mpsc::Receiver<WorkerEvent>,
+        thread: Option<thread::JoinHandle<()>>,
+    }
+
+    impl TestBank {
+        fn start(cfg: WorkerConfig) -> Self {
+            let (tx, events) = mpsc::channel();
+            let host = TestHost {
+                sim: Arc::default(),
+                events: tx,
+            };
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_time()
+                .build()
+                .unwrap();
+            let (worker, task) = worker_task(Arc::new(host), cfg);
+            Self {
+                worker,
+                events,
+                thread: Some(thread::spawn(move || rt.block_on(task))),
+            }
+        }
+
+        fn next_event(&self, mut f: impl FnMut(&WorkerEvent) -> bool) -> WorkerEvent {
+            let deadline = Instant::now() + WAIT;
+            loop {
+                let left = deadline.saturating_duration_since(Instant::now());
+                let event = self.events.recv_timeout(left).expect("worker event");
+                if f(&event) {
+                    return event;
+                }
+            }
+        }
+
+        fn connected(&self) {
+            self.next_event(|e| matches!(e, WorkerEvent::Status(_)));
+        }
+
+        fn set_mask(&self, mask: u16) -> Result<LoadBankStatus, ContactorCmdError> {
+            let (reply, wait) = oneshot::channel();
+            self.worker
+                .send(RuntimeCmd::SetContactorsConfirmed {
+                    mask,
+                    timeout: Duration::from_millis(DEFAULT_CONFIRM_TIMEOUT_MS),
+                    retries: DEFAULT_CONFIRM_RETRIES,
+                    reply,
+                })
+                .unwrap();
+            wait.blocking_recv().unwrap()
+        }
+    }
+
+    impl Drop for TestBank {
+        fn drop(&mut self) {
+            self.worker.stop();
+            if let Some(thread) = self.thread.take() {
+                let _ = thread.join();
+            }
+        }
+    }
+
+    fn config(port_name: &str) -> WorkerConfig {
+        WorkerConfig::new("test", 115_200, RuntimeMode::from_port_name(port_name))
+    }
+
+    /// A simulated bank behind a TCP socket: a real link as far as the worker knows.
+    fn tcp_bank() -> String {
+        tcp_bank_stuck(0)
+    }
+
+    /// Same, with the contactors of `stuck_open` never closing.
+    fn tcp_bank_stuck(stuck_open: u16) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let Ok((mut sock, _)) = listener.accept() else {
+                return;
+            };
+            sock.set_read_timeout(Some(Duration::from_millis(5)))
+                .unwrap();
+            let mut dev = SimDevice::new();
+            let mut decoder = FrameDecoder::new();
+            let mut buf = [0u8; 256];
+            loop {
+                match sock.read(&mut buf) {
+                    Ok(0) => return,
+                    Ok(n) => decoder.push(&buf[..n]),
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(e) if e.kind() == io::ErrorKind::TimedOut => {}
+                    Err(_) => return,
+                }
+                while let Some(frame) = decoder.next_frame() {
+                    let mut fields = frame.decode().unwrap();
+                    fields.contactors_mask &= !stuck_open;
+                    dev.receive(fields.encode().as_bytes(), Instant::now());
+                }
+                dev.tick(Instant::now());
+                if sock.write_all(&dev.take_output()).is_err() {
+                    return;
+                }
+            }
+        });
+        format!("{TCP_SCHEME}{addr}")
+    }
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"))
+    }
+
+    fn temp_db(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("lb-worker-{name}-{}.sqlite3", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    // after everything queued before it; `from` is a table, optionally filtered
+    fn count_rows(events: &EventLogSink, from: &'static str) -> i64 {
+        let (tx, rx) = mpsc::channel();
+        events.with_conn(move |conn| {
+            let n = conn.and_then(|c| {
+                c.query_row(&format!("SELECT COUNT(*) FROM {from}"), [], |r| r.get(0))
+                    .map_err(|e| e.to_string())
+            });
+            let _ = tx.send(n);
+        });
+        rx.recv_timeout(WAIT).unwrap().unwrap()
+    }
+
+    // closes R1+R2 for a moment, then opens everything again
+    fn switch_once(bank: &TestBank) {
+        bank.connected();
+        assert_eq!(bank.set_mask(0x0003).unwrap().contactors_mask, 0x0003);
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(bank.set_mask(0).unwrap().contactors_mask, 0);
+    }
+
+    // a simulated and a replayed session, both switching R1+R2
+    fn run_sim_and_replay(events: &EventLogSink) {
+        let bank = TestBank::start(WorkerConfig {
+            events: events.clone(),
+            ..config("sim")
+        });
+        switch_once(&bank);
+        drop(bank);
+
+        let replay = format!("{REPLAY_SCHEME}{}?speed=20", fixture("clean_session.jsonl"));
+        let bank = TestBank::start(WorkerConfig {
+            events: events.clone(),
+            ..config(&replay)
+        });
+        bank.next_event(|e| {
+            matches!(e, WorkerEvent::Health(h) if h.reason.as_deref() == Some("replay finished"))
+        });
+    }
+
+    fn run_tcp(events: &EventLogSink) {
+        let bank = TestBank::start(WorkerConfig {
+            events: events.clone(),
+            ..config(&tcp_bank())
+        });
+        switch_once(&bank);
+    }
+
+    #[test]
+    fn wear_counts_real_links_only() {
+        let path = temp_db("wear");
+        let events = EventLogSink::open(path.clone());
+
+        run_sim_and_replay(&events);
+        assert_eq!(count_rows(&events, "lb_contactor_wear"), 0);
+        run_tcp(&events);
+        // one cycle each for R1 and R2
+        assert_eq!(count_rows(&events, "lb_contactor_wear"), 2);
+
+        drop(events);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn event_log_keeps_real_links_only() {
+        let path = temp_db("events");
+        let events = EventLogSink::open(path.clone());
+
+        run_sim_and_replay(&events);
+        assert_eq!(count_rows(&events, "lb_events"), 0);
+        run_tcp(&events);
+        assert!(count_rows(&events, "lb_events") > 0);
+
+        drop(events);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn refusals_are_logged() {
+        let path = temp_db("interlocks");
+        let events = EventLogSink::open(path.clone());
+        let bank = TestBank::start(WorkerConfig {
+            events: events.clone(),
+            ..config(&tcp_bank())
+        });
+        bank.connected();
+        assert!(matches!(
+            bank.set_mask(0x0100),
+            Err(ContactorCmdError::Interlock {
+                interlock: Interlock::UnwiredContactors { .. },
+                ..
+            })
+        ));
+        assert_eq!(
+            count_rows(
+                &events,
+                "lb_events WHERE kind = 'interlock' AND code = 'unwiredContactors' \
+                 AND state = 'refused' AND mask = 256"
+            ),
+            1
+        );
+
+        drop(bank);
+        drop(events);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn confirm_fails_with_the_reported_mask() {
+        // R2 never closes
+        let bank = TestBank::start(config(&tcp_bank_stuck(0x0002)));
+        bank.connected();
+        let t0 = Instant::now();
+        let err = bank.set_mask(0x0003).unwrap_err();
+        // every attempt waited out its deadline despite the early statuses
+        let attempts = DEFAULT_CONFIRM_RETRIES + 1;
+        assert!(t0.elapsed() >= Duration::from_millis(DEFAULT_CONFIRM_TIMEOUT_MS) * attempts);
+        assert!(matches!(
+            err,
+            ContactorCmdError::Mismatch {
+                requested: 0x0003,
+                reported: 0x0001,
+                attempts: a,
+                ..
+            } if a == attempts
+        ));
+
+        // what the bank can follow still confirms
+        assert_eq!(bank.set_mask(0x0001).unwrap().contactors_mask, 0x0001);
+    }
+
+    #[test]
+    fn budget_spends_on_real_links_only() {
+        let budget = Arc::new(Mutex::new(DutyCycleBudget::new(RDP4000)));
+        let used = |budget: &Arc<Mutex<DutyCycleBudget>>| {
+            budget.lock().unwrap().used_on_ms(0x0001, Instant::now())
+        };
+
+        let bank = TestBank::start(WorkerConfig {
+            budget: budget.clone(),
+            ..config("sim")
+        });
+        switch_once(&bank);
+        drop(bank);
+        assert_eq!(used(&budget), 0.0);
+
+        let bank = TestBank::start(WorkerConfig {
+            budget: budget.clone(),
+            ..config(&tcp_bank())
+        });
+        switch_once(&bank);
+        assert!(used(&budget) > 0.0);
+    }
+
+    #[test]
+    fn reset_queued_before_a_retrip_keeps_the_latch() {
+        let cfg = config("sim");
+        let latch = cfg.estop.clone();
+        let bank = TestBank::start(cfg);
+        bank.connected();
+        fn estop_state(state: EstopState) -> impl Fn(&WorkerEvent) -> bool {
+            move |e| matches!(e, WorkerEvent::Estop(ev) if ev.state == state)
+        }
+
+        latch.trip("first");
+        bank.next_event(estop_state(EstopState::Confirmed));
+
+        // reset, then tripped again before the worker got to the reset
+        assert!(latch.release());
+        latch.trip("second");
+        bank.worker.send(RuntimeCmd::ResetEstop).unwrap();
+        assert!(matches!(
+            bank.set_mask(0x0001),
+            Err(ContactorCmdError::Interlock {
+                interlock: Interlock::EmergencyStop,
+                ..
+            })
+        ));
+        assert!(!bank
+            .events
+            .try_iter()
+            .any(|e| estop_state(EstopState::Released)(&e)));
+        assert!(bank.worker.shared.lock().unwrap().estop.is_some());
+
+        assert!(latch.release());
+        bank.worker.send(RuntimeCmd::ResetEstop).unwrap();
+        bank.next_event(estop_state(EstopState::Released));
+        assert_eq!(bank.set_mask(0x0001).unwrap().contactors_mask, 0x0001);
+    }
+}
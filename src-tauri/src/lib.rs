@@ -1,30 +1,117 @@
+#[cfg(feature = "gui")]
 use business::{list_process, max_memory, max_runtime};
+#[cfg(feature = "gui")]
 use clock::start_clock;
+#[cfg(feature = "gui")]
 use does_it_talk::{
     close, connect, list_ports, test_roundtrip_bytes, test_roundtrip_text, SerialState,
 };
+#[cfg(feature = "gui")]
 use export_xlsx::{export_xlsx, parse_xlsx_from_dialog, parse_xlsx_path, pick_xlsx_path};
+#[cfg(feature = "gui")]
 use import::read_file_to_string;
+#[cfg(feature = "gui")]
 use import_tool_cal_files::parse_tool_calibration;
+#[cfg(feature = "gui")]
+use lb_capture::{
+    lb_capture_export, lb_capture_list, lb_capture_start, lb_capture_status, lb_capture_stop,
+    LoadBankCaptureState,
+};
+#[cfg(feature = "gui")]
+use lb_discovery::{
+    lb_forget_known_host, lb_get_discovery_policy, lb_list_known_hosts, lb_set_discovery_policy,
+    LoadBankDiscoveryState,
+};
+#[cfg(feature = "gui")]
+use lb_eventlog::{
+    lb_eventlog_export_xlsx, lb_eventlog_info, lb_eventlog_query, lb_eventlog_set_station,
+    LoadBankEventLogState,
+};
+#[cfg(feature = "gui")]
+use lb_failsafe::install_panic_hook;
+#[cfg(feature = "gui")]
+use lb_link::list_ports_detailed;
+#[cfg(feature = "gui")]
+use lb_replay::lb_replay_check;
+#[cfg(feature = "gui")]
 use lb_runtime::{
-    lb_set_contactors, lb_set_polling, lb_start_polling, lb_stop_polling, lb_write_bytes,
-    list_ports_detailed, LoadBankRuntimeState,
+    lb_aggregate_status, lb_emergency_stop, lb_get_budget, lb_get_safety, lb_get_state,
+    lb_reset_estop, lb_scan_all_ports, lb_set_contactors, lb_set_contactors_combined,
+    lb_set_contactors_confirmed, lb_set_keepalive, lb_set_polling, lb_set_safety, lb_start_polling,
+    lb_stop_polling, lb_write_bytes, LoadBankRuntimeState,
+};
+#[cfg(feature = "gui")]
+use lb_sequence::{
+    lb_sequence_abort, lb_sequence_pause, lb_sequence_resume, lb_sequence_start, lb_sequence_status,
 };
+#[cfg(feature = "gui")]
+use lb_serial::{
+    lb_forget_serial_config, lb_get_serial_config, lb_list_serial_configs, lb_set_serial_config,
+    LoadBankSerialState,
+};
+#[cfg(feature = "gui")]
+use lb_sim::{lb_sim_reset, lb_sim_set_faults, LoadBankSimState};
+#[cfg(feature = "gui")]
+use lb_wear::{
+    lb_get_wear_thresholds, lb_maintenance_report, lb_reset_contactor_wear, lb_set_wear_thresholds,
+};
+#[cfg(feature = "gui")]
+use load_model::{lb_explain_mask, lb_resolve_setpoint};
+#[cfg(feature = "gui")]
+use port_watch::start_port_watch;
+#[cfg(feature = "gui")]
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(feature = "gui")]
 use std::sync::Mutex;
+#[cfg(feature = "gui")]
+use tauri::{Manager, RunEvent};
+#[cfg(feature = "gui")]
 use upload_tool_cal_files::upload_calibration_file;
 
+#[cfg(feature = "gui")]
 mod business;
+#[cfg(feature = "gui")]
 mod clock;
 mod data_structures;
 mod does_it_talk;
+#[cfg(feature = "gui")]
 mod export_xlsx;
+#[cfg(feature = "gui")]
 mod import;
+#[cfg(feature = "gui")]
 mod import_tool_cal_files;
+mod lb_budget;
+mod lb_capture;
+mod lb_cli;
+#[cfg(feature = "gui")]
+mod lb_discovery;
+mod lb_eventlog;
+mod lb_failsafe;
+mod lb_faults;
+mod lb_link;
+mod lb_link_stats;
+mod lb_protocol;
+mod lb_replay;
+#[cfg(feature = "gui")]
 mod lb_runtime;
+mod lb_safety;
+mod lb_sequence;
+mod lb_serial;
+mod lb_sim;
+mod lb_transport;
+mod lb_wear;
+mod lb_worker;
+mod load_model;
+#[cfg(feature = "gui")]
+mod port_watch;
+#[cfg(feature = "gui")]
 mod upload_tool_cal_files;
 
+#[cfg(feature = "gui")]
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    // ExitRequested is followed by Exit
+    let shut_down = AtomicBool::new(false);
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
@@ -33,8 +120,15 @@ pub fn run() {
             port: Mutex::new(None),
         })
         .manage(LoadBankRuntimeState::default())
+        .manage(LoadBankSimState::default())
+        .manage(LoadBankCaptureState::default())
+        .manage(LoadBankEventLogState::default())
+        .manage(LoadBankSerialState::default())
+        .manage(LoadBankDiscoveryState::default())
         .setup(|app| {
             start_clock(app.handle().clone());
+            start_port_watch(app.handle().clone());
+            install_panic_hook(app.state::<LoadBankRuntimeState>().failsafe());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -54,8 +148,51 @@ pub fn run() {
             lb_stop_polling,
             lb_set_polling,
             lb_write_bytes,
+            lb_get_state,
+            lb_sequence_start,
+            lb_sequence_pause,
+            lb_sequence_resume,
+            lb_sequence_abort,
+            lb_sequence_status,
             list_ports_detailed,
+            lb_get_serial_config,
+            lb_list_serial_configs,
+            lb_set_serial_config,
+            lb_forget_serial_config,
+            lb_scan_all_ports,
+            lb_get_discovery_policy,
+            lb_set_discovery_policy,
+            lb_list_known_hosts,
+            lb_forget_known_host,
             lb_set_contactors,
+            lb_set_contactors_confirmed,
+            lb_set_keepalive,
+            lb_aggregate_status,
+            lb_set_contactors_combined,
+            lb_set_safety,
+            lb_get_safety,
+            lb_emergency_stop,
+            lb_reset_estop,
+            lb_resolve_setpoint,
+            lb_explain_mask,
+            lb_get_budget,
+            lb_capture_start,
+            lb_capture_stop,
+            lb_capture_status,
+            lb_capture_list,
+            lb_capture_export,
+            lb_replay_check,
+            lb_eventlog_info,
+            lb_eventlog_set_station,
+            lb_eventlog_query,
+            lb_eventlog_export_xlsx,
+            lb_maintenance_report,
+            lb_get_wear_thresholds,
+            lb_set_wear_thresholds,
+            lb_reset_contactor_wear,
+            // simulated load bank (dev)
+            lb_sim_set_faults,
+            lb_sim_reset,
             // import/export files
             read_file_to_string,
             pick_xlsx_path,
@@ -66,6 +203,20 @@ pub fn run() {
             parse_tool_calibration,
             upload_calibration_file
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(move |app, event| match event {
+            // leave the banks open, whatever the webview was doing
+            RunEvent::ExitRequested { .. } | RunEvent::Exit => {
+                if !shut_down.swap(true, Ordering::SeqCst) {
+                    app.state::<LoadBankRuntimeState>().shutdown("app closing");
+                }
+            }
+            _ => {}
+        });
+}
+
+/// Headless diagnostics (`lbctl`), no webview.
+pub fn run_cli() -> std::process::ExitCode {
+    lb_cli::run(std::env::args().skip(1).collect())
 }
@@ -0,0 +1,686 @@
+// Resistor network model of the load bank: which R1..R8 branches give a target
+// current at the IEC U2 of the process.
+//
+// Port of src/services/utils/setpoints.ts (`resolveLoadBankSetpoint`) and the
+// tables in src/types/calibrationTypes.ts (`LB_BRANCHES`, `RDP4000`). Keep both
+// sides in sync: the UI shows the candidates, the backend validates the masks.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+#[cfg(feature = "gui")]
+use std::time::Instant;
+#[cfg(feature = "gui")]
+use tauri::State;
+
+#[cfg(feature = "gui")]
+use crate::lb_runtime::{bank_key, LoadBankRuntimeState};
+
+// -----------------------------------------------------------------------------
+// Tables
+// -----------------------------------------------------------------------------
+
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Branch {
+    pub id: &'static str,
+    pub ohm: f64,
+    /// Bit in `contactorsMask` that closes this branch.
+    pub mask_bit: u16,
+    pub tunnel: usize,
+}
+
+pub const LB_BRANCHES: [Branch; 8] = [
+    Branch {
+        id: "R1",
+        ohm: 4.28,
+        mask_bit: 1 << 0,
+        tunnel: 0,
+    },
+    Branch {
+        id: "R2",
+        ohm: 2.0,
+        mask_bit: 1 << 1,
+        tunnel: 0,
+    },
+    Branch {
+        id: "R3",
+        ohm: 1.0,
+        mask_bit: 1 << 2,
+        tunnel: 1,
+    },
+    Branch {
+        id: "R4",
+        ohm: 0.5,
+        mask_bit: 1 << 3,
+        tunnel: 1,
+    },
+    Branch {
+        id: "R5",
+        ohm: 0.36,
+        mask_bit: 1 << 4,
+        tunnel: 2,
+    },
+    Branch {
+        id: "R6",
+        ohm: 0.36,
+        mask_bit: 1 << 5,
+        tunnel: 2,
+    },
+    Branch {
+        id: "R7",
+        ohm: 0.23,
+        mask_bit: 1 << 6,
+        tunnel: 3,
+    },
+    Branch {
+        id: "R8",
+        ohm: 0.23,
+        mask_bit: 1 << 7,
+        tunnel: 3,
+    },
+];
+
+const TUNNELS: usize = 4;
+
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct OverloadWindow {
+    /// Multiple of `p_r_w` allowed.
+    pub factor_max: f64,
+    /// Max ON time at that factor.
+    pub t_on_max_ms: f64,
+    /// Reference cycle.
+    pub cycle_ms: f64,
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ResistorSpec {
+    pub p_r_w: f64,
+    pub surface_tmax_c: f64,
+    pub overload_windows: &'static [OverloadWindow],
+}
+
+pub const RDP4000: ResistorSpec = ResistorSpec {
+    p_r_w: 4000.0,
+    surface_tmax_c: 450.0,
+    overload_windows: &[
+        OverloadWindow {
+            factor_max: 7.5,
+            t_on_max_ms: 5000.0,
+            cycle_ms: 120000.0,
+        },
+        OverloadWindow {
+            factor_max: 5.0,
+            t_on_max_ms: 10000.0,
+            cycle_ms: 120000.0,
+        },
+        OverloadWindow {
+            factor_max: 2.8,
+            t_on_max_ms: 20000.0,
+            cycle_ms: 120000.0,
+        },
+        OverloadWindow {
+            factor_max: 1.7,
+            t_on_max_ms: 40000.0,
+            cycle_ms: 120000.0,
+        },
+    ],
+};
+
+// -----------------------------------------------------------------------------
+// Config (same values as setpoints.ts)
+// -----------------------------------------------------------------------------
+
+// expected "measurement on" time for a setpoint burn (used for overload feasibility)
+const MEAS_PULSE_MS: f64 = 5000.0;
+
+// tunnel cooling: 2 resistors per tunnel + fan; balance target (not a hard limit)
+const TUNNEL_CONT_KW: f64 = 8.0;
+
+// Error preference: best is around -0.1% (slightly under target); near-zero
+// negatives count as positive-ish so 0.0% doesn't win over a small negative.
+const TARGET_UNDER: f64 = -0.001;
+const ZERO_AS_POS: f64 = 0.0005;
+const POS_FINE_MAX: f64 = 0.001;
+
+/// Default tolerance of `resolveLoadBankSetpoint`.
+pub const DEFAULT_MAX_REL_ERROR: f64 = 0.15;
+
+const DEFAULT_CYCLE_MS: f64 = 120000.0;
+
+// -----------------------------------------------------------------------------
+// Network math
+// -----------------------------------------------------------------------------
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Process {
+    #[serde(rename = "MMA")]
+    Mma,
+    #[serde(rename = "TIG")]
+    Tig,
+    #[serde(rename = "MIGInv")]
+    MigInv,
+    #[serde(rename = "MIGConv")]
+    MigConv,
+}
+
+/// IEC 60974-1 conventional load voltage for `i2` (clamped to the process range).
+pub fn calc_u2(process: Process, i2: f64) -> f64 {
+    let i = i2.max(5.0);
+    match process {
+        Process::Mma => (0.04 * i + 20.0).clamp(20.0, 44.0),
+        Process::Tig => (0.04 * i + 10.0).clamp(10.0, 34.0),
+        Process::MigConv | Process::MigInv => (0.05 * i + 14.0).clamp(14.0, 44.0),
+    }
+}
+
+/// Conventional load voltage across `mask`: the U2 at which the current it
+/// draws (U2 / Req) sits on the load line of `process`. None when nothing is
+/// closed.
+pub fn mask_u2(process: Process, mask: u16) -> Option<f64> {
+    let r = equivalent_ohm(mask)?;
+    // calc_u2 is clamped to 10..44 V, so the crossing lies between 0 and 100 V
+    let (mut lo, mut hi) = (0.0, 100.0);
+    for _ in 0..40 {
+        let u: f64 = (lo + hi) / 2.0;
+        if calc_u2(process, u / r) > u {
+            lo = u;
+        } else {
+            hi = u;
+        }
+    }
+    Some((lo + hi) / 2.0)
+}
+
+/// Contactors that close a modelled branch.
+pub fn wired_mask() -> u16 {
+    LB_BRANCHES.iter().fold(0, |m, b| m | b.mask_bit)
+}
+
+pub fn branches_in(mask: u16) -> impl Iterator<Item = &'static Branch> {
+    LB_BRANCHES.iter().filter(move |b| mask & b.mask_bit != 0)
+}
+
+/// Parallel resistance of the closed branches (None when nothing is closed).
+pub fn equivalent_ohm(mask: u16) -> Option<f64> {
+    let siemens: f64 = branches_in(mask).map(|b| 1.0 / b.ohm).sum();
+    (siemens > 0.0).then(|| 1.0 / siemens)
+}
+
+/// Total power of `mask` at `u2_v`.
+pub fn power_w(mask: u16, u2_v: f64) -> f64 {
+    equivalent_ohm(mask).map_or(0.0, |r| u2_v * u2_v / r)
+}
+
+/// Human readable view of a mask (used in logs and by the UI).
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct MaskLoad {
+    pub mask: u16,
+    pub branches: Vec<&'static str>,
+    pub req_ohm: Option<f64>,
+    pub u2_v: f64,
+    pub current_a: f64,
+    pub power_w: f64,
+}
+
+pub fn explain_mask(mask: u16, u2_v: f64) -> MaskLoad {
+    let req_ohm = equivalent_ohm(mask);
+    MaskLoad {
+        mask,
+        branches: branches_in(mask).map(|b| b.id).collect(),
+        req_ohm,
+        u2_v,
+        current_a: req_ohm.map_or(0.0, |r| u2_v / r),
+        power_w: power_w(mask, u2_v),
+    }
+}
+
+impl MaskLoad {
+    pub fn label(&self) -> String {
+        match self.req_ohm {
+            None => "all open".into(),
+            Some(r) => format!(
+                "{} (Req {:.4} Ω, ~{:.0} A / {:.1} kW @ {:.1} V)",
+                self.branches.join("+"),
+                r,
+                self.current_a,
+                self.power_w / 1000.0,
+                self.u2_v
+            ),
+        }
+    }
+}
+
+// -----------------------------------------------------------------------------
+// Thermal feasibility
+// -----------------------------------------------------------------------------
+
+/// Allowed ON time for one branch at `p_w`:
+/// `Some(None)` = continuous, `Some(Some(ms))` = time-limited, `None` = impossible.
+pub fn max_allowed_on_ms(p_w: f64, spec: &ResistorSpec) -> Option<Option<f64>> {
+    let factor = p_w / spec.p_r_w;
+    if factor <= 1.0 {
+        return Some(None);
+    }
+    spec.overload_windows
+        .iter()
+        .filter(|w| factor <= w.factor_max)
+        .map(|w| w.t_on_max_ms)
+        .reduce(f64::max)
+        .map(Some)
+}
+
+struct Feasibility {
+    ok: bool,
+    max_on_ms: Option<f64>,
+    max_branch_kw: f64,
+    max_branch_factor: f64,
+    max_tunnel_kw: f64,
+    used_on_ms_max: f64,
+}
+
+fn check_pulse(
+    mask: u16,
+    u2_v: f64,
+    spec: &ResistorSpec,
+    pulse_ms: f64,
+    used_on_ms: &dyn Fn(u16) -> f64,
+) -> Feasibility {
+    let mut feas = Feasibility {
+        ok: true,
+        max_on_ms: None,
+        max_branch_kw: 0.0,
+        max_branch_factor: 0.0,
+        max_tunnel_kw: 0.0,
+        used_on_ms_max: 0.0,
+    };
+    let mut tunnel_kw = [0.0; TUNNELS];
+
+    for b in branches_in(mask) {
+        let p_w = u2_v * u2_v / b.ohm;
+        feas.max_branch_kw = feas.max_branch_kw.max(p_w / 1000.0);
+        feas.max_branch_factor = feas.max_branch_factor.max(p_w / spec.p_r_w);
+        tunnel_kw[b.tunnel] += p_w / 1000.0;
+
+        let Some(allowed) = max_allowed_on_ms(p_w, spec) else {
+            feas.ok = false;
+            break;
+        };
+
+        let used = used_on_ms(b.mask_bit);
+        feas.used_on_ms_max = feas.used_on_ms_max.max(used);
+
+        // remaining budget in the rolling cycle; combo limit = min over branches
+        if let Some(allowed) = allowed {
+            let remaining = (allowed - used).max(0.0);
+            feas.max_on_ms = Some(feas.max_on_ms.map_or(remaining, |m| m.min(remaining)));
+        }
+    }
+
+    feas.max_tunnel_kw = tunnel_kw.iter().copied().fold(0.0, f64::max);
+    // Hard gate: the planned pulse must fit in the remaining budget
+    if feas.max_on_ms.is_some_and(|m| pulse_ms > m) {
+        feas.ok = false;
+    }
+    feas
+}
+
+// -----------------------------------------------------------------------------
+// Ranking
+// -----------------------------------------------------------------------------
+
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ComboCandidate {
+    pub mask: u16,
+    pub branches: Vec<&'static str>,
+    pub req_ohm: f64,
+
+    pub u2_v: f64,
+    pub approx_current_a: f64,
+
+    /// Signed current error: (I_actual - I_target) / I_target
+    pub err_i: f64,
+    pub abs_err_i: f64,
+    /// Signed resistance error: (R_req - R_target) / R_target (what we rank on)
+    pub err_r: f64,
+    pub abs_err_r: f64,
+
+    pub score: f64,
+    pub max_branch_kw: f64,
+    /// Max P_branch / P_R
+    pub max_branch_factor: f64,
+    pub max_tunnel_kw: f64,
+
+    /// Min remaining ON time among branches (None = continuous).
+    pub max_on_ms: Option<f64>,
+    pub cycle_ms: f64,
+    pub used_on_ms_max: f64,
+
+    /// Warning only, not a selection gate.
+    pub out_of_tolerance: bool,
+}
+
+fn score_combo(
+    branch_count: usize,
+    abs_err_r: f64,
+    max_branch_factor: f64,
+    max_tunnel_kw: f64,
+) -> f64 {
+    let w_e = 1.0; // resistance error (only after the preference comparator ties)
+    let w_p = 0.5; // overload above continuous
+    let w_t = 0.2; // tunnel imbalance
+    let w_b = 0.05; // branch count
+
+    let overload_penalty = (max_branch_factor - 1.0).max(0.0);
+    let tunnel_penalty = max_tunnel_kw / TUNNEL_CONT_KW;
+
+    w_e * abs_err_r + w_p * overload_penalty + w_t * tunnel_penalty + w_b * branch_count as f64
+}
+
+// 0: true negatives (preferred), 1: near-zero / small positive, 2: other positive
+fn bucket_err_r(err_r: f64) -> u8 {
+    if err_r < -ZERO_AS_POS {
+        0
+    } else if err_r <= POS_FINE_MAX {
+        1
+    } else {
+        2
+    }
+}
+
+fn pref_cost_err_r(err_r: f64) -> f64 {
+    match bucket_err_r(err_r) {
+        0 => (err_r - TARGET_UNDER).abs(), // closest to -0.1%
+        1 => err_r.abs(),                  // closest to 0.0%
+        _ => err_r,                        // smallest positive
+    }
+}
+
+fn cmp_f64_eps(a: f64, b: f64) -> Ordering {
+    if (a - b).abs() > 1e-12 {
+        a.total_cmp(&b)
+    } else {
+        Ordering::Equal
+    }
+}
+
+fn compare_candidates(a: &ComboCandidate, b: &ComboCandidate) -> Ordering {
+    // 1) within tolerance first (soft rule, not a filter)
+    a.out_of_tolerance
+        .cmp(&b.out_of_tolerance)
+        // 2) bucket + preference on errR
+        .then(bucket_err_r(a.err_r).cmp(&bucket_err_r(b.err_r)))
+        .then(cmp_f64_eps(
+            pref_cost_err_r(a.err_r),
+            pref_cost_err_r(b.err_r),
+        ))
+        // 3) thermal / balance / branch penalties
+        .then(cmp_f64_eps(a.score, b.score))
+        // 4) tie-breaks
+        .then(a.branches.len().cmp(&b.branches.len()))
+        .then(a.abs_err_r.total_cmp(&b.abs_err_r))
+}
+
+/// Every thermally feasible combo for `target_a`, best first.
+/// `used_on_ms(mask_bit)` is the ON time a branch already spent in the current cycle.
+pub fn rank_combos(
+    process: Process,
+    target_a: f64,
+    max_rel_error: f64,
+    used_on_ms: &dyn Fn(u16) -> f64,
+) -> Vec<ComboCandidate> {
+    if !target_a.is_finite() || target_a <= 0.0 {
+        return Vec::new();
+    }
+
+    let u2_v = calc_u2(process, target_a);
+    let r_target = u2_v / target_a;
+    let cycle_ms = RDP4000
+        .overload_windows
+        .first()
+        .map_or(DEFAULT_CYCLE_MS, |w| w.cycle_ms);
+
+    let mut candidates: Vec<ComboCandidate> = (1..(1u16 << LB_BRANCHES.len()))
+        .filter_map(|index| {
+            // combo index -> contactor mask (bit i of index = LB_BRANCHES[i])
+            let mask = LB_BRANCHES
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| index & (1 << i) != 0)
+                .fold(0u16, |m, (_, b)| m | b.mask_bit);
+            let req_ohm = equivalent_ohm(mask)?;
+            let approx_current_a = u2_v / req_ohm;
+
+            let err_r = (req_ohm - r_target) / r_target;
+            let err_i = (approx_current_a - target_a) / target_a;
+
+            let feas = check_pulse(mask, u2_v, &RDP4000, MEAS_PULSE_MS, used_on_ms);
+            if !feas.ok {
+                return None;
+            }
+
+            let branches: Vec<&'static str> = branches_in(mask).map(|b| b.id).collect();
+            let score = score_combo(
+                branches.len(),
+                err_r.abs(),
+                feas.max_branch_factor,
+                feas.max_tunnel_kw,
+            );
+
+            Some(ComboCandidate {
+                mask,
+                branches,
+                req_ohm,
+                u2_v,
+                approx_current_a,
+                err_i,
+                abs_err_i: err_i.abs(),
+                err_r,
+                abs_err_r: err_r.abs(),
+                score,
+                max_branch_kw: feas.max_branch_kw,
+                max_branch_factor: feas.max_branch_factor,
+                max_tunnel_kw: feas.max_tunnel_kw,
+                max_on_ms: feas.max_on_ms,
+                cycle_ms,
+                used_on_ms_max: feas.used_on_ms_max,
+                out_of_tolerance: err_r.abs() > max_rel_error,
+            })
+        })
+        .collect();
+
+    candidates.sort_by(compare_candidates);
+    candidates
+}
+
+// -----------------------------------------------------------------------------
+// Commands
+// -----------------------------------------------------------------------------
+
+#[cfg(feature = "gui")]
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SetpointResolution {
+    pub process: Process,
+    pub current_a: f64,
+    pub u2_v: f64,
+    pub r_target_ohm: f64,
+    /// Best first; empty when no combo is feasible.
+    pub candidates: Vec<ComboCandidate>,
+}
+
+/// Backend equivalent of `resolveLoadBankSetpoint` (returns the top `limit` combos).
+/// Thermal feasibility uses the duty-cycle budget of `bank_id`.
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_resolve_setpoint(
+    state: State<LoadBankRuntimeState>,
+    bank_id: Option<String>,
+    process: Process,
+    current_a: f64,
+    max_rel_error: Option<f64>,
+    limit: Option<usize>,
+) -> Result<SetpointResolution, String> {
+    if !current_a.is_finite() || current_a <= 0.0 {
+        return Err(format!("invalid current: {current_a}"));
+    }
+    let u2_v = calc_u2(process, current_a);
+    let budget = state.budget(&bank_key(bank_id));
+    let budget = budget.lock().unwrap();
+    let now = Instant::now();
+    let mut candidates = rank_combos(
+        process,
+        current_a,
+        max_rel_error.unwrap_or(DEFAULT_MAX_REL_ERROR),
+        &|bit| budget.used_on_ms(bit, now),
+    );
+    candidates.truncate(limit.unwrap_or(1).max(1));
+
+    Ok(SetpointResolution {
+        process,
+        current_a,
+        u2_v,
+        r_target_ohm: u2_v / current_a,
+        candidates,
+    })
+}
+
+/// What a mask does at `u2_v` (branches, Req, current, power).
+#[cfg(feature = "gui")]
+#[tauri::command]
+pub fn lb_explain_mask(mask: u16, u2_v: f64) -> MaskLoad {
+    explain_mask(mask, u2_v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-6, "{a} != {b}");
+    }
+
+    #[test]
+    fn tables_match_calibration_types() {
+        // LB_BRANCHES / RDP4000 in src/types/calibrationTypes.ts
+        let ts = [
+            ("R1", 4.28, 0),
+            ("R2", 2.0, 0),
+            ("R3", 1.0, 1),
+            ("R4", 0.5, 1),
+            ("R5", 0.36, 2),
+            ("R6", 0.36, 2),
+            ("R7", 0.23, 3),
+            ("R8", 0.23, 3),
+        ];
+        for (i, (b, (id, ohm, tunnel))) in LB_BRANCHES.iter().zip(ts).enumerate() {
+            assert_eq!(
+                (b.id, b.ohm, b.mask_bit, b.tunnel),
+                (id, ohm, 1 << i, tunnel)
+            );
+        }
+        let windows: Vec<_> = RDP4000
+            .overload_windows
+            .iter()
+            .map(|w| (w.factor_max, w.t_on_max_ms, w.cycle_ms))
+            .collect();
+        assert_eq!(
+            windows,
+            [
+                (7.5, 5000.0, 120000.0),
+                (5.0, 10000.0, 120000.0),
+                (2.8, 20000.0, 120000.0),
+                (1.7, 40000.0, 120000.0),
+            ]
+        );
+        assert_eq!(RDP4000.p_r_w, 4000.0);
+    }
+
+    #[test]
+    fn branches_follow_the_mask() {
+        assert_eq!(wired_mask(), 0x00FF);
+        assert_eq!(branches_in(0).count(), 0);
+        let ids: Vec<_> = branches_in(0x0005).map(|b| b.id).collect();
+        assert_eq!(ids, ["R1", "R3"]);
+        // C9..C16 close nothing modelled
+        let ids: Vec<_> = branches_in(0xFF80).map(|b| b.id).collect();
+        assert_eq!(ids, ["R8"]);
+    }
+
+    #[test]
+    fn mask_maps_to_resistance_and_current() {
+        assert_eq!(equivalent_ohm(0), None);
+        assert_eq!(equivalent_ohm(0xFF00), None);
+        assert_close(equivalent_ohm(0x0008).unwrap(), 0.5);
+        // R1 || R2
+        assert_close(
+            equivalent_ohm(0x0003).unwrap(),
+            1.0 / (1.0 / 4.28 + 1.0 / 2.0),
+        );
+        // R7 || R8
+        assert_close(equivalent_ohm(0x00C0).unwrap(), 0.115);
+
+        let load = explain_mask(0x000C, 30.0);
+        assert_eq!(load.branches, ["R3", "R4"]);
+        assert_close(load.req_ohm.unwrap(), 1.0 / 3.0);
+        assert_close(load.current_a, 90.0);
+        assert_close(load.power_w, 2700.0);
+        assert_eq!(explain_mask(0, 30.0).label(), "all open");
+        assert_eq!(power_w(0, 30.0), 0.0);
+    }
+
+    #[test]
+    fn u2_follows_the_iec_load_lines() {
+        // calcU2 in src/services/utils/setpoints.ts
+        assert_close(calc_u2(Process::Mma, 100.0), 24.0);
+        assert_close(calc_u2(Process::Mma, 0.0), 20.2);
+        assert_close(calc_u2(Process::Mma, 1000.0), 44.0);
+        assert_close(calc_u2(Process::Tig, 100.0), 14.0);
+        assert_close(calc_u2(Process::Tig, 700.0), 34.0);
+        assert_close(calc_u2(Process::MigInv, 200.0), 24.0);
+        assert_close(calc_u2(Process::MigConv, 200.0), 24.0);
+        assert_close(calc_u2(Process::MigConv, 1000.0), 44.0);
+
+        // R4 on the MMA line: U = 0.04 * U / 0.5 + 20
+        assert_close(mask_u2(Process::Mma, 0x0008).unwrap(), 20.0 / 0.92);
+        // R1 draws under 5 A at the bottom of the TIG line
+        assert_close(mask_u2(Process::Tig, 0x0001).unwrap(), 10.2);
+        assert_eq!(mask_u2(Process::Mma, 0), None);
+    }
+
+    #[test]
+    fn overload_windows_limit_on_time() {
+        // maxAllowedOnMsForBranch in src/services/utils/setpoints.ts
+        assert_eq!(max_allowed_on_ms(4000.0, &RDP4000), Some(None));
+        assert_eq!(max_allowed_on_ms(6000.0, &RDP4000), Some(Some(40000.0)));
+        assert_eq!(max_allowed_on_ms(11200.0, &RDP4000), Some(Some(20000.0)));
+        assert_eq!(max_allowed_on_ms(20000.0, &RDP4000), Some(Some(10000.0)));
+        assert_eq!(max_allowed_on_ms(30000.0, &RDP4000), Some(Some(5000.0)));
+        assert_eq!(max_allowed_on_ms(30001.0, &RDP4000), None);
+    }
+
+    #[test]
+    fn ranks_feasible_combos_best_first() {
+        let fresh = |_| 0.0;
+        let ranked = rank_combos(Process::Mma, 200.0, DEFAULT_MAX_REL_ERROR, &fresh);
+        let best = &ranked[0];
+        assert_close(best.u2_v, 28.0);
+        assert!(!best.out_of_tolerance);
+        assert!(best.abs_err_r <= DEFAULT_MAX_REL_ERROR);
+        assert!(ranked
+            .windows(2)
+            .all(|w| compare_candidates(&w[0], &w[1]).is_le()));
+
+        // every time-limited branch spent its budget: continuous combos only
+        let spent = |_| f64::MAX;
+        let ranked = rank_combos(Process::Mma, 200.0, DEFAULT_MAX_REL_ERROR, &spent);
+        assert!(!ranked.is_empty());
+        assert!(ranked.iter().all(|c| c.max_on_ms.is_none()));
+
+        assert!(rank_combos(Process::Mma, 0.0, DEFAULT_MAX_REL_ERROR, &fresh).is_empty());
+        assert!(rank_combos(Process::Mma, f64::NAN, DEFAULT_MAX_REL_ERROR, &fresh).is_empty());
+    }
+}
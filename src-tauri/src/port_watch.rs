@@ -0,0 +1,68 @@
+// Background serial port watcher (hot-plug), independent of the load-bank runtime.
+//
+// Lists the ports every WATCH_EVERY_MS and emits
+// - `ports/added` / `ports/removed` once per port, payload = full SerialPortInfo
+//   (a port whose USB identity changed under the same name counts as both)
+// - `lb/ports` with the sorted names whenever the set changes
+// and tells the load-bank runtimes, so a pulled cable drops the bank right
+// away and a plugged one is tried without waiting for the next scan.
+
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::time::interval;
+
+use crate::lb_link::{list_ports_detailed, SerialPortInfo};
+use crate::lb_runtime::{LoadBankRuntimeState, PortsEvent};
+
+const WATCH_EVERY_MS: u64 = 500;
+
+/// (added, removed) between two listings.
+pub fn diff_ports(
+    old: &[SerialPortInfo],
+    new: &[SerialPortInfo],
+) -> (Vec<SerialPortInfo>, Vec<SerialPortInfo>) {
+    let added = new.iter().filter(|p| !old.contains(p)).cloned().collect();
+    let removed = old.iter().filter(|p| !new.contains(p)).cloned().collect();
+    (added, removed)
+}
+
+pub fn start_port_watch(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut ticker = interval(Duration::from_millis(WATCH_EVERY_MS));
+        // first listing is the baseline (the UI asks `list_ports_detailed` on mount)
+        let mut known: Option<Vec<SerialPortInfo>> = None;
+        loop {
+            ticker.tick().await;
+            // enumeration may hit the OS / udev, keep it off the async workers
+            let Ok(mut ports) = tauri::async_runtime::spawn_blocking(list_ports_detailed).await
+            else {
+                continue;
+            };
+            ports.sort_by(|a, b| a.port_name.cmp(&b.port_name));
+
+            let Some(old) = known.as_ref() else {
+                known = Some(ports);
+                continue;
+            };
+            let (added, removed) = diff_ports(old, &ports);
+            if added.is_empty() && removed.is_empty() {
+                continue;
+            }
+
+            for p in &removed {
+                eprintln!("[PORTS] removed {} ({})", p.port_name, p.port_type);
+                let _ = app.emit("ports/removed", p.clone());
+            }
+            for p in &added {
+                eprintln!("[PORTS] added {} ({})", p.port_name, p.port_type);
+                let _ = app.emit("ports/added", p.clone());
+            }
+            let names: Vec<String> = ports.iter().map(|p| p.port_name.clone()).collect();
+            let _ = app.emit("lb/ports", PortsEvent { ports: names });
+
+            app.state::<LoadBankRuntimeState>()
+                .ports_changed(&added, &removed);
+            known = Some(ports);
+        }
+    });
+}
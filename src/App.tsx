@@ -8,7 +8,8 @@ import React, {
    useMemo 
 } from 'react';
 import { 
-   useDisclosure 
+   useDisclosure,
+   useHotkeys
 } from '@mantine/hooks';
 import { 
    AppShell,
@@ -57,6 +58,7 @@ import {
 } from '@/types/checklistTypes';
 // Global functionality
 import { initLoadBankMonitoring } from '@/services/hw/loadBankRuntimeStore';
+import { lbEmergencyStop } from '@/services/hw/lbProtocol';
 import { getInitialSubmission } from '@/dev/bootstrap';
 
 
@@ -169,6 +171,10 @@ const App: React.FC  = () => {
    }, []);
    // Estado da conexão banca
    useEffect(() => { initLoadBankMonitoring().catch(console.error); }, []);
+   // Paragem de emergência (Ctrl+Shift+E, também dentro de inputs)
+   useHotkeys([
+      ['mod+shift+E', () => { lbEmergencyStop(undefined, 'keyboard shortcut').catch(console.error); }],
+   ], []);
 
 
 
@@ -2,13 +2,40 @@ import { invoke } from "@tauri-apps/api/core";
 import { listen, type UnlistenFn } from "@tauri-apps/api/event";
 import { CRC8_TABLE, LB_FRAME_LEN } from "@/types/loadBankTypes";
 import type {
+   LoadBankAggregate,
+   LoadBankBudget,
+   LoadBankCaptureFile,
+   LoadBankCaptureInfo,
+   LoadBankDiscoveryPolicy,
+   LoadBankFaultEvent,
    LoadBankFrame,
    LoadBankHealth,
+   LoadBankInterlockEvent,
+   LoadBankKnownHost,
+   LoadBankLinkStats,
+   LoadBankEstopEvent,
+   LoadBankLogFilter,
+   LoadBankLogInfo,
+   LoadBankLogRow,
+   LoadBankMaintenanceReport,
+   LoadBankMaskLoad,
+   LoadBankProfile,
+   LoadBankReplayReport,
+   LoadBankRuntimeSnapshot,
+   LoadBankSafetyConfig,
+   LoadBankSequenceCapture,
+   LoadBankSequenceProgress,
+   LoadBankSetpointResolution,
    LoadBankStatus,
+   LoadBankWearThresholds,
+   SerialConfig,
    SerialRxChunk,
    SerialTxChunk,
    PortsEvent,
+   PortChange,
+   SerialPortInfo,
 } from "@/types/loadBankTypes";
+import type { Process } from "@/types/checklistTypes";
 import { DEV_ECHO_BAUD } from "@/dev/devConfig";
 import { toHex } from "../utils/generalUtils";
 
@@ -93,24 +120,254 @@ export function parseLoadBankFrame(rx: Uint8Array): LoadBankFrame | null {
 // Backend runtime control
 // -----------------------------------------------------------------------------
 
-export async function lbEnsureRuntimeAuto(opts?: { baud?: number }) {
+// serial: applies to ports without a saved config (AUTO) / is saved for the port (fixed)
+export async function lbEnsureRuntimeAuto(opts?: { baud?: number; serial?: Partial<SerialConfig> }) {
    await ensureListeners();
-   await invoke("lb_start_polling", { portName: "", baud: opts?.baud ?? DEV_ECHO_BAUD });
+   await invoke("lb_start_polling", { portName: "", baud: opts?.baud ?? DEV_ECHO_BAUD, serial: opts?.serial });
 }
 
-export async function lbEnsureRuntimeFixed(portName: string, opts?: { baud?: number }) {
+export async function lbEnsureRuntimeFixed(
+   portName: string,
+   opts?: { baud?: number; serial?: Partial<SerialConfig> },
+) {
    await ensureListeners();
-   await invoke("lb_start_polling", { portName, baud: opts?.baud ?? DEV_ECHO_BAUD });
+   await invoke("lb_start_polling", { portName, baud: opts?.baud ?? DEV_ECHO_BAUD, serial: opts?.serial });
 }
 
-export async function lbStopRuntime() {
-   await invoke("lb_stop_polling").catch(() => {});
+// Snapshot straight from the runtime (bankId omitted => default bank)
+export async function lbGetState(bankId?: string) {
+   return invoke<LoadBankRuntimeSnapshot>("lb_get_state", { bankId });
+}
+
+// bankId omitted => stops every bank
+export async function lbStopRuntime(bankId?: string) {
+   await invoke("lb_stop_polling", { bankId }).catch(() => {});
+}
+
+// Multi-bank: one runtime per bankId (port "" = AUTO, skips ports held by other banks)
+export async function lbEnsureRuntimeBank(
+   bankId: string,
+   portName: string,
+   opts?: { baud?: number; serial?: Partial<SerialConfig> },
+) {
+   await ensureListeners();
+   await invoke("lb_start_polling", { portName, baud: opts?.baud ?? DEV_ECHO_BAUD, bankId, serial: opts?.serial });
+}
+
+// Serial line settings per port (used the next time the port is opened)
+export async function lbGetSerialConfig(portName: string) {
+   return invoke<SerialConfig>("lb_get_serial_config", { portName });
+}
+
+export async function lbListSerialConfigs() {
+   return invoke<Record<string, SerialConfig>>("lb_list_serial_configs");
+}
+
+export async function lbSetSerialConfig(portName: string, config: Partial<SerialConfig>) {
+   return invoke<SerialConfig>("lb_set_serial_config", { portName, config });
+}
+
+export async function lbForgetSerialConfig(portName: string) {
+   await invoke("lb_forget_serial_config", { portName });
+}
+
+// Current ports with USB info (baseline for subscribePortChanges)
+export async function lbListPortsDetailed() {
+   return invoke<SerialPortInfo[]>("list_ports_detailed");
+}
+
+// AUTO port discovery: known adapters first, the rest only with blindScan
+export async function lbGetDiscoveryPolicy() {
+   return invoke<LoadBankDiscoveryPolicy>("lb_get_discovery_policy");
+}
+
+export async function lbSetDiscoveryPolicy(policy: LoadBankDiscoveryPolicy) {
+   return invoke<LoadBankDiscoveryPolicy>("lb_set_discovery_policy", { policy });
+}
+
+export async function lbListKnownHosts() {
+   return invoke<LoadBankKnownHost[]>("lb_list_known_hosts");
+}
+
+export async function lbForgetKnownHost(serialNumber: string) {
+   await invoke("lb_forget_known_host", { serialNumber });
+}
+
+// One pass over every port (AUTO runtimes only, e.g. first setup of a station)
+export async function lbScanAllPorts(bankId?: string) {
+   await invoke("lb_scan_all_ports", { bankId });
+}
+
+export async function lbAggregateStatus(): Promise<LoadBankAggregate> {
+   return invoke<LoadBankAggregate>("lb_aggregate_status");
+}
+
+// Combined mask across banks (same bank order as lbAggregateStatus)
+export async function lbSetContactorsCombined(mask: number) {
+   await invoke("lb_set_contactors_combined", { mask });
 }
 
 export async function lbSetPolling(enabled: boolean, intervalMs: number) {
    await invoke("lb_set_polling", { enabled, intervalMs });
 }
 
+export async function lbSetKeepalive(cfg: {
+   enabled: boolean;
+   probeAfterMs: number;
+   degradedAfterMs: number;
+   offlineAfterMs: number;
+   // proposed firmware watchdog, leave off until the firmware has it
+   feedWatchdog?: boolean;
+}) {
+   await invoke("lb_set_keepalive", cfg);
+}
+
+// Safety interlocks (backend refuses / auto-opens; see "lb/interlock")
+export async function lbSetSafety(config: LoadBankSafetyConfig) {
+   await invoke("lb_set_safety", { config });
+}
+
+export async function lbGetSafety(): Promise<LoadBankSafetyConfig> {
+   return invoke<LoadBankSafetyConfig>("lb_get_safety");
+}
+
+// Emergency stop: opens everything ahead of queued commands and latches
+// (contactor commands refused until lbResetEstop). No bankId => every bank.
+export async function lbEmergencyStop(bankId?: string, reason?: string) {
+   await invoke("lb_emergency_stop", { bankId, reason });
+}
+
+export async function lbResetEstop(bankId?: string) {
+   await invoke("lb_reset_estop", { bankId });
+}
+
+// Backend load model (same solver as services/utils/setpoints.ts)
+export async function lbResolveSetpoint(
+   process: Process,
+   currentA: number,
+   opts?: { maxRelError?: number; limit?: number }
+): Promise<LoadBankSetpointResolution> {
+   return invoke<LoadBankSetpointResolution>("lb_resolve_setpoint", {
+      process,
+      currentA,
+      maxRelError: opts?.maxRelError,
+      limit: opts?.limit,
+   });
+}
+
+// Load profile run by the backend worker (timing doesn't depend on the webview)
+export async function lbSequenceStart(profile: LoadBankProfile, bankId?: string) {
+   return invoke<LoadBankSequenceProgress>("lb_sequence_start", { profile, bankId });
+}
+
+export async function lbSequencePause(bankId?: string) {
+   return invoke<LoadBankSequenceProgress>("lb_sequence_pause", { bankId });
+}
+
+export async function lbSequenceResume(bankId?: string) {
+   return invoke<LoadBankSequenceProgress>("lb_sequence_resume", { bankId });
+}
+
+// Stops the profile and opens the contactors
+export async function lbSequenceAbort(bankId?: string) {
+   return invoke<LoadBankSequenceProgress>("lb_sequence_abort", { bankId });
+}
+
+export async function lbSequenceStatus(bankId?: string) {
+   return invoke<LoadBankSequenceProgress | null>("lb_sequence_status", { bankId });
+}
+
+export async function lbExplainMask(mask: number, u2V: number): Promise<LoadBankMaskLoad> {
+   return invoke<LoadBankMaskLoad>("lb_explain_mask", { mask: clampU16(mask), u2V });
+}
+
+// Remaining ON time per branch (backend owns the budget; also "lb/budget")
+export async function lbGetBudget(bankId?: string): Promise<LoadBankBudget> {
+   return invoke<LoadBankBudget>("lb_get_budget", { bankId });
+}
+
+// Traffic capture (JSONL under app data; on by default)
+export async function lbCaptureStart(opts?: { maxFileKb?: number; maxFiles?: number }) {
+   return invoke<LoadBankCaptureInfo>("lb_capture_start", {
+      maxFileKb: opts?.maxFileKb,
+      maxFiles: opts?.maxFiles,
+   });
+}
+
+export async function lbCaptureStop() {
+   return invoke<LoadBankCaptureInfo>("lb_capture_stop");
+}
+
+export async function lbCaptureStatus() {
+   return invoke<LoadBankCaptureInfo>("lb_capture_status");
+}
+
+export async function lbCaptureList() {
+   return invoke<LoadBankCaptureFile[]>("lb_capture_list");
+}
+
+// names empty => every capture; destPath from a save dialog (.zip)
+export async function lbCaptureExport(names: string[], destPath: string) {
+   return invoke<LoadBankCaptureFile>("lb_capture_export", { names, destPath });
+}
+
+// Plays a capture file through the runtime (same events as a live bank).
+// speed > 1 => accelerated; port picks one port of a multi-bank capture
+export async function lbEnsureRuntimeReplay(
+   path: string,
+   opts?: { speed?: number; port?: string; bankId?: string },
+) {
+   const query = [
+      opts?.speed != null ? `speed=${opts.speed}` : null,
+      opts?.port ? `port=${opts.port}` : null,
+   ].filter(Boolean).join("&");
+   const portName = `replay://${path}${query ? `?${query}` : ""}`;
+   await ensureListeners();
+   await invoke("lb_start_polling", { portName, baud: DEV_ECHO_BAUD, bankId: opts?.bankId });
+}
+
+// Parser regression check: re-decodes the capture's RX bytes against its recorded frames
+export async function lbReplayCheck(path: string, port?: string) {
+   return invoke<LoadBankReplayReport>("lb_replay_check", { path, port });
+}
+
+// Event log (health / handshake / fault / contactor history)
+export async function lbEventLogInfo() {
+   return invoke<LoadBankLogInfo>("lb_eventlog_info");
+}
+
+export async function lbEventLogSetStation(station: string) {
+   return invoke<string>("lb_eventlog_set_station", { station });
+}
+
+// newest first
+export async function lbEventLogQuery(filter?: LoadBankLogFilter) {
+   return invoke<LoadBankLogRow[]>("lb_eventlog_query", { filter });
+}
+
+// destPath from a save dialog (.xlsx); resolves to the number of rows written
+export async function lbEventLogExportXlsx(destPath: string, filter?: LoadBankLogFilter) {
+   return invoke<number>("lb_eventlog_export_xlsx", { filter, destPath });
+}
+
+// Contactor wear (counters are flushed by the runtime every ~10 s)
+export async function lbMaintenanceReport(bankId?: string, bankNo?: number) {
+   return invoke<LoadBankMaintenanceReport>("lb_maintenance_report", { bankId, bankNo });
+}
+
+export async function lbGetWearThresholds() {
+   return invoke<LoadBankWearThresholds>("lb_get_wear_thresholds");
+}
+
+export async function lbSetWearThresholds(thresholds: LoadBankWearThresholds) {
+   return invoke<LoadBankWearThresholds>("lb_set_wear_thresholds", { thresholds });
+}
+
+// after replacing a contactor (bit 0 => C1)
+export async function lbResetContactorWear(bankId: string, bankNo: number, bit: number) {
+   await invoke("lb_reset_contactor_wear", { bankId, bankNo, bit });
+}
+
 // Raw send
 export async function lbWriteBytes(bytes: Uint8Array) {
    console.log("[LB/TX]", toHex(bytes));
@@ -123,6 +380,20 @@ export async function lbSetContactors(mask: number) {
    await invoke("lb_set_contactors", { mask: m });
 }
 
+// Production: resolves with the status frame confirming the mask
+// (rejects with ContactorCmdError on timeout / mismatch)
+export async function lbSetContactorsConfirmed(
+   mask: number,
+   opts?: { timeoutMs?: number; retries?: number }
+): Promise<LoadBankStatus> {
+   const m = clampU16(mask);
+   return invoke<LoadBankStatus>("lb_set_contactors_confirmed", {
+      mask: m,
+      timeoutMs: opts?.timeoutMs,
+      retries: opts?.retries,
+   });
+}
+
 // -----------------------------------------------------------------------------
 // Event bus (frontend is event-driven; no polling loops here)
 // -----------------------------------------------------------------------------
@@ -130,14 +401,30 @@ export async function lbSetContactors(mask: number) {
 type StatusCb = (s: LoadBankStatus) => void;
 type HealthCb = (h: LoadBankHealth) => void;
 type PortsCb = (p: PortsEvent) => void;
+type PortChangeCb = (c: PortChange) => void;
 type RxCb = (c: SerialRxChunk) => void;
 type TxCb = (c: SerialTxChunk) => void;
+type InterlockCb = (e: LoadBankInterlockEvent) => void;
+type BudgetCb = (b: LoadBankBudget) => void;
+type FaultCb = (e: LoadBankFaultEvent) => void;
+type LinkStatsCb = (s: LoadBankLinkStats) => void;
+type SequenceCb = (p: LoadBankSequenceProgress) => void;
+type SequenceCaptureCb = (c: LoadBankSequenceCapture) => void;
+type EstopCb = (e: LoadBankEstopEvent) => void;
 
 const statusCbs = new Set<StatusCb>();
 const healthCbs = new Set<HealthCb>();
 const portsCbs = new Set<PortsCb>();
+const portChangeCbs = new Set<PortChangeCb>();
 const rxCbs = new Set<RxCb>();
 const txCbs = new Set<TxCb>();
+const interlockCbs = new Set<InterlockCb>();
+const budgetCbs = new Set<BudgetCb>();
+const faultCbs = new Set<FaultCb>();
+const linkStatsCbs = new Set<LinkStatsCb>();
+const sequenceCbs = new Set<SequenceCb>();
+const sequenceCaptureCbs = new Set<SequenceCaptureCb>();
+const estopCbs = new Set<EstopCb>();
 
 let lastStatus: LoadBankStatus | null = null;
 let lastHealth: LoadBankHealth | null = null;
@@ -171,6 +458,18 @@ async function ensureListeners() {
          })
       );
 
+      unlistenFns.push(
+         await listen<SerialPortInfo>("ports/added", (e) => {
+         for (const cb of portChangeCbs) cb({ kind: "added", port: e.payload });
+         })
+      );
+
+      unlistenFns.push(
+         await listen<SerialPortInfo>("ports/removed", (e) => {
+         for (const cb of portChangeCbs) cb({ kind: "removed", port: e.payload });
+         })
+      );
+
       unlistenFns.push(
          await listen<SerialRxChunk>("lb/rx", (e) => {
          for (const cb of rxCbs) cb(e.payload);
@@ -182,6 +481,55 @@ async function ensureListeners() {
          for (const cb of txCbs) cb(e.payload);
          })
       );
+
+      unlistenFns.push(
+         await listen<LoadBankInterlockEvent>("lb/interlock", (e) => {
+         console.warn("[LB/SAFETY]", e.payload.action, e.payload.message);
+         for (const cb of interlockCbs) cb(e.payload);
+         })
+      );
+
+      unlistenFns.push(
+         await listen<LoadBankBudget>("lb/budget", (e) => {
+         for (const cb of budgetCbs) cb(e.payload);
+         })
+      );
+
+      unlistenFns.push(
+         await listen<LoadBankFaultEvent>("lb/fault", (e) => {
+         console.warn("[LB/FAULT]", e.payload.edge, e.payload.fault.message);
+         for (const cb of faultCbs) cb(e.payload);
+         })
+      );
+
+      unlistenFns.push(
+         await listen<LoadBankLinkStats>("lb/link-stats", (e) => {
+         for (const cb of linkStatsCbs) cb(e.payload);
+         })
+      );
+
+      unlistenFns.push(
+         await listen<LoadBankSequenceProgress>("lb/sequence", (e) => {
+         for (const cb of sequenceCbs) cb(e.payload);
+         })
+      );
+
+      unlistenFns.push(
+         await listen<LoadBankSequenceCapture>("lb/sequence-capture", (e) => {
+         for (const cb of sequenceCaptureCbs) cb(e.payload);
+         })
+      );
+
+      unlistenFns.push(
+         await listen<LoadBankEstopEvent>("lb/estop", (e) => {
+         console.warn("[LB/ESTOP]", e.payload.bankId, e.payload.state, e.payload.reason);
+         for (const cb of estopCbs) cb(e.payload);
+         })
+      );
+
+      // after a webview reload: start from the runtime's last status, not blind
+      const snap = await lbGetState().catch(() => null);
+      if (snap?.status && !lastStatus) lastStatus = snap.status;
    })();
 
    return listenersReady;
@@ -220,6 +568,13 @@ export async function subscribePorts(cb: PortsCb): Promise<() => void> {
    return () => portsCbs.delete(cb);
 }
 
+// Hot-plug: one call per port plugged in / pulled out (full USB info)
+export async function subscribePortChanges(cb: PortChangeCb): Promise<() => void> {
+   await ensureListeners();
+   portChangeCbs.add(cb);
+   return () => portChangeCbs.delete(cb);
+}
+
 export async function subscribeRx(cb: RxCb): Promise<() => void> {
    await ensureListeners();
    rxCbs.add(cb);
@@ -232,6 +587,48 @@ export async function subscribeTx(cb: TxCb): Promise<() => void> {
    return () => txCbs.delete(cb);
 }
 
+export async function subscribeInterlock(cb: InterlockCb): Promise<() => void> {
+   await ensureListeners();
+   interlockCbs.add(cb);
+   return () => interlockCbs.delete(cb);
+}
+
+export async function subscribeBudget(cb: BudgetCb): Promise<() => void> {
+   await ensureListeners();
+   budgetCbs.add(cb);
+   return () => budgetCbs.delete(cb);
+}
+
+export async function subscribeFault(cb: FaultCb): Promise<() => void> {
+   await ensureListeners();
+   faultCbs.add(cb);
+   return () => faultCbs.delete(cb);
+}
+
+export async function subscribeLinkStats(cb: LinkStatsCb): Promise<() => void> {
+   await ensureListeners();
+   linkStatsCbs.add(cb);
+   return () => linkStatsCbs.delete(cb);
+}
+
+export async function subscribeSequence(cb: SequenceCb): Promise<() => void> {
+   await ensureListeners();
+   sequenceCbs.add(cb);
+   return () => sequenceCbs.delete(cb);
+}
+
+export async function subscribeSequenceCapture(cb: SequenceCaptureCb): Promise<() => void> {
+   await ensureListeners();
+   sequenceCaptureCbs.add(cb);
+   return () => sequenceCaptureCbs.delete(cb);
+}
+
+export async function subscribeEstop(cb: EstopCb): Promise<() => void> {
+   await ensureListeners();
+   estopCbs.add(cb);
+   return () => estopCbs.delete(cb);
+}
+
 // Await a status that matches a mask
 export async function waitForLoadBankMask(expectedMask: number, cfg: { timeoutMs?: number } = {}) {
    const timeoutMs = cfg.timeoutMs ?? 2000;
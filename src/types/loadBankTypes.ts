@@ -1,19 +1,21 @@
-import { Unit } from "./checklistTypes";
+import { Process, Unit } from "./checklistTypes";
 
 
 /* ──────────────────────────────────────────────────────────────────────────────
    Load bank protocol constants
 ────────────────────────────────────────────────────────────────────────────── */
 /**
- * Wire frame length (NO START/STOP encoding):
+ * Wire frame length (NO START/STOP encoding), protocol v1:
  *  - 14 bytes payload + 1 byte CRC8 = 15 bytes total
+ * v2 appends bankHealth (u8) at 14 and moves the CRC to 15 (16 bytes);
+ * the backend codec picks the layout from the version byte.
  *
  * Payload layout (indexes):
  *  0  version (u8)
  *  1  bankPower_hi
  *  2  bankPower_lo
  *  3  bankNo (u8)
- *  3  bankHealth (u8)
+ *  4  handshake (u8)
  *  5  contactorsMask_hi
  *  6  contactorsMask_lo
  *  7  errContactors_hi
@@ -91,24 +93,66 @@ export type LoadBankProbe =
 export type SerialPortInfo = {
    portName: string;
    portType: string;
-   vId?: Uint16Array;
-   pId?: Uint16Array;
-   serialNumber?: string;
-   manufacturer?:  string;
-   product?: string;
+   vid: number | null;
+   pid: number | null;
+   serialNumber: string | null;
+   manufacturer: string | null;
+   product: string | null;
+};
+
+// port watcher (hot-plug): `ports/added` / `ports/removed`
+export type PortChange = {
+   kind: "added" | "removed";
+   port: SerialPortInfo;
 };
 
 export type PortsEvent = {
    ports: string[];
 };
 
+export type SerialParity = "none" | "odd" | "even";
+export type SerialStopBits = "one" | "two";
+export type SerialFlowControl = "none" | "software" | "hardware";
+export type SerialConfig = { // backend lb_serial (saved per port; omitted fields => defaults, 8N1)
+   dataBits: number; // 5..8
+   parity: SerialParity;
+   stopBits: SerialStopBits;
+   flowControl: SerialFlowControl;
+   dtr: boolean | null; // level set after open, null => driver default
+   rts: boolean | null;
+   readTimeoutMs: number;
+};
+
+export type UsbId = {
+   vid: number;
+   pid: number;
+   serialNumber?: string | null; // null => any adapter with this VID/PID
+};
+
+export type LoadBankDiscoveryPolicy = { // backend lb_discovery (AUTO port ranking)
+   known: UsbId[];
+   blindScan: boolean; // also try ports matching nothing
+};
+
+export type LoadBankKnownHost = { // USB adapter a bank was last adopted on
+   serialNumber: string;
+   vid: number | null;
+   pid: number | null;
+   bankNo: number;
+   bankId: string;
+   portName: string;
+   lastSeen: string;
+};
+
 // new stuff - reflects rust
 export type SerialRxChunk = {
+   bankId: string;
    portName: string;
    bytes: number[];
    hex: Uint8Array;
 };
 export type SerialTxChunk = {
+   bankId: string;
    portName: string;
    bytes: number[];
    hex: Uint8Array;
@@ -127,17 +171,346 @@ export type LoadBankFrame = {
    errFans: number;
    errThermals: number;
    otherErrors: number;   
+   bankHealth?: number | null; // v2+ firmware
 };
 
 export type LoadBankStatus = LoadBankFrame & {
+   bankId: string;
    portName: string;
+   faults: LoadBankFault[]; // error bitfields decoded by the backend, most severe first
    rawFrameHex?: string;
 };
+export type LoadBankFaultSeverity = "warning" | "critical";
+export type LoadBankFault = { // backend lb_faults
+   code: string; // "contactor:C3", "fan:T2", "thermal:T1", "other:7"
+   kind: "contactor" | "fan" | "thermal" | "other"; // other: undocumented otherErrors bit
+   bit: number;
+   severity: LoadBankFaultSeverity;
+   message: string;
+   branch: string | null;
+   tunnel: number | null; // 1-based
+};
+export type LoadBankFaultEvent = {
+   t: string;
+   bankId: string;
+   portName: string;
+   edge: "raised" | "cleared";
+   fault: LoadBankFault;
+};
+export type LoadBankLinkState = "online" | "degraded" | "offline";
 export type LoadBankHealth = { // connection health
+   bankId: string;
    portName: string;
    online: boolean;
+   link: LoadBankLinkState;
    lastSeenMs: number;
    reason?: string | null;
+   protocolVersion?: number | null; // negotiated with the connected bank
+   unsupportedVersion?: number | null; // firmware we refused to pair with
+};
+export type LoadBankInterlock =
+   | { kind: "offline" }
+   | { kind: "thermalFault"; errThermals: number }
+   | { kind: "fanFault"; errFans: number }
+   | { kind: "unwiredContactors"; mask: number; allowedMask: number }
+   | { kind: "overPower"; mask: number; estimatedW: number; maxW: number }
+   | { kind: "overload"; branch: string; factor: number }
+   | { kind: "budgetExhausted"; branch: string; usedOnMs: number; allowedOnMs: number }
+   | { kind: "emergencyStop" }; // until lb_reset_estop
+export type LoadBankInterlockEvent = {
+   bankId: string;
+   portName: string;
+   action: "refused" | "autoOpen";
+   mask: number;
+   interlock: LoadBankInterlock;
+   message: string;
+};
+export type LoadBankSafetyConfig = { // backend interlocks
+   enabled: boolean;
+   openOnThermal: boolean;
+   openOnFans: boolean;
+   allowedMask: number;
+   maxPowerW: number | null; // present DUT, null = no limit
+   u2MaxV: number;
+};
+export type LoadBankBranchBudget = {
+   id: string;
+   maskBit: number;
+   on: boolean;
+   factor: number;             // P_branch / P_R at u2V
+   usedOnMs: number;
+   allowedOnMs: number | null; // null => continuous
+   remainingOnMs: number | null;
+   forbidden: boolean;         // no overload window for this factor
+};
+export type LoadBankBudget = { // backend duty-cycle budget ("lb/budget")
+   bankId: string;
+   cycleMs: number;
+   u2V: number;
+   mask: number;
+   branches: LoadBankBranchBudget[];
+   exhausted: boolean;
+};
+export type LoadBankCaptureInfo = { // backend traffic recorder
+   recording: boolean;
+   dir: string | null;
+   session: string | null;
+   currentFile: string | null;
+   records: number;
+   maxFileBytes: number;
+   maxFiles: number;
+};
+export type LoadBankCaptureFile = {
+   name: string;
+   path: string;
+   sizeBytes: number;
+   modified: string | null;
+};
+export type LoadBankLogKind = "health" | "handshakeFailed" | "fault" | "contactors" | "sequence" | "emergencyStop" | "interlock";
+export type LoadBankLogRow = { // backend lb_eventlog
+   id: number;
+   t: string;
+   station: string;
+   bankId: string;
+   bankNo: number | null;
+   portName: string;
+   kind: LoadBankLogKind;
+   code: string | null;
+   state: string | null; // link state (health) / "raised" | "cleared" (fault) / "refused" (interlock)
+   severity: LoadBankFaultSeverity | null;
+   message: string;
+   mask: number | null;
+};
+export type LoadBankLogFilter = {
+   from?: string; // ISO date-time
+   to?: string;
+   bankNo?: number;
+   bankId?: string;
+   kinds?: LoadBankLogKind[];
+   limit?: number;
+};
+export type LoadBankLogInfo = {
+   enabled: boolean;
+   path: string | null;
+   station: string | null;
+   events: number;
+};
+export type LoadBankFrameMismatch = {
+   index: number; // among the recorded frames
+   recorded: LoadBankFrame;
+};
+export type LoadBankReplayReport = { // backend lb_replay_check
+   portName: string | null;
+   rxChunks: number;
+   rxBytes: number;
+   badLines: number;
+   recordedFrames: number;
+   decodedFrames: number;
+   extraDecoded: number;
+   decodeErrors: string[];
+   resyncs: number;
+   garbageBytes: number;
+   mismatches: LoadBankFrameMismatch[];
+   matches: boolean;
+};
+export type LoadBankWearThresholds = { // backend lb_wear (null => not checked)
+   maxCycles: number | null;
+   maxOnHours: number | null;
+   maxEnergyKwh: number | null;
+   dueFraction: number; // share of a limit from which a contactor is "due"
+};
+export type LoadBankWearStatus = "ok" | "due" | "replace";
+export type LoadBankContactorWear = {
+   bankId: string;
+   bankNo: number;
+   bit: number; // mask bit, 0 => C1
+   contactor: string; // "C1".."C16"
+   branch: string | null;
+   cycles: number;
+   onHours: number;
+   energyKwh: number;
+   since: string; // first count or last reset
+   updated: string;
+   wear: number; // highest used share of a limit (1 = reached)
+   status: LoadBankWearStatus;
+};
+export type LoadBankMaintenanceReport = {
+   generated: string;
+   thresholds: LoadBankWearThresholds;
+   contactors: LoadBankContactorWear[]; // most worn first
+   due: number;
+   replace: number;
+};
+export type LoadBankMaskLoad = { // backend load_model::explain_mask
+   mask: number;
+   branches: string[];
+   reqOhm: number | null;
+   u2V: number;
+   currentA: number;
+   powerW: number;
+};
+export type LoadBankComboCandidate = { // backend load_model (mirrors ComboCandidate)
+   mask: number;
+   branches: string[];
+   reqOhm: number;
+   u2V: number;
+   approxCurrentA: number;
+   errI: number;
+   absErrI: number;
+   errR: number;
+   absErrR: number;
+   score: number;
+   maxBranchKw: number;
+   maxBranchFactor: number;
+   maxTunnelKw: number;
+   maxOnMs: number | null; // null => continuous
+   cycleMs: number;
+   usedOnMsMax: number;
+   outOfTolerance: boolean;
+};
+export type LoadBankSetpointResolution = {
+   process: Process;
+   currentA: number;
+   u2V: number;
+   rTargetOhm: number;
+   candidates: LoadBankComboCandidate[]; // best first
+};
+export type ContactorCmdError =
+   | { kind: "notRunning" }
+   | { kind: "offline"; reason: string }
+   | { kind: "superseded" }
+   | { kind: "interlock"; interlock: LoadBankInterlock; message: string }
+   | { kind: "timeout"; requested: number; attempts: number }
+   | {
+         kind: "mismatch";
+         requested: number;
+         reported: number;
+         attempts: number;
+         errContactors: number;
+         status: LoadBankStatus;
+      };
+export type LoadBankCmdLatency = { // command queued -> bytes written, per bank
+   count: number;
+   lastUs: number;
+   maxUs: number;
+};
+export type LoadBankStepTarget =
+   | { kind: "mask"; mask: number }
+   | { kind: "current"; currentA: number; process: Process }; // resolved when the step starts
+export type LoadBankProfileStep = {
+   label?: string;
+   target: LoadBankStepTarget;
+   settleMs?: number; // after the status frame confirming the mask
+   dwellMs: number;
+   captureAtMs?: number[]; // offsets into the dwell -> "lb/sequence-capture"
+};
+export type LoadBankProfile = { // backend sequencer (lb_sequence_start)
+   name?: string;
+   steps: LoadBankProfileStep[];
+   maxRelError?: number;
+};
+export type LoadBankSequenceState = "running" | "paused" | "finished" | "aborted" | "failed";
+export type LoadBankSequenceProgress = { // backend "lb/sequence"
+   t: string;
+   bankId: string;
+   profile: string;
+   state: LoadBankSequenceState;
+   step: number; // 0-based
+   steps: number;
+   label: string | null;
+   phase: "switching" | "settling" | "dwelling";
+   mask: number | null;
+   phaseRemainingMs: number | null;
+   elapsedMs: number; // pauses excluded
+   reason: string | null;
+};
+export type LoadBankSequenceCapture = { // backend "lb/sequence-capture"
+   t: string;
+   bankId: string;
+   profile: string;
+   step: number;
+   label: string | null;
+   atMs: number;
+   mask: number;
+   status: LoadBankStatus | null;
+};
+export type LoadBankLinkCounters = { // since the runtime started
+   rxBytes: number;
+   framesOk: number;
+   crcErrors: number;
+   resyncs: number;
+   discardedBytes: number;
+   truncations: number;
+   truncatedBytes: number;
+   handshakeRetries: number;
+   reconnects: number;
+};
+export type LoadBankLinkRates = { // over the last ~10 s (windowMs)
+   windowMs: number;
+   rxBytesPerS: number;
+   framesPerS: number;
+   crcErrorsPerMin: number;
+   resyncsPerMin: number;
+   discardedBytesPerS: number;
+   crcErrorRatio: number | null; // null => no frames in the window
+};
+export type LoadBankLinkStats = { // backend "lb/link-stats", every second
+   t: string;
+   bankId: string;
+   portName: string | null;
+   counters: LoadBankLinkCounters;
+   rates: LoadBankLinkRates;
+};
+export type LoadBankEstopState = "latched" | "confirmed" | "released";
+export type LoadBankEstopEvent = { // backend "lb/estop"
+   t: string;
+   bankId: string;
+   portName: string | null;
+   state: LoadBankEstopState; // confirmed => a status frame reported all open
+   reason: string | null;
+   sends: number; // all-open frames sent so far
+};
+export type LoadBankHandshakeOutcome = {
+   t: string;
+   portName: string;
+   ok: boolean;
+   error: string | null;
+   protocolVersion: number | null;
+};
+export type LoadBankRuntimeSnapshot = { // backend lb_get_state
+   bankId: string;
+   mode: string; // "auto" | "fixed:<port>" | "sim" | "tcp:<addr>" | "replay:<spec>"
+   baud: number;
+   portName: string | null;
+   online: boolean;
+   link: LoadBankLinkState;
+   protocolVersion: number | null;
+   msSinceFrame: number | null; // null => no frame yet
+   msSinceStatus: number | null;
+   status: LoadBankStatus | null;
+   poll: { enabled: boolean; intervalMs: number };
+   framesOk: number;
+   crcErrors: number;
+   resyncs: number;
+   lastHandshake: LoadBankHandshakeOutcome | null;
+   cmdLatency: LoadBankCmdLatency;
+   linkStats: LoadBankLinkStats | null;
+   estop: LoadBankEstopEvent | null; // null => not latched
+};
+export type LoadBankBankSummary = {
+   bankId: string;
+   mode: string;
+   portName: string | null;
+   online: boolean;
+   status: LoadBankStatus | null;
+   cmdLatency: LoadBankCmdLatency;
+};
+export type LoadBankAggregate = { // several banks as one network
+   banks: LoadBankBankSummary[];
+   allOnline: boolean;
+   totalBankPower: number;
+   combinedMask: number; // bank i => bits 16*i..16*i+15
+   hasErrors: boolean;
 };
 export type LoadBankLive = {
    portName: string | null;